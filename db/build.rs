@@ -0,0 +1,5 @@
+// `sqlx::migrate!` embeds the migrations, so new or changed ones have to
+// trigger a rebuild.
+fn main() {
+    println!("cargo:rerun-if-changed=migrations");
+}
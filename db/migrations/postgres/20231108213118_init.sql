@@ -0,0 +1,104 @@
+-- # Entity schema.
+
+create table if not exists users (
+    id bigint generated by default as identity primary key,
+    username text not null unique,
+    password text not null
+);
+
+create table if not exists avatars (
+    id bigint generated by default as identity primary key,
+    img_data bytea not null,
+    user_id bigint references users(id)
+);
+
+create table if not exists articles (
+    id bigint generated by default as identity primary key,
+    user_id bigint references users(id),
+    title text not null,
+    editor_content jsonb not null,
+    content text not null
+);
+
+create table if not exists categories (
+    id bigint generated by default as identity primary key,
+    title text not null,
+    content text
+);
+
+create table if not exists threads (
+    id bigint generated by default as identity primary key,
+    category_id bigint references categories(id),
+    user_id bigint references users(id),
+    title text not null
+);
+
+create table if not exists posts (
+    id bigint generated by default as identity primary key,
+    thread_id bigint references threads(id),
+    user_id bigint references users(id),
+    title text not null,
+    content text not null
+);
+
+create table if not exists groups (
+    id bigint generated by default as identity primary key,
+    name text not null unique
+);
+
+create table if not exists permissions (
+    id bigint generated by default as identity primary key,
+    name text not null unique
+);
+
+
+-- # Join tables.
+
+create table if not exists users_groups (
+    user_id bigint references users(id),
+    group_id bigint references groups(id),
+    primary key (user_id, group_id)
+);
+
+create table if not exists groups_permissions (
+    group_id bigint references groups(id),
+    permission_id bigint references permissions(id),
+    primary key (group_id, permission_id)
+);
+
+
+-- # Fixture hydration, matching the SQLite migrations.
+
+insert into users (username, password)
+values (
+    'ferris',
+    '$argon2id$v=19$m=19456,t=2,p=1$VE0e3g7DalWHgDwou3nuRA$uC6TER156UQpk0lNQ5+jHM0l5poVjPA1he/Tyn9J4Zw'
+), (
+    'admin',
+    '$argon2id$v=19$m=19456,t=2,p=1$VE0e3g7DalWHgDwou3nuRA$uC6TER156UQpk0lNQ5+jHM0l5poVjPA1he/Tyn9J4Zw'
+);
+
+insert into groups (name) values ('users'), ('superusers');
+
+insert into permissions (name) values ('protected.read'), ('restricted.read');
+
+insert into groups_permissions (group_id, permission_id)
+values (
+    (select id from groups where name = 'users'),
+    (select id from permissions where name = 'protected.read')
+), (
+    (select id from groups where name = 'superusers'),
+    (select id from permissions where name = 'restricted.read')
+);
+
+insert into users_groups (user_id, group_id)
+values (
+    (select id from users where username = 'ferris'),
+    (select id from groups where name = 'users')
+), (
+    (select id from users where username = 'admin'),
+    (select id from groups where name = 'users')
+), (
+    (select id from users where username = 'admin'),
+    (select id from groups where name = 'superusers')
+);
@@ -0,0 +1,9 @@
+-- See the SQLite migration of the same name.
+create table if not exists user_identities (
+    id bigint generated by default as identity primary key,
+    user_id bigint not null references users(id) on delete cascade,
+    provider text not null,
+    subject text not null,
+    created_at bigint not null,
+    unique (provider, subject)
+);
@@ -0,0 +1,21 @@
+-- See the SQLite migration of the same name. The session data lives in
+-- `tower_sessions.session`, created by the session store.
+create table if not exists user_sessions (
+    id bigint generated by default as identity primary key,
+    session_id text not null unique,
+    user_id bigint not null references users(id) on delete cascade,
+    created_at bigint not null,
+    last_seen_at bigint not null,
+    user_agent text,
+    ip text
+);
+
+create index if not exists user_sessions_user_id on user_sessions(user_id);
+
+insert into permissions (name) values ('sessions.manage');
+
+insert into groups_permissions (group_id, permission_id)
+values (
+    (select id from groups where name = 'superusers'),
+    (select id from permissions where name = 'sessions.manage')
+);
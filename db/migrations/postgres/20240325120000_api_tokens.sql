@@ -0,0 +1,12 @@
+-- See the SQLite migration of the same name.
+create table if not exists api_tokens (
+    id bigint generated by default as identity primary key,
+    user_id bigint not null references users(id) on delete cascade,
+    name text not null,
+    token_hash text not null unique,
+    scopes text not null,
+    created_at bigint not null,
+    last_used_at bigint
+);
+
+create index if not exists api_tokens_user_id on api_tokens(user_id);
@@ -0,0 +1,12 @@
+-- The post permissions, which the SQLite init migration failed to create.
+insert into permissions (name)
+values ('edit_own_post'), ('delete_own_post'), ('edit_any_post'), ('delete_any_post')
+on conflict do nothing;
+
+insert into groups_permissions (group_id, permission_id)
+values
+    ((select id from groups where name = 'users'), (select id from permissions where name = 'edit_own_post')),
+    ((select id from groups where name = 'users'), (select id from permissions where name = 'delete_own_post')),
+    ((select id from groups where name = 'superusers'), (select id from permissions where name = 'edit_any_post')),
+    ((select id from groups where name = 'superusers'), (select id from permissions where name = 'delete_any_post'))
+on conflict do nothing;
@@ -0,0 +1,19 @@
+-- Full-text search vectors over the titles and plain-text content of
+-- threads, posts and articles. Generated columns keep them in sync with
+-- every insert and update; titles weigh more than content.
+alter table threads add column search tsvector
+    generated always as (to_tsvector('english', title)) stored;
+
+alter table posts add column search tsvector
+    generated always as (
+        setweight(to_tsvector('english', title), 'A') || setweight(to_tsvector('english', content), 'B')
+    ) stored;
+
+alter table articles add column search tsvector
+    generated always as (
+        setweight(to_tsvector('english', title), 'A') || setweight(to_tsvector('english', content), 'B')
+    ) stored;
+
+create index if not exists threads_search on threads using gin (search);
+create index if not exists posts_search on posts using gin (search);
+create index if not exists articles_search on articles using gin (search);
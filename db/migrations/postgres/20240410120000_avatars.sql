@@ -0,0 +1,19 @@
+-- See the SQLite migration of the same name.
+alter table avatars rename to avatars_original;
+-- The new primary key index would clash with the old one's name.
+alter index avatars_pkey rename to avatars_original_pkey;
+
+create table avatars (
+    user_id bigint not null references users(id) on delete cascade,
+    size bigint not null,
+    content_type text not null,
+    img_data bytea not null,
+    primary key (user_id, size)
+);
+
+insert into avatars (user_id, size, content_type, img_data)
+select user_id, 0, 'application/octet-stream', img_data
+from avatars_original
+where id in (select max(id) from avatars_original where user_id is not null group by user_id);
+
+drop table avatars_original;
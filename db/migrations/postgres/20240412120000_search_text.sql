@@ -0,0 +1,23 @@
+-- Index the text of posts and articles instead of their HTML, so markup
+-- neither matches searches nor ends up in headlines. `search_text` is written
+-- along with `content`; rows from before this migration are filled in by
+-- `db::migrate`.
+alter table posts add column search_text text;
+alter table articles add column search_text text;
+
+-- Dropping the columns drops their indexes too.
+alter table posts drop column search;
+alter table articles drop column search;
+
+alter table posts add column search tsvector
+    generated always as (
+        setweight(to_tsvector('english', title), 'A') || setweight(to_tsvector('english', coalesce(search_text, '')), 'B')
+    ) stored;
+
+alter table articles add column search tsvector
+    generated always as (
+        setweight(to_tsvector('english', title), 'A') || setweight(to_tsvector('english', coalesce(search_text, '')), 'B')
+    ) stored;
+
+create index posts_search on posts using gin (search);
+create index articles_search on articles using gin (search);
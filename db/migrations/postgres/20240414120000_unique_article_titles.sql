@@ -0,0 +1,9 @@
+-- See the SQLite migration of the same name.
+update articles
+set title = title || ' (' || id || ')'
+where exists (
+    select 1 from articles as other
+    where other.user_id = articles.user_id and other.title = articles.title and other.id > articles.id
+);
+
+create unique index if not exists articles_user_id_title on articles (user_id, title);
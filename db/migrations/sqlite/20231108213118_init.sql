@@ -21,7 +21,7 @@ CREATE TABLE articles (
     user_id integer references users(id),
     title text not null,
     editor_content json not null,
-    content text not null,
+    content text not null
 );
 
 -- Creating the Posts table
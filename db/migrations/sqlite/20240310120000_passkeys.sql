@@ -0,0 +1,14 @@
+-- Create `passkeys` table holding the WebAuthn public key credentials
+-- registered by each user. `passkey` is the serialized credential as returned
+-- by the authenticator, `credential_id` its base64url encoded id.
+create table if not exists passkeys (
+    id integer primary key autoincrement,
+    user_id integer not null references users(id) on delete cascade,
+    credential_id text not null unique,
+    name text not null,
+    passkey text not null,
+    created_at integer not null,
+    last_used_at integer
+);
+
+create index if not exists passkeys_user_id on passkeys(user_id);
@@ -0,0 +1,11 @@
+-- Create `user_identities` table linking users to accounts at external
+-- identity providers. `subject` is the provider's stable identifier (the
+-- OIDC `sub` claim), which is unique per provider.
+create table if not exists user_identities (
+    id integer primary key autoincrement,
+    user_id integer not null references users(id) on delete cascade,
+    provider text not null,
+    subject text not null,
+    created_at integer not null,
+    unique (provider, subject)
+);
@@ -0,0 +1,22 @@
+-- Create `user_sessions` table tracking metadata about the authenticated
+-- sessions in `tower_sessions`, so users can see where they are logged in.
+create table if not exists user_sessions (
+    id integer primary key autoincrement,
+    session_id text not null unique,
+    user_id integer not null references users(id) on delete cascade,
+    created_at integer not null,
+    last_seen_at integer not null,
+    user_agent text,
+    ip text
+);
+
+create index if not exists user_sessions_user_id on user_sessions(user_id);
+
+-- Permission to list and revoke other users' sessions.
+insert into permissions (name) values ('sessions.manage');
+
+insert into groups_permissions (group_id, permission_id)
+values (
+    (select id from groups where name = 'superusers'),
+    (select id from permissions where name = 'sessions.manage')
+);
@@ -0,0 +1,13 @@
+-- Create `api_tokens` table for personal access tokens. Only a SHA-256 hash
+-- of the token is stored; `scopes` is a space separated list.
+create table if not exists api_tokens (
+    id integer primary key autoincrement,
+    user_id integer not null references users(id) on delete cascade,
+    name text not null,
+    token_hash text not null unique,
+    scopes text not null,
+    created_at integer not null,
+    last_used_at integer
+);
+
+create index if not exists api_tokens_user_id on api_tokens(user_id);
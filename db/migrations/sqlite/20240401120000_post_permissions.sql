@@ -0,0 +1,16 @@
+-- The init migration links the post permissions to groups without ever
+-- creating them, which left rows with a NULL `permission_id`. Create the
+-- permissions and link them properly.
+delete from groups_permissions where permission_id is null;
+
+insert or ignore into permissions (name) values ('edit_own_post');
+insert or ignore into permissions (name) values ('delete_own_post');
+insert or ignore into permissions (name) values ('edit_any_post');
+insert or ignore into permissions (name) values ('delete_any_post');
+
+insert or ignore into groups_permissions (group_id, permission_id)
+values
+    ((select id from groups where name = 'users'), (select id from permissions where name = 'edit_own_post')),
+    ((select id from groups where name = 'users'), (select id from permissions where name = 'delete_own_post')),
+    ((select id from groups where name = 'superusers'), (select id from permissions where name = 'edit_any_post')),
+    ((select id from groups where name = 'superusers'), (select id from permissions where name = 'delete_any_post'));
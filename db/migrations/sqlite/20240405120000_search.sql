@@ -0,0 +1,70 @@
+-- Full-text indexes over the titles and plain-text content of threads, posts
+-- and articles. They are external content tables: the text lives in the
+-- source tables and the triggers below keep the indexes in sync with every
+-- insert, update and delete.
+create virtual table if not exists threads_fts using fts5(
+    title,
+    content = 'threads',
+    content_rowid = 'id',
+    tokenize = 'porter unicode61 remove_diacritics 2'
+);
+
+create virtual table if not exists posts_fts using fts5(
+    title,
+    content,
+    content = 'posts',
+    content_rowid = 'id',
+    tokenize = 'porter unicode61 remove_diacritics 2'
+);
+
+create virtual table if not exists articles_fts using fts5(
+    title,
+    content,
+    content = 'articles',
+    content_rowid = 'id',
+    tokenize = 'porter unicode61 remove_diacritics 2'
+);
+
+create trigger if not exists threads_fts_insert after insert on threads begin
+    insert into threads_fts (rowid, title) values (new.id, new.title);
+end;
+
+create trigger if not exists threads_fts_delete after delete on threads begin
+    insert into threads_fts (threads_fts, rowid, title) values ('delete', old.id, old.title);
+end;
+
+create trigger if not exists threads_fts_update after update of title on threads begin
+    insert into threads_fts (threads_fts, rowid, title) values ('delete', old.id, old.title);
+    insert into threads_fts (rowid, title) values (new.id, new.title);
+end;
+
+create trigger if not exists posts_fts_insert after insert on posts begin
+    insert into posts_fts (rowid, title, content) values (new.id, new.title, new.content);
+end;
+
+create trigger if not exists posts_fts_delete after delete on posts begin
+    insert into posts_fts (posts_fts, rowid, title, content) values ('delete', old.id, old.title, old.content);
+end;
+
+create trigger if not exists posts_fts_update after update of title, content on posts begin
+    insert into posts_fts (posts_fts, rowid, title, content) values ('delete', old.id, old.title, old.content);
+    insert into posts_fts (rowid, title, content) values (new.id, new.title, new.content);
+end;
+
+create trigger if not exists articles_fts_insert after insert on articles begin
+    insert into articles_fts (rowid, title, content) values (new.id, new.title, new.content);
+end;
+
+create trigger if not exists articles_fts_delete after delete on articles begin
+    insert into articles_fts (articles_fts, rowid, title, content) values ('delete', old.id, old.title, old.content);
+end;
+
+create trigger if not exists articles_fts_update after update of title, content on articles begin
+    insert into articles_fts (articles_fts, rowid, title, content) values ('delete', old.id, old.title, old.content);
+    insert into articles_fts (rowid, title, content) values (new.id, new.title, new.content);
+end;
+
+-- Index everything written before this migration.
+insert into threads_fts (threads_fts) values ('rebuild');
+insert into posts_fts (posts_fts) values ('rebuild');
+insert into articles_fts (articles_fts) values ('rebuild');
@@ -0,0 +1,19 @@
+-- Store one row per resized copy of a user's avatar, replaced together on
+-- upload. Avatars from the original table are kept as uploaded with size 0,
+-- the newest one per user; the server resizes them when it starts.
+alter table avatars rename to avatars_original;
+
+create table avatars (
+    user_id integer not null references users(id) on delete cascade,
+    size integer not null,
+    content_type text not null,
+    img_data blob not null,
+    primary key (user_id, size)
+);
+
+insert into avatars (user_id, size, content_type, img_data)
+select user_id, 0, 'application/octet-stream', img_data
+from avatars_original
+where id in (select max(id) from avatars_original where user_id is not null group by user_id);
+
+drop table avatars_original;
@@ -0,0 +1,61 @@
+-- Index the text of posts and articles instead of their HTML, so markup
+-- neither matches searches nor ends up in snippets. `search_text` is written
+-- along with `content`; rows from before this migration are filled in by
+-- `db::migrate`, which fires the update triggers below.
+alter table posts add column search_text text;
+alter table articles add column search_text text;
+
+drop trigger if exists posts_fts_insert;
+drop trigger if exists posts_fts_delete;
+drop trigger if exists posts_fts_update;
+drop trigger if exists articles_fts_insert;
+drop trigger if exists articles_fts_delete;
+drop trigger if exists articles_fts_update;
+drop table if exists posts_fts;
+drop table if exists articles_fts;
+
+create virtual table posts_fts using fts5(
+    title,
+    search_text,
+    content = 'posts',
+    content_rowid = 'id',
+    tokenize = 'porter unicode61 remove_diacritics 2'
+);
+
+create virtual table articles_fts using fts5(
+    title,
+    search_text,
+    content = 'articles',
+    content_rowid = 'id',
+    tokenize = 'porter unicode61 remove_diacritics 2'
+);
+
+create trigger posts_fts_insert after insert on posts begin
+    insert into posts_fts (rowid, title, search_text) values (new.id, new.title, new.search_text);
+end;
+
+create trigger posts_fts_delete after delete on posts begin
+    insert into posts_fts (posts_fts, rowid, title, search_text) values ('delete', old.id, old.title, old.search_text);
+end;
+
+create trigger posts_fts_update after update of title, search_text on posts begin
+    insert into posts_fts (posts_fts, rowid, title, search_text) values ('delete', old.id, old.title, old.search_text);
+    insert into posts_fts (rowid, title, search_text) values (new.id, new.title, new.search_text);
+end;
+
+create trigger articles_fts_insert after insert on articles begin
+    insert into articles_fts (rowid, title, search_text) values (new.id, new.title, new.search_text);
+end;
+
+create trigger articles_fts_delete after delete on articles begin
+    insert into articles_fts (articles_fts, rowid, title, search_text) values ('delete', old.id, old.title, old.search_text);
+end;
+
+create trigger articles_fts_update after update of title, search_text on articles begin
+    insert into articles_fts (articles_fts, rowid, title, search_text) values ('delete', old.id, old.title, old.search_text);
+    insert into articles_fts (rowid, title, search_text) values (new.id, new.title, new.search_text);
+end;
+
+-- Index the titles now, the text follows once it is filled in.
+insert into posts_fts (posts_fts) values ('rebuild');
+insert into articles_fts (articles_fts) values ('rebuild');
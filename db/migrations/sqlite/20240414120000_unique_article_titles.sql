@@ -0,0 +1,11 @@
+-- Titles are unique per author, which `Article::upsert` relies on to update
+-- a draft in place. Older duplicates get their id appended first so the
+-- index can be built without losing any of them.
+update articles
+set title = title || ' (' || id || ')'
+where exists (
+    select 1 from articles as other
+    where other.user_id = articles.user_id and other.title = articles.title and other.id > articles.id
+);
+
+create unique index if not exists articles_user_id_title on articles (user_id, title);
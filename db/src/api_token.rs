@@ -0,0 +1,74 @@
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+
+use crate::DbPool;
+
+/// A personal API token. The token itself is only shown once on creation,
+/// we keep a hash of it to look it up.
+#[derive(Clone, Serialize, Deserialize, FromRow, Debug)]
+pub struct DbApiToken {
+    pub id: i64,
+    pub user_id: i64,
+    pub name: String,
+    #[serde(skip_serializing)]
+    pub token_hash: String,
+    pub scopes: String,
+    pub created_at: i64,
+    pub last_used_at: Option<i64>,
+}
+
+impl DbApiToken {
+    pub async fn create(
+        user_id: i64,
+        name: &str,
+        token_hash: &str,
+        scopes: &str,
+        created_at: i64,
+        pool: &DbPool,
+    ) -> Result<DbApiToken, sqlx::Error> {
+        sqlx::query_as(
+            r#"INSERT INTO api_tokens (user_id, name, token_hash, scopes, created_at) VALUES ($1, $2, $3, $4, $5) RETURNING *"#,
+        )
+        .bind(user_id)
+        .bind(name)
+        .bind(token_hash)
+        .bind(scopes)
+        .bind(created_at)
+        .fetch_one(pool)
+        .await
+    }
+
+    pub async fn find_by_hash(token_hash: &str, pool: &DbPool) -> Result<Option<DbApiToken>, sqlx::Error> {
+        sqlx::query_as(r#"SELECT * FROM api_tokens WHERE token_hash = $1"#)
+            .bind(token_hash)
+            .fetch_optional(pool)
+            .await
+    }
+
+    pub async fn find_by_user(user_id: i64, pool: &DbPool) -> Result<Vec<DbApiToken>, sqlx::Error> {
+        sqlx::query_as(r#"SELECT * FROM api_tokens WHERE user_id = $1 ORDER BY created_at DESC"#)
+            .bind(user_id)
+            .fetch_all(pool)
+            .await
+    }
+
+    pub async fn touch(id: i64, last_used_at: i64, pool: &DbPool) -> Result<(), sqlx::Error> {
+        sqlx::query("UPDATE api_tokens SET last_used_at = $1 WHERE id = $2")
+            .bind(last_used_at)
+            .bind(id)
+            .execute(pool)
+            .await?;
+        Ok(())
+    }
+
+    /// Revokes a token, scoped to its owner. Returns whether a token was
+    /// removed.
+    pub async fn delete(id: i64, user_id: i64, pool: &DbPool) -> Result<bool, sqlx::Error> {
+        let result = sqlx::query("DELETE FROM api_tokens WHERE id = $1 AND user_id = $2")
+            .bind(id)
+            .bind(user_id)
+            .execute(pool)
+            .await?;
+        Ok(result.rows_affected() > 0)
+    }
+}
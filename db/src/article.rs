@@ -1,11 +1,15 @@
 use serde::{Deserialize, Serialize};
-use sqlx::{FromRow, Pool, Row, Sqlite, SqliteConnection};
+use sqlx::{FromRow, Row};
+
+use crate::{search::plain_text, DbPool, DbRow};
 
 #[derive(Clone, Serialize, Deserialize, FromRow, Debug)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
 pub struct Article {
     pub id: i64,
     pub user_id: i64,
     pub title: String,
+    #[cfg_attr(feature = "openapi", schema(value_type = Object))]
     pub editor_content: serde_json::Value,
     pub content: String,
     //pub created_at: chrono::NaiveDateTime,
@@ -18,19 +22,19 @@ impl Article {
         title: String,
         editor_content: serde_json::Value,
         content: String,
-        pool: &Pool<Sqlite>,
+        pool: &DbPool,
     ) -> Result<Article, sqlx::Error> {
-        let article = sqlx::query_as!(
-            Article,
-            r#"INSERT INTO articles (user_id, title, editor_content, content) VALUES (?, ?, ?, ?) RETURNING *"#,
-            user_id,
-            title,
-            editor_content,
-            content
+        let search_text = plain_text(&content);
+        sqlx::query_as(
+            r#"INSERT INTO articles (user_id, title, editor_content, content, search_text) VALUES ($1, $2, $3, $4, $5) RETURNING *"#,
         )
+        .bind(user_id)
+        .bind(title)
+        .bind(editor_content)
+        .bind(content)
+        .bind(search_text)
         .fetch_one(pool)
-        .await?;
-        Ok(article)
+        .await
     }
 
     pub async fn upsert(
@@ -38,35 +42,56 @@ impl Article {
         title: String,
         editor_content: serde_json::Value,
         content: String,
-        pool: &Pool<Sqlite>,
+        pool: &DbPool,
     ) -> Result<Article, sqlx::Error> {
-        let article = sqlx::query_as!(
-            Article,
-            r#"INSERT INTO articles (user_id, title, editor_content, content) VALUES (?, ?, ?, ?) ON CONFLICT (user_id, title) DO UPDATE SET editor_content = ?, content = ? RETURNING *"#,
-            user_id,
-            title,
-            editor_content,
-            content,
-            editor_content,
-            content
+        let search_text = plain_text(&content);
+        sqlx::query_as(
+            r#"INSERT INTO articles (user_id, title, editor_content, content, search_text) VALUES ($1, $2, $3, $4, $5)
+            ON CONFLICT (user_id, title) DO UPDATE SET editor_content = excluded.editor_content, content = excluded.content,
+                search_text = excluded.search_text
+            RETURNING *"#,
         )
+        .bind(user_id)
+        .bind(title)
+        .bind(editor_content)
+        .bind(content)
+        .bind(search_text)
         .fetch_one(pool)
-        .await?;
-        Ok(article)
+        .await
     }
 
-    pub async fn find_by_id(id: i64, pool: &Pool<Sqlite>) -> Result<Article, sqlx::Error> {
-        let article = sqlx::query_as!(Article, r#"SELECT * FROM articles WHERE id = ?"#, id)
+    pub async fn find_by_id(id: i64, pool: &DbPool) -> Result<Article, sqlx::Error> {
+        sqlx::query_as(r#"SELECT * FROM articles WHERE id = $1"#)
+            .bind(id)
             .fetch_one(pool)
-            .await?;
-        Ok(article)
+            .await
     }
 
-    pub async fn find_all(pool: &Pool<Sqlite>) -> Result<Vec<Article>, sqlx::Error> {
-        let articles = sqlx::query_as!(Article, r#"SELECT * FROM articles"#)
+    pub async fn find_all(pool: &DbPool) -> Result<Vec<Article>, sqlx::Error> {
+        sqlx::query_as(r#"SELECT * FROM articles"#)
             .fetch_all(pool)
-            .await?;
-        Ok(articles)
+            .await
+    }
+
+    pub async fn list(
+        user_id: Option<i64>,
+        limit: i64,
+        offset: i64,
+        pool: &DbPool,
+    ) -> Result<Vec<Article>, sqlx::Error> {
+        sqlx::query_as(r#"SELECT * FROM articles WHERE ($1 IS NULL OR user_id = $1) ORDER BY id DESC LIMIT $2 OFFSET $3"#)
+            .bind(user_id)
+            .bind(limit)
+            .bind(offset)
+            .fetch_all(pool)
+            .await
+    }
+
+    pub async fn count(user_id: Option<i64>, pool: &DbPool) -> Result<i64, sqlx::Error> {
+        sqlx::query_scalar(r#"SELECT COUNT(*) FROM articles WHERE ($1 IS NULL OR user_id = $1)"#)
+            .bind(user_id)
+            .fetch_one(pool)
+            .await
     }
 
     pub async fn update(
@@ -74,29 +99,28 @@ impl Article {
         title: String,
         editor_content: serde_json::Value,
         content: String,
-        pool: &Pool<Sqlite>,
+        pool: &DbPool,
     ) -> Result<Article, sqlx::Error> {
-        let article = sqlx::query_as!(
-            Article,
-            r#"UPDATE articles SET title = ?, editor_content = ?, content = ? WHERE id = ? RETURNING *"#,
-            title,
-            editor_content,
-            content,
-            id
-        )
-        .fetch_one(pool)
-        .await?;
-        Ok(article)
+        let search_text = plain_text(&content);
+        sqlx::query_as(r#"UPDATE articles SET title = $1, editor_content = $2, content = $3, search_text = $4 WHERE id = $5 RETURNING *"#)
+            .bind(title)
+            .bind(editor_content)
+            .bind(content)
+            .bind(search_text)
+            .bind(id)
+            .fetch_one(pool)
+            .await
     }
 
-    pub async fn delete(id: i64, pool: &Pool<Sqlite>) -> Result<(), sqlx::Error> {
-        sqlx::query!("DELETE FROM articles WHERE id = ?", id)
+    pub async fn delete(id: i64, pool: &DbPool) -> Result<(), sqlx::Error> {
+        sqlx::query("DELETE FROM articles WHERE id = $1")
+            .bind(id)
             .execute(pool)
             .await?;
         Ok(())
     }
 
-    pub fn from_row(row: sqlx::sqlite::SqliteRow) -> Self {
+    pub fn from_row(row: DbRow) -> Self {
         Self {
             id: row.get(0),
             user_id: row.get(1),
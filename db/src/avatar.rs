@@ -0,0 +1,74 @@
+use sqlx::FromRow;
+
+use crate::DbPool;
+
+/// The size of an avatar kept as it was uploaded, with
+/// [`ORIGINAL_CONTENT_TYPE`]. Avatars from before they were resized on upload
+/// and those of version 2 dumps are stored like this until the server
+/// resizes them.
+pub const ORIGINAL_SIZE: i64 = 0;
+pub const ORIGINAL_CONTENT_TYPE: &str = "application/octet-stream";
+
+/// One size of a user's avatar. Every upload is stored in a few standard
+/// sizes, which are replaced together.
+#[derive(Clone, FromRow, Debug)]
+pub struct Avatar {
+    pub user_id: i64,
+    /// Width and height in pixels, avatars are square.
+    pub size: i64,
+    pub content_type: String,
+    pub img_data: Vec<u8>,
+}
+
+impl Avatar {
+    pub async fn find(user_id: i64, size: i64, pool: &DbPool) -> Result<Option<Avatar>, sqlx::Error> {
+        sqlx::query_as(r#"SELECT * FROM avatars WHERE user_id = $1 AND size = $2"#)
+            .bind(user_id)
+            .bind(size)
+            .fetch_optional(pool)
+            .await
+    }
+
+    /// Avatars stored at [`ORIGINAL_SIZE`], which still have to be resized.
+    pub async fn originals(pool: &DbPool) -> Result<Vec<Avatar>, sqlx::Error> {
+        sqlx::query_as(r#"SELECT * FROM avatars WHERE size = $1 ORDER BY user_id"#)
+            .bind(ORIGINAL_SIZE)
+            .fetch_all(pool)
+            .await
+    }
+
+    pub async fn exists(user_id: i64, pool: &DbPool) -> Result<bool, sqlx::Error> {
+        sqlx::query_scalar(r#"SELECT EXISTS (SELECT 1 FROM avatars WHERE user_id = $1)"#)
+            .bind(user_id)
+            .fetch_one(pool)
+            .await
+    }
+
+    /// Replaces all sizes of the user's avatar with `avatars`.
+    pub async fn replace(user_id: i64, avatars: &[Avatar], pool: &DbPool) -> Result<(), sqlx::Error> {
+        let mut tx = pool.begin().await?;
+        sqlx::query("DELETE FROM avatars WHERE user_id = $1")
+            .bind(user_id)
+            .execute(&mut *tx)
+            .await?;
+        for avatar in avatars {
+            sqlx::query("INSERT INTO avatars (user_id, size, content_type, img_data) VALUES ($1, $2, $3, $4)")
+                .bind(user_id)
+                .bind(avatar.size)
+                .bind(&avatar.content_type)
+                .bind(&avatar.img_data)
+                .execute(&mut *tx)
+                .await?;
+        }
+        tx.commit().await
+    }
+
+    /// Returns whether the user had an avatar.
+    pub async fn delete(user_id: i64, pool: &DbPool) -> Result<bool, sqlx::Error> {
+        let result = sqlx::query("DELETE FROM avatars WHERE user_id = $1")
+            .bind(user_id)
+            .execute(pool)
+            .await?;
+        Ok(result.rows_affected() > 0)
+    }
+}
@@ -0,0 +1,285 @@
+//! Backups of the SQLite database. [`backup`] copies the database through
+//! SQLite's backup API, which takes a consistent snapshot while the forum
+//! keeps serving requests. [`restore`] swaps a backup in for the database and
+//! is meant to run while the forum is stopped.
+
+use std::{
+    ffi::{CStr, CString},
+    path::{Path, PathBuf},
+    ptr,
+    str::FromStr,
+};
+
+use anyhow::{bail, ensure, Context};
+use libsqlite3_sys as ffi;
+use sqlx::{
+    migrate::Migrate,
+    sqlite::{SqliteConnectOptions, SqliteConnection},
+    ConnectOptions, Connection,
+};
+
+use crate::{DbPool, MIGRATOR};
+
+const SNAPSHOT_PREFIX: &str = "forum-";
+const SNAPSHOT_EXTENSION: &str = "db";
+
+/// The file a `sqlite:` URL points at.
+pub fn database_path(url: &str) -> anyhow::Result<PathBuf> {
+    let options = SqliteConnectOptions::from_str(url)?;
+    let path = options.get_filename();
+    ensure!(
+        !path.as_os_str().is_empty() && path != Path::new(":memory:"),
+        "{url} is an in-memory database, there is nothing to back up or restore"
+    );
+    Ok(path.to_path_buf())
+}
+
+/// Copies the database to `dest`. The copy is written next to `dest` first
+/// and renamed once complete, so `dest` never holds a partial backup.
+pub async fn backup(pool: &DbPool, dest: &Path) -> anyhow::Result<()> {
+    let source = (*pool.connect_options()).clone().get_filename().into_owned();
+    ensure!(
+        !source.as_os_str().is_empty() && source != Path::new(":memory:"),
+        "the database is in memory, there is nothing to back up"
+    );
+    let partial = with_suffix(dest, ".partial");
+    remove_if_exists(&partial)?;
+    let source_name = c_path(&source)?;
+    let partial_name = c_path(&partial)?;
+
+    // The copy gets a connection and a thread of its own, so a large
+    // database ties up neither a pooled connection nor a runtime worker
+    // however long it takes. With the write-ahead log it doesn't block
+    // writers either.
+    let copied = tokio::task::spawn_blocking(move || {
+        // SAFETY: both connections are opened, used and closed within the
+        // call, on this thread only.
+        unsafe { copy_database(&source_name, &partial_name) }
+    })
+    .await
+    .context("the backup task panicked")?;
+
+    if let Err(e) = copied {
+        let _ = std::fs::remove_file(&partial);
+        return Err(e.context(format!("failed to back up to {}", dest.display())));
+    }
+    std::fs::rename(&partial, dest)
+        .with_context(|| format!("failed to move the backup to {}", dest.display()))?;
+    Ok(())
+}
+
+fn c_path(path: &Path) -> anyhow::Result<CString> {
+    CString::new(path.to_string_lossy().as_bytes())
+        .with_context(|| format!("{} contains a nul byte", path.display()))
+}
+
+/// Copies every page of the database at `source` into a new database at
+/// `dest` in a single pass, within one read transaction on `source`.
+unsafe fn copy_database(source: &CStr, dest: &CStr) -> anyhow::Result<()> {
+    let mut from = ptr::null_mut();
+    if ffi::sqlite3_open_v2(source.as_ptr(), &mut from, ffi::SQLITE_OPEN_READONLY, ptr::null())
+        != ffi::SQLITE_OK
+    {
+        let message = error_message(from);
+        ffi::sqlite3_close(from);
+        bail!("failed to open the database: {message}");
+    }
+    // Waits out a checkpoint holding the log, like the pooled connections do.
+    ffi::sqlite3_busy_timeout(from, 5_000);
+
+    let mut target = ptr::null_mut();
+    let flags = ffi::SQLITE_OPEN_READWRITE | ffi::SQLITE_OPEN_CREATE;
+    if ffi::sqlite3_open_v2(dest.as_ptr(), &mut target, flags, ptr::null()) != ffi::SQLITE_OK {
+        let message = error_message(target);
+        ffi::sqlite3_close(target);
+        ffi::sqlite3_close(from);
+        bail!("failed to create the backup file: {message}");
+    }
+
+    let main = c"main".as_ptr();
+    let backup = ffi::sqlite3_backup_init(target, main, from, main);
+    if backup.is_null() {
+        let message = error_message(target);
+        ffi::sqlite3_close(target);
+        ffi::sqlite3_close(from);
+        bail!("failed to start the backup: {message}");
+    }
+
+    let step = ffi::sqlite3_backup_step(backup, -1);
+    let finish = ffi::sqlite3_backup_finish(backup);
+    let message = error_message(target);
+    ffi::sqlite3_close(target);
+    ffi::sqlite3_close(from);
+
+    ensure!(
+        step == ffi::SQLITE_DONE && finish == ffi::SQLITE_OK,
+        "the backup did not complete: {message}"
+    );
+    Ok(())
+}
+
+unsafe fn error_message(db: *mut ffi::sqlite3) -> String {
+    if db.is_null() {
+        return "out of memory".into();
+    }
+    CStr::from_ptr(ffi::sqlite3_errmsg(db))
+        .to_string_lossy()
+        .into_owned()
+}
+
+/// Backs the database up into `dir` under a name with the current time, then
+/// deletes all but the newest `keep` snapshots.
+pub async fn snapshot(pool: &DbPool, dir: &Path, keep: usize) -> anyhow::Result<PathBuf> {
+    std::fs::create_dir_all(dir)
+        .with_context(|| format!("failed to create {}", dir.display()))?;
+
+    let now = time::OffsetDateTime::now_utc();
+    let name = format!(
+        "{SNAPSHOT_PREFIX}{:04}{:02}{:02}T{:02}{:02}{:02}Z.{SNAPSHOT_EXTENSION}",
+        now.year(),
+        u8::from(now.month()),
+        now.day(),
+        now.hour(),
+        now.minute(),
+        now.second()
+    );
+    let path = dir.join(name);
+    backup(pool, &path).await?;
+
+    let snapshots = snapshots(dir)?;
+    let expired = snapshots.len().saturating_sub(keep.max(1));
+    for old in &snapshots[..expired] {
+        std::fs::remove_file(old)
+            .with_context(|| format!("failed to delete old snapshot {}", old.display()))?;
+    }
+    Ok(path)
+}
+
+/// The snapshots in `dir`, oldest first.
+pub fn snapshots(dir: &Path) -> anyhow::Result<Vec<PathBuf>> {
+    let mut snapshots: Vec<PathBuf> = std::fs::read_dir(dir)
+        .with_context(|| format!("failed to read {}", dir.display()))?
+        .filter_map(Result::ok)
+        .map(|entry| entry.path())
+        .filter(|path| {
+            let name = path.file_name().and_then(|name| name.to_str()).unwrap_or_default();
+            name.starts_with(SNAPSHOT_PREFIX)
+                && path.extension().and_then(|ext| ext.to_str()) == Some(SNAPSHOT_EXTENSION)
+        })
+        .collect();
+    // The names contain the time they were taken at, so they sort by age.
+    snapshots.sort();
+    Ok(snapshots)
+}
+
+/// Checks that `backup` is an intact forum database this build can run: every
+/// migration applied to it must be one of ours, unchanged and complete.
+/// Migrations added since the backup was taken are fine, they are applied on
+/// the next start. Returns the newest migration version in the backup.
+pub async fn verify(backup: &Path) -> anyhow::Result<i64> {
+    ensure!(backup.is_file(), "{} does not exist", backup.display());
+    let mut conn = SqliteConnectOptions::new()
+        .filename(backup)
+        .connect()
+        .await
+        .with_context(|| format!("failed to open {}", backup.display()))?;
+    let version = check_schema(&mut conn).await;
+    conn.close().await?;
+    version.with_context(|| format!("{} can't be restored", backup.display()))
+}
+
+async fn check_schema(conn: &mut SqliteConnection) -> anyhow::Result<i64> {
+    let integrity: String = sqlx::query_scalar("PRAGMA integrity_check")
+        .fetch_one(&mut *conn)
+        .await?;
+    ensure!(integrity == "ok", "the database is damaged: {integrity}");
+
+    let has_migrations: bool = sqlx::query_scalar(
+        "SELECT EXISTS (SELECT 1 FROM sqlite_master WHERE type = 'table' AND name = '_sqlx_migrations')",
+    )
+    .fetch_one(&mut *conn)
+    .await?;
+    ensure!(has_migrations, "it is not a forum database");
+
+    if let Some(version) = conn.dirty_version().await? {
+        bail!("migration {version} was interrupted while being applied");
+    }
+
+    let applied = conn.list_applied_migrations().await?;
+    for migration in &applied {
+        let Some(known) = MIGRATOR.iter().find(|known| known.version == migration.version) else {
+            bail!(
+                "it has migration {} applied, which this build doesn't know; it was taken by a newer version of the forum",
+                migration.version
+            );
+        };
+        ensure!(
+            known.checksum == migration.checksum,
+            "migration {} has been changed since it was applied to the backup",
+            migration.version
+        );
+    }
+
+    applied
+        .iter()
+        .map(|migration| migration.version)
+        .max()
+        .context("it has no migrations applied")
+}
+
+/// Replaces the database at `target` with `backup` after [`verify`]ing it.
+/// The current database is kept next to it with a `.before-restore` suffix.
+/// The forum must not be running. Returns the schema version of the backup.
+pub async fn restore(backup: &Path, target: &Path) -> anyhow::Result<i64> {
+    let version = verify(backup).await?;
+
+    let staged = with_suffix(target, ".restore");
+    std::fs::copy(backup, &staged)
+        .with_context(|| format!("failed to copy {} to {}", backup.display(), staged.display()))?;
+    std::fs::File::open(&staged)?.sync_all()?;
+
+    if target.exists() {
+        checkpoint(target).await?;
+        let previous = with_suffix(target, ".before-restore");
+        std::fs::rename(target, &previous)
+            .with_context(|| format!("failed to move the current database to {}", previous.display()))?;
+    }
+    // The log of the replaced database must not be applied to the backup.
+    remove_if_exists(&with_suffix(target, "-wal"))?;
+    remove_if_exists(&with_suffix(target, "-shm"))?;
+
+    std::fs::rename(&staged, target)
+        .with_context(|| format!("failed to move the backup to {}", target.display()))?;
+    Ok(version)
+}
+
+/// Moves everything in the write-ahead log of `path` into the database file,
+/// so the file is complete on its own. Fails while another connection is
+/// using the database, which most likely means the forum is still running.
+async fn checkpoint(path: &Path) -> anyhow::Result<()> {
+    let mut conn = SqliteConnectOptions::new().filename(path).connect().await?;
+    let (busy, _, _): (i64, i64, i64) = sqlx::query_as("PRAGMA wal_checkpoint(TRUNCATE)")
+        .fetch_one(&mut conn)
+        .await?;
+    conn.close().await?;
+    ensure!(
+        busy == 0,
+        "{} is in use, stop the forum before restoring",
+        path.display()
+    );
+    Ok(())
+}
+
+fn with_suffix(path: &Path, suffix: &str) -> PathBuf {
+    let mut path = path.as_os_str().to_owned();
+    path.push(suffix);
+    path.into()
+}
+
+fn remove_if_exists(path: &Path) -> anyhow::Result<()> {
+    match std::fs::remove_file(path) {
+        Ok(()) => Ok(()),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(()),
+        Err(e) => Err(e).with_context(|| format!("failed to delete {}", path.display())),
+    }
+}
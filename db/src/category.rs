@@ -0,0 +1,35 @@
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+
+use crate::DbPool;
+
+#[derive(Clone, Serialize, Deserialize, FromRow, Debug)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct Category {
+    pub id: i64,
+    pub title: String,
+    pub content: Option<String>,
+}
+
+impl Category {
+    pub async fn find_by_id(id: i64, pool: &DbPool) -> Result<Option<Category>, sqlx::Error> {
+        sqlx::query_as(r#"SELECT * FROM categories WHERE id = $1"#)
+            .bind(id)
+            .fetch_optional(pool)
+            .await
+    }
+
+    pub async fn list(limit: i64, offset: i64, pool: &DbPool) -> Result<Vec<Category>, sqlx::Error> {
+        sqlx::query_as(r#"SELECT * FROM categories ORDER BY id LIMIT $1 OFFSET $2"#)
+            .bind(limit)
+            .bind(offset)
+            .fetch_all(pool)
+            .await
+    }
+
+    pub async fn count(pool: &DbPool) -> Result<i64, sqlx::Error> {
+        sqlx::query_scalar(r#"SELECT COUNT(*) FROM categories"#)
+            .fetch_one(pool)
+            .await
+    }
+}
@@ -3,6 +3,7 @@ pub enum DbError {
     NotFound,
     UserNotFound,
     PasswordIncorrect,
+    CredentialRejected(String),
     Sqlx(sqlx::Error),
     Other(anyhow::Error),
 }
@@ -15,6 +16,7 @@ impl std::fmt::Display for DbError {
             DbError::Other(err) => write!(f, "Other error: {}", err),
             DbError::UserNotFound => write!(f, "User not found"),
             DbError::PasswordIncorrect => write!(f, "Password incorrect"),
+            DbError::CredentialRejected(reason) => write!(f, "Credential rejected: {}", reason),
         }
     }
 }
@@ -0,0 +1,530 @@
+//! Export and import of the whole forum, for moving it to another instance
+//! or archiving it. A dump is written as JSON lines: a [`Header`] naming the
+//! format and version, then one [`Record`] per row, parents before the rows
+//! referring to them. Ids in a dump are those of the exporting forum, the
+//! importer maps them to the ids the rows get on insert.
+
+use std::{
+    collections::{HashMap, HashSet},
+    io::{Read, Write},
+};
+
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+
+use crate::{
+    article::Article,
+    avatar::{ORIGINAL_CONTENT_TYPE, ORIGINAL_SIZE},
+    category::Category,
+    group::DbGroup,
+    post::Post,
+    search::plain_text,
+    thread::Thread,
+    DbConnection, DbPool,
+};
+
+/// Identifies a dump, so importing some other JSON file fails early.
+pub const DUMP_FORMAT: &str = "foundry-forum";
+
+/// Bumped whenever the layout of [`Dump`] changes incompatibly. Version 1
+/// was a single JSON document without passwords or avatars, version 2 had
+/// each avatar once, as uploaded.
+pub const DUMP_VERSION: u32 = 3;
+
+/// The forum's content. Password hashes are only included when asked for,
+/// otherwise imported users have to reset their password.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Dump {
+    pub version: u32,
+    pub users: Vec<DumpUser>,
+    pub groups: Vec<DbGroup>,
+    pub memberships: Vec<Membership>,
+    #[serde(default)]
+    pub group_permissions: Vec<GroupPermission>,
+    pub categories: Vec<Category>,
+    pub threads: Vec<Thread>,
+    pub posts: Vec<Post>,
+    pub articles: Vec<Article>,
+    #[serde(default)]
+    pub avatars: Vec<DumpAvatar>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct DumpUser {
+    pub id: i64,
+    pub username: String,
+    /// The password hash, if the export included them.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub password: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct Membership {
+    pub user_id: i64,
+    pub group_id: i64,
+}
+
+/// A permission granted to a group. Permissions are named rather than
+/// referred to by id, the importing forum seeds its own.
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct GroupPermission {
+    pub group_id: i64,
+    pub permission: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct DumpAvatar {
+    pub user_id: i64,
+    /// Missing in version 2 dumps, whose avatars are imported as originals
+    /// for the server to resize.
+    #[serde(default = "original_size")]
+    pub size: i64,
+    #[serde(default = "original_content_type")]
+    pub content_type: String,
+    /// The image, base64 encoded in the dump.
+    #[serde(with = "base64_data")]
+    pub img_data: Vec<u8>,
+}
+
+fn original_size() -> i64 {
+    ORIGINAL_SIZE
+}
+
+fn original_content_type() -> String {
+    ORIGINAL_CONTENT_TYPE.into()
+}
+
+/// The first line of a dump.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Header {
+    pub format: String,
+    pub version: u32,
+    /// Whether the users carry their password hashes.
+    pub passwords: bool,
+}
+
+/// A line of a dump.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Record {
+    Header(Header),
+    User(DumpUser),
+    Group(DbGroup),
+    Membership(Membership),
+    GroupPermission(GroupPermission),
+    Category(Category),
+    Thread(Thread),
+    Post(Post),
+    Article(Article),
+    Avatar(DumpAvatar),
+}
+
+/// [`Record`] for writing, so a dump doesn't have to be cloned.
+#[derive(Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum RecordRef<'a> {
+    Header(&'a Header),
+    User(&'a DumpUser),
+    Group(&'a DbGroup),
+    Membership(&'a Membership),
+    GroupPermission(&'a GroupPermission),
+    Category(&'a Category),
+    Thread(&'a Thread),
+    Post(&'a Post),
+    Article(&'a Article),
+    Avatar(&'a DumpAvatar),
+}
+
+/// What an import added to the database.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ImportSummary {
+    pub users: usize,
+    pub groups: usize,
+    pub categories: usize,
+    pub threads: usize,
+    pub posts: usize,
+    pub articles: usize,
+    pub avatars: usize,
+}
+
+impl Dump {
+    /// Reads the whole forum. Password hashes are left out unless
+    /// `passwords` is set.
+    pub async fn export(pool: &DbPool, passwords: bool) -> Result<Dump, sqlx::Error> {
+        let users = if passwords {
+            "SELECT id, username, password FROM users ORDER BY id"
+        } else {
+            "SELECT id, username, CAST(NULL AS TEXT) AS password FROM users ORDER BY id"
+        };
+        Ok(Dump {
+            version: DUMP_VERSION,
+            users: sqlx::query_as(users).fetch_all(pool).await?,
+            groups: sqlx::query_as("SELECT * FROM groups ORDER BY id")
+                .fetch_all(pool)
+                .await?,
+            memberships: sqlx::query_as("SELECT user_id, group_id FROM users_groups")
+                .fetch_all(pool)
+                .await?,
+            group_permissions: sqlx::query_as(
+                "SELECT groups_permissions.group_id, permissions.name AS permission
+                FROM groups_permissions JOIN permissions ON permissions.id = groups_permissions.permission_id
+                ORDER BY groups_permissions.group_id, permissions.name",
+            )
+            .fetch_all(pool)
+            .await?,
+            categories: sqlx::query_as("SELECT * FROM categories ORDER BY id")
+                .fetch_all(pool)
+                .await?,
+            threads: sqlx::query_as("SELECT * FROM threads ORDER BY id")
+                .fetch_all(pool)
+                .await?,
+            posts: sqlx::query_as("SELECT * FROM posts ORDER BY id")
+                .fetch_all(pool)
+                .await?,
+            articles: sqlx::query_as("SELECT * FROM articles ORDER BY id")
+                .fetch_all(pool)
+                .await?,
+            avatars: sqlx::query_as("SELECT user_id, size, content_type, img_data FROM avatars ORDER BY user_id, size")
+                .fetch_all(pool)
+                .await?,
+        })
+    }
+
+    /// How many users have an avatar in the dump, each is stored in
+    /// several sizes.
+    pub fn avatar_users(&self) -> usize {
+        self.avatars
+            .iter()
+            .map(|avatar| avatar.user_id)
+            .collect::<HashSet<_>>()
+            .len()
+    }
+
+    /// Writes the dump as JSON lines.
+    pub fn write(&self, mut writer: impl Write) -> anyhow::Result<()> {
+        let header = Header {
+            format: DUMP_FORMAT.to_owned(),
+            version: DUMP_VERSION,
+            passwords: self.users.iter().any(|user| user.password.is_some()),
+        };
+        let records = std::iter::once(RecordRef::Header(&header))
+            .chain(self.users.iter().map(RecordRef::User))
+            .chain(self.groups.iter().map(RecordRef::Group))
+            .chain(self.memberships.iter().map(RecordRef::Membership))
+            .chain(self.group_permissions.iter().map(RecordRef::GroupPermission))
+            .chain(self.categories.iter().map(RecordRef::Category))
+            .chain(self.threads.iter().map(RecordRef::Thread))
+            .chain(self.posts.iter().map(RecordRef::Post))
+            .chain(self.articles.iter().map(RecordRef::Article))
+            .chain(self.avatars.iter().map(RecordRef::Avatar));
+        for record in records {
+            serde_json::to_writer(&mut writer, &record)?;
+            writer.write_all(b"\n")?;
+        }
+        writer.flush()?;
+        Ok(())
+    }
+
+    /// Reads a dump written by [`Dump::write`] of this or the previous
+    /// version, or a version 1 document.
+    pub fn read(mut reader: impl Read) -> anyhow::Result<Dump> {
+        let mut input = String::new();
+        reader.read_to_string(&mut input)?;
+
+        let first = input.lines().find(|line| !line.trim().is_empty()).unwrap_or_default();
+        let header = match serde_json::from_str(first) {
+            Ok(Record::Header(header)) => header,
+            // Version 1 dumps are one pretty-printed document.
+            _ => return Ok(serde_json::from_str(&input)?),
+        };
+        anyhow::ensure!(header.format == DUMP_FORMAT, "not a forum dump: the format is {}", header.format);
+        anyhow::ensure!(
+            (2..=DUMP_VERSION).contains(&header.version),
+            "unsupported dump version {}, expected at most {}",
+            header.version,
+            DUMP_VERSION
+        );
+
+        let mut dump = Dump {
+            version: header.version,
+            users: Vec::new(),
+            groups: Vec::new(),
+            memberships: Vec::new(),
+            group_permissions: Vec::new(),
+            categories: Vec::new(),
+            threads: Vec::new(),
+            posts: Vec::new(),
+            articles: Vec::new(),
+            avatars: Vec::new(),
+        };
+        for (number, line) in input.lines().enumerate() {
+            if line.trim().is_empty() {
+                continue;
+            }
+            let record = serde_json::from_str(line)
+                .map_err(|e| anyhow::anyhow!("line {}: {e}", number + 1))?;
+            match record {
+                Record::Header(_) => {}
+                Record::User(user) => dump.users.push(user),
+                Record::Group(group) => dump.groups.push(group),
+                Record::Membership(membership) => dump.memberships.push(membership),
+                Record::GroupPermission(permission) => dump.group_permissions.push(permission),
+                Record::Category(category) => dump.categories.push(category),
+                Record::Thread(thread) => dump.threads.push(thread),
+                Record::Post(post) => dump.posts.push(post),
+                Record::Article(article) => dump.articles.push(article),
+                Record::Avatar(avatar) => dump.avatars.push(avatar),
+            }
+        }
+        Ok(dump)
+    }
+
+    /// Imports the dump in a single transaction. Users and groups are matched
+    /// by name, so importing into a freshly migrated forum reuses the seeded
+    /// accounts; only users created by the import get the password hash from
+    /// the dump. Content gets new ids, keeps its authors and is only imported
+    /// into a forum that has none yet.
+    pub async fn import(&self, pool: &DbPool) -> anyhow::Result<ImportSummary> {
+        anyhow::ensure!(
+            (1..=DUMP_VERSION).contains(&self.version),
+            "unsupported dump version {}, expected at most {}",
+            self.version,
+            DUMP_VERSION
+        );
+
+        let mut tx = pool.begin().await?;
+        ensure_empty(&mut tx).await?;
+
+        let mut summary = ImportSummary::default();
+
+        let mut users = HashMap::new();
+        for user in &self.users {
+            let (id, created) = find_or_create(
+                &mut tx,
+                "SELECT id FROM users WHERE username = $1",
+                // "!" is never a valid password hash.
+                "INSERT INTO users (username, password) VALUES ($1, '!') RETURNING id",
+                &user.username,
+            )
+            .await?;
+            if let (true, Some(password)) = (created, &user.password) {
+                sqlx::query("UPDATE users SET password = $1 WHERE id = $2")
+                    .bind(password)
+                    .bind(id)
+                    .execute(&mut *tx)
+                    .await?;
+            }
+            users.insert(user.id, id);
+            summary.users += created as usize;
+        }
+
+        let mut groups = HashMap::new();
+        for group in &self.groups {
+            let (id, created) = find_or_create(
+                &mut tx,
+                "SELECT id FROM groups WHERE name = $1",
+                "INSERT INTO groups (name) VALUES ($1) RETURNING id",
+                &group.name,
+            )
+            .await?;
+            groups.insert(group.id, id);
+            summary.groups += created as usize;
+        }
+
+        for membership in &self.memberships {
+            if let (Some(user_id), Some(group_id)) =
+                (users.get(&membership.user_id), groups.get(&membership.group_id))
+            {
+                sqlx::query("INSERT INTO users_groups (user_id, group_id) VALUES ($1, $2) ON CONFLICT DO NOTHING")
+                    .bind(user_id)
+                    .bind(group_id)
+                    .execute(&mut *tx)
+                    .await?;
+            }
+        }
+
+        // Groups found by name keep the permissions they have, the dump can
+        // only add to them.
+        for grant in &self.group_permissions {
+            let Some(group_id) = groups.get(&grant.group_id) else {
+                continue;
+            };
+            let (permission_id, _) = find_or_create(
+                &mut tx,
+                "SELECT id FROM permissions WHERE name = $1",
+                "INSERT INTO permissions (name) VALUES ($1) RETURNING id",
+                &grant.permission,
+            )
+            .await?;
+            sqlx::query("INSERT INTO groups_permissions (group_id, permission_id) VALUES ($1, $2) ON CONFLICT DO NOTHING")
+                .bind(group_id)
+                .bind(permission_id)
+                .execute(&mut *tx)
+                .await?;
+        }
+
+        let mut categories = HashMap::new();
+        for category in &self.categories {
+            let id: i64 = sqlx::query_scalar("INSERT INTO categories (title, content) VALUES ($1, $2) RETURNING id")
+                .bind(&category.title)
+                .bind(&category.content)
+                .fetch_one(&mut *tx)
+                .await?;
+            categories.insert(category.id, id);
+            summary.categories += 1;
+        }
+
+        let mut threads = HashMap::new();
+        for thread in &self.threads {
+            let id: i64 = sqlx::query_scalar(
+                "INSERT INTO threads (category_id, user_id, title) VALUES ($1, $2, $3) RETURNING id",
+            )
+            .bind(thread.category_id.and_then(|id| categories.get(&id)))
+            .bind(thread.user_id.and_then(|id| users.get(&id)))
+            .bind(&thread.title)
+            .fetch_one(&mut *tx)
+            .await?;
+            threads.insert(thread.id, id);
+            summary.threads += 1;
+        }
+
+        for post in &self.posts {
+            sqlx::query("INSERT INTO posts (thread_id, user_id, title, content, search_text) VALUES ($1, $2, $3, $4, $5)")
+                .bind(post.thread_id.and_then(|id| threads.get(&id)))
+                .bind(post.user_id.and_then(|id| users.get(&id)))
+                .bind(&post.title)
+                .bind(&post.content)
+                .bind(plain_text(&post.content))
+                .execute(&mut *tx)
+                .await?;
+            summary.posts += 1;
+        }
+
+        for article in &self.articles {
+            sqlx::query("INSERT INTO articles (user_id, title, editor_content, content, search_text) VALUES ($1, $2, $3, $4, $5)")
+                .bind(users.get(&article.user_id))
+                .bind(&article.title)
+                .bind(&article.editor_content)
+                .bind(&article.content)
+                .bind(plain_text(&article.content))
+                .execute(&mut *tx)
+                .await?;
+            summary.articles += 1;
+        }
+
+        let mut avatars = HashSet::new();
+        for avatar in &self.avatars {
+            if let Some(user_id) = users.get(&avatar.user_id) {
+                sqlx::query("INSERT INTO avatars (user_id, size, content_type, img_data) VALUES ($1, $2, $3, $4)")
+                    .bind(user_id)
+                    .bind(avatar.size)
+                    .bind(&avatar.content_type)
+                    .bind(&avatar.img_data)
+                    .execute(&mut *tx)
+                    .await?;
+                avatars.insert(user_id);
+            }
+        }
+        summary.avatars = avatars.len();
+
+        tx.commit().await?;
+        Ok(summary)
+    }
+}
+
+/// Fails if the forum has any content, so an import can't be applied twice
+/// or mixed into a running forum.
+pub(crate) async fn ensure_empty(conn: &mut DbConnection) -> anyhow::Result<()> {
+    let content: i64 = sqlx::query_scalar(
+        "SELECT (SELECT COUNT(*) FROM categories) + (SELECT COUNT(*) FROM threads) + (SELECT COUNT(*) FROM posts) + (SELECT COUNT(*) FROM articles) + (SELECT COUNT(*) FROM avatars)",
+    )
+    .fetch_one(&mut *conn)
+    .await?;
+    anyhow::ensure!(content == 0, "the forum already has content, import into an empty database");
+    Ok(())
+}
+
+/// Returns the id of the row named `name`, inserting it first if needed,
+/// and whether it was inserted.
+pub(crate) async fn find_or_create(
+    conn: &mut DbConnection,
+    find: &str,
+    insert: &str,
+    name: &str,
+) -> Result<(i64, bool), sqlx::Error> {
+    if let Some(id) = sqlx::query_scalar(find).bind(name).fetch_optional(&mut *conn).await? {
+        return Ok((id, false));
+    }
+    let id = sqlx::query_scalar(insert).bind(name).fetch_one(&mut *conn).await?;
+    Ok((id, true))
+}
+
+/// Binary columns as base64 strings, which keeps every line of a dump text.
+mod base64_data {
+    use base64::{engine::general_purpose::STANDARD, Engine};
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(data: &[u8], serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&STANDARD.encode(data))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<u8>, D::Error> {
+        let encoded = String::deserialize(deserializer)?;
+        STANDARD.decode(encoded).map_err(serde::de::Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn header(version: u32) -> String {
+        format!(r#"{{"type": "header", "format": "{DUMP_FORMAT}", "version": {version}, "passwords": false}}"#)
+    }
+
+    #[test]
+    fn version_2_avatars_are_read_as_originals() {
+        let input = format!("{}\n{}\n", header(2), r#"{"type": "avatar", "user_id": 7, "img_data": "AQI="}"#);
+        let dump = Dump::read(input.as_bytes()).unwrap();
+        assert_eq!(dump.version, 2);
+        let [avatar] = &dump.avatars[..] else { panic!("{:?}", dump.avatars) };
+        assert_eq!(avatar.user_id, 7);
+        assert_eq!(avatar.size, ORIGINAL_SIZE);
+        assert_eq!(avatar.content_type, ORIGINAL_CONTENT_TYPE);
+        assert_eq!(avatar.img_data, [1, 2]);
+    }
+
+    #[test]
+    fn written_dumps_read_back() {
+        let dump = Dump {
+            version: DUMP_VERSION,
+            users: Vec::new(),
+            groups: Vec::new(),
+            memberships: Vec::new(),
+            group_permissions: Vec::new(),
+            categories: Vec::new(),
+            threads: Vec::new(),
+            posts: Vec::new(),
+            articles: Vec::new(),
+            avatars: vec![DumpAvatar {
+                user_id: 7,
+                size: 64,
+                content_type: "image/png".into(),
+                img_data: vec![1, 2],
+            }],
+        };
+        let mut output = Vec::new();
+        dump.write(&mut output).unwrap();
+
+        let read = Dump::read(&output[..]).unwrap();
+        assert_eq!(read.version, DUMP_VERSION);
+        assert_eq!(read.avatars[0].size, 64);
+        assert_eq!(read.avatars[0].content_type, "image/png");
+    }
+
+    #[test]
+    fn newer_versions_are_rejected() {
+        let error = Dump::read(header(DUMP_VERSION + 1).as_bytes()).unwrap_err();
+        assert!(error.to_string().contains("unsupported dump version"), "{error}");
+    }
+}
@@ -0,0 +1,48 @@
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+
+use crate::DbPool;
+
+#[derive(Clone, Serialize, Deserialize, FromRow, Debug)]
+pub struct DbGroup {
+    pub id: i64,
+    pub name: String,
+}
+
+impl DbGroup {
+    pub async fn find_by_name(name: &str, pool: &DbPool) -> Result<Option<DbGroup>, sqlx::Error> {
+        sqlx::query_as(r#"SELECT * FROM groups WHERE name = $1"#)
+            .bind(name)
+            .fetch_optional(pool)
+            .await
+    }
+
+    pub async fn find_by_user(user_id: i64, pool: &DbPool) -> Result<Vec<DbGroup>, sqlx::Error> {
+        sqlx::query_as(
+            r#"SELECT groups.* FROM groups JOIN users_groups ON users_groups.group_id = groups.id WHERE users_groups.user_id = $1"#,
+        )
+        .bind(user_id)
+        .fetch_all(pool)
+        .await
+    }
+
+    /// Adds a user to a group. Adding a user to a group they are already in
+    /// is a no-op.
+    pub async fn add_user(group_id: i64, user_id: i64, pool: &DbPool) -> Result<(), sqlx::Error> {
+        sqlx::query("INSERT INTO users_groups (user_id, group_id) VALUES ($1, $2) ON CONFLICT DO NOTHING")
+            .bind(user_id)
+            .bind(group_id)
+            .execute(pool)
+            .await?;
+        Ok(())
+    }
+
+    pub async fn remove_user(group_id: i64, user_id: i64, pool: &DbPool) -> Result<(), sqlx::Error> {
+        sqlx::query("DELETE FROM users_groups WHERE user_id = $1 AND group_id = $2")
+            .bind(user_id)
+            .bind(group_id)
+            .execute(pool)
+            .await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,169 @@
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+
+use crate::{user::DbUser, DbConnection, DbPool};
+
+/// Links a local user to an account at an external identity provider.
+#[derive(Clone, Serialize, Deserialize, FromRow, Debug)]
+pub struct DbIdentity {
+    pub id: i64,
+    pub user_id: i64,
+    pub provider: String,
+    pub subject: String,
+    pub created_at: i64,
+}
+
+impl DbIdentity {
+    pub async fn create(
+        user_id: i64,
+        provider: &str,
+        subject: &str,
+        created_at: i64,
+        pool: &DbPool,
+    ) -> Result<DbIdentity, sqlx::Error> {
+        sqlx::query_as(
+            r#"INSERT INTO user_identities (user_id, provider, subject, created_at) VALUES ($1, $2, $3, $4) RETURNING *"#,
+        )
+        .bind(user_id)
+        .bind(provider)
+        .bind(subject)
+        .bind(created_at)
+        .fetch_one(pool)
+        .await
+    }
+
+    pub async fn find(
+        provider: &str,
+        subject: &str,
+        pool: &DbPool,
+    ) -> Result<Option<DbIdentity>, sqlx::Error> {
+        sqlx::query_as(r#"SELECT * FROM user_identities WHERE provider = $1 AND subject = $2"#)
+            .bind(provider)
+            .bind(subject)
+            .fetch_optional(pool)
+            .await
+    }
+
+    /// Logs in through an identity provider in a single transaction: finds the
+    /// user linked to `subject`, or creates one named after
+    /// `preferred_username` who joins `users`, then sets their membership of
+    /// the given groups. When two first logins for the same subject race, the
+    /// loser uses the user the winner created.
+    pub async fn sign_in(login: &IdentityLogin<'_>, pool: &DbPool) -> Result<SignIn, sqlx::Error> {
+        // Every transaction starts with a write: SQLite fails a transaction
+        // that reads and then wants to write while another one writes,
+        // instead of waiting for it.
+        let existing = find_user(login, pool).await?;
+        let mut tx = pool.begin().await?;
+        let (user, created) = match existing {
+            Some(user) => (user, false),
+            None => match provision(login, &mut tx).await {
+                Ok(user) => (user, true),
+                Err(e) if is_unique_violation(&e) => {
+                    // Another login linked the subject first. PostgreSQL
+                    // aborts the transaction on the failed insert.
+                    tx.rollback().await?;
+                    tx = pool.begin().await?;
+                    let user = find_user(login, pool).await?.ok_or(sqlx::Error::RowNotFound)?;
+                    (user, false)
+                }
+                Err(e) => return Err(e),
+            },
+        };
+
+        for (group, member) in login.memberships {
+            let query = if *member {
+                "INSERT INTO users_groups (user_id, group_id) SELECT $1, id FROM groups WHERE name = $2 ON CONFLICT DO NOTHING"
+            } else {
+                "DELETE FROM users_groups WHERE user_id = $1 AND group_id IN (SELECT id FROM groups WHERE name = $2)"
+            };
+            sqlx::query(query).bind(user.id).bind(group).execute(&mut *tx).await?;
+        }
+
+        let mut unknown_groups = vec![];
+        for (group, _) in login.memberships {
+            let exists: bool = sqlx::query_scalar("SELECT EXISTS (SELECT 1 FROM groups WHERE name = $1)")
+                .bind(group)
+                .fetch_one(&mut *tx)
+                .await?;
+            if !exists {
+                unknown_groups.push(group.clone());
+            }
+        }
+
+        tx.commit().await?;
+        Ok(SignIn {
+            user,
+            created,
+            unknown_groups,
+        })
+    }
+}
+
+/// A login through an identity provider, see [`DbIdentity::sign_in`].
+#[derive(Debug, Clone)]
+pub struct IdentityLogin<'a> {
+    pub provider: &'a str,
+    pub subject: &'a str,
+    /// Name for a new user, a number is appended while it is taken.
+    pub preferred_username: &'a str,
+    /// Groups by name and whether the user should be a member.
+    pub memberships: &'a [(String, bool)],
+    pub created_at: i64,
+}
+
+#[derive(Debug, Clone)]
+pub struct SignIn {
+    pub user: DbUser,
+    /// Whether the user was created by this login.
+    pub created: bool,
+    /// Groups of [`IdentityLogin::memberships`] that don't exist.
+    pub unknown_groups: Vec<String>,
+}
+
+async fn find_user(login: &IdentityLogin<'_>, pool: &DbPool) -> Result<Option<DbUser>, sqlx::Error> {
+    sqlx::query_as(
+        r#"SELECT users.* FROM users JOIN user_identities ON user_identities.user_id = users.id WHERE provider = $1 AND subject = $2"#,
+    )
+    .bind(login.provider)
+    .bind(login.subject)
+    .fetch_optional(pool)
+    .await
+}
+
+async fn provision(login: &IdentityLogin<'_>, conn: &mut DbConnection) -> Result<DbUser, sqlx::Error> {
+    let mut username = login.preferred_username.to_owned();
+    let mut suffix = 1;
+    let user = loop {
+        // "!" is never a valid password hash, so these users can't log in with
+        // a password until they set one.
+        let user: Option<DbUser> = sqlx::query_as(
+            "INSERT INTO users (username, password) VALUES ($1, '!') ON CONFLICT (username) DO NOTHING RETURNING *",
+        )
+        .bind(&username)
+        .fetch_optional(&mut *conn)
+        .await?;
+        if let Some(user) = user {
+            break user;
+        }
+        suffix += 1;
+        username = format!("{}{suffix}", login.preferred_username);
+    };
+
+    sqlx::query("INSERT INTO user_identities (user_id, provider, subject, created_at) VALUES ($1, $2, $3, $4)")
+        .bind(user.id)
+        .bind(login.provider)
+        .bind(login.subject)
+        .bind(login.created_at)
+        .execute(&mut *conn)
+        .await?;
+    sqlx::query("INSERT INTO users_groups (user_id, group_id) SELECT $1, id FROM groups WHERE name = 'users'")
+        .bind(user.id)
+        .execute(&mut *conn)
+        .await?;
+    Ok(user)
+}
+
+fn is_unique_violation(e: &sqlx::Error) -> bool {
+    e.as_database_error().is_some_and(|e| e.is_unique_violation())
+}
@@ -0,0 +1,72 @@
+-- MySQL dump 10.13  Distrib 8.0.36, for Linux (x86_64)
+--
+-- Host: localhost    Database: forum
+-- ------------------------------------------------------
+
+/*!40101 SET @OLD_CHARACTER_SET_CLIENT=@@CHARACTER_SET_CLIENT */;
+/*!40101 SET NAMES utf8mb4 */;
+
+--
+-- Table structure for table `phpbb_config`
+--
+
+DROP TABLE IF EXISTS `phpbb_config`;
+CREATE TABLE `phpbb_config` (
+  `config_name` varchar(255) NOT NULL DEFAULT '',
+  `config_value` varchar(255) NOT NULL DEFAULT '',
+  PRIMARY KEY (`config_name`)
+) ENGINE=InnoDB DEFAULT CHARSET=utf8mb3 COLLATE=utf8mb3_bin;
+
+INSERT INTO `phpbb_config` VALUES ('sitename','A forum; with a semicolon'),('version','3.3.11');
+
+--
+-- Table structure for table `phpbb_users`
+--
+
+DROP TABLE IF EXISTS `phpbb_users`;
+/*!40101 SET @saved_cs_client     = @@character_set_client */;
+CREATE TABLE `phpbb_users` (
+  `user_id` int unsigned NOT NULL AUTO_INCREMENT,
+  `user_type` tinyint NOT NULL DEFAULT '0',
+  `group_id` mediumint unsigned NOT NULL DEFAULT '3',
+  `username` varchar(255) COLLATE utf8mb3_bin NOT NULL DEFAULT '',
+  `user_sig` mediumtext COLLATE utf8mb3_bin NOT NULL,
+  PRIMARY KEY (`user_id`),
+  UNIQUE KEY `username_clean` (`username`),
+  KEY `user_type` (`user_type`),
+  CONSTRAINT `check_type` CHECK ((`user_type` in (0,1,2,3)))
+) ENGINE=InnoDB AUTO_INCREMENT=53 DEFAULT CHARSET=utf8mb3 COLLATE=utf8mb3_bin;
+
+LOCK TABLES `phpbb_users` WRITE;
+/*!40000 ALTER TABLE `phpbb_users` DISABLE KEYS */;
+INSERT INTO `phpbb_users` VALUES (1,2,1,'Anonymous',''),(2,3,5,'admin','Sig, with (parens), commas'),(3,2,6,'Googlebot',''),(48,0,2,'O\'Brien &amp; Sons','-- not a comment'),(52,0,2,'carol','');
+/*!40000 ALTER TABLE `phpbb_users` ENABLE KEYS */;
+UNLOCK TABLES;
+
+--
+-- Dumping data for table `phpbb_forums`
+--
+
+INSERT INTO phpbb_forums (forum_id, parent_id, forum_type, forum_name, forum_desc, forum_desc_uid) VALUES
+(1, 0, 0, 'Your first category', '', ''),
+(2, 1, 1, 'General &amp; Off-topic', 'Talk about [b:2xkq3m4p]anything[/b:2xkq3m4p]', '2xkq3m4p'),
+(3, 1, 2, 'Our website', 'A link forum', '');
+
+CREATE TABLE IF NOT EXISTS forum.phpbb_topics (
+  topic_id int unsigned NOT NULL AUTO_INCREMENT,
+  forum_id mediumint unsigned NOT NULL DEFAULT '0',
+  topic_title varchar(255) NOT NULL DEFAULT '',
+  topic_poster int unsigned NOT NULL DEFAULT '0',
+  topic_moved_id int unsigned NOT NULL DEFAULT '0',
+  PRIMARY KEY (topic_id),
+  KEY forum_id (forum_id)
+);
+
+INSERT INTO `forum`.`phpbb_topics` VALUES (1,2,'Welcome to phpBB3',2,0),(2,1,'In a category forum',2,0),(3,2,'Moved elsewhere',48,1),(4,2,'Guests welcome',1,0);
+
+INSERT INTO `phpbb_posts` (`post_id`, `topic_id`, `forum_id`, `poster_id`, `post_subject`, `post_text`, `bbcode_uid`, `post_attachment`) VALUES
+(5,4,2,1,'Re: Guests welcome','Hello from a guest <!-- s:) --><img class="smilies" src="{SMILIES_PATH}/icon_e_smile.gif" width="15" height="17" alt=":)" title="Smile" /><!-- s:) -->','',0),
+(1,1,2,2,'Welcome to phpBB3','[b:3h2kxq1z]Bold[/b:3h2kxq1z] and a [url=http&#58;//example.com:3h2kxq1z]link[/url:3h2kxq1z]\nSee <!-- m --><a class="postlink" href="https://www.phpbb.com/">https://www.phpbb.com/</a><!-- m -->','3h2kxq1z',0),
+(2,1,2,48,'Re: Welcome','[list:9x8y7z6w][*:9x8y7z6w]one[/*:m:9x8y7z6w]\n[*:9x8y7z6w]two[/*:m:9x8y7z6w][/list:u:9x8y7z6w]\n&lt;script&gt;alert(&quot;x&quot;)&lt;/script&gt; It\'s ''quoted''; done','9x8y7z6w',_binary 0),
+(3,2,1,2,'Hidden','In a category forum','',0),
+(4,3,2,48,'Moved','Moved topic',NULL,0);
@@ -0,0 +1,419 @@
+//! Conversion of imported content to the HTML posts are stored as. Markup
+//! from other forums is untrusted: text is escaped, raw HTML is never passed
+//! through and links are limited to http, https, mailto and relative URLs.
+
+use std::fmt::Write;
+
+use pulldown_cmark::{html, CowStr, Event, Options, Parser, Tag};
+use serde::Deserialize;
+
+/// How the body of an imported post is written.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Markup {
+    #[default]
+    Text,
+    Bbcode,
+    Markdown,
+}
+
+impl Markup {
+    pub fn to_html(self, source: &str) -> String {
+        match self {
+            Markup::Text => text_to_html(source),
+            Markup::Bbcode => bbcode_to_html(source),
+            Markup::Markdown => markdown_to_html(source),
+        }
+    }
+}
+
+fn text_to_html(text: &str) -> String {
+    let mut out = String::new();
+    push_text(&mut out, text);
+    out
+}
+
+fn markdown_to_html(markdown: &str) -> String {
+    let events = Parser::new_ext(markdown, Options::ENABLE_STRIKETHROUGH | Options::ENABLE_TABLES).map(
+        |event| match event {
+            Event::Html(html) | Event::InlineHtml(html) => Event::Text(html),
+            Event::Start(Tag::Link { link_type, dest_url, title, id }) => Event::Start(Tag::Link {
+                link_type,
+                dest_url: safe_dest(dest_url),
+                title,
+                id,
+            }),
+            Event::Start(Tag::Image { link_type, dest_url, title, id }) => Event::Start(Tag::Image {
+                link_type,
+                dest_url: safe_dest(dest_url),
+                title,
+                id,
+            }),
+            event => event,
+        },
+    );
+    let mut out = String::new();
+    html::push_html(&mut out, events);
+    out
+}
+
+fn safe_dest(url: CowStr<'_>) -> CowStr<'_> {
+    if safe_url(&url).is_some() {
+        url
+    } else {
+        CowStr::Borrowed("")
+    }
+}
+
+/// Tags that map onto an HTML element wrapping their content.
+fn simple_tag(name: &str) -> Option<(&'static str, &'static str)> {
+    Some(match name {
+        "b" => ("<strong>", "</strong>"),
+        "i" => ("<em>", "</em>"),
+        "u" => ("<u>", "</u>"),
+        "s" | "strike" => ("<s>", "</s>"),
+        "sub" => ("<sub>", "</sub>"),
+        "sup" => ("<sup>", "</sup>"),
+        _ => return None,
+    })
+}
+
+/// Tags that only style their content, which is kept without the styling.
+fn ignored_tag(name: &str) -> bool {
+    matches!(name, "color" | "size" | "font" | "center" | "left" | "right" | "align")
+}
+
+/// A BBCode tag like `[b]`, `[/b]` or `[quote="alice"]`.
+struct BbTag<'a> {
+    name: String,
+    argument: Option<&'a str>,
+    closing: bool,
+    /// Length of the tag in the source, brackets included.
+    len: usize,
+}
+
+fn parse_tag(source: &str) -> Option<BbTag<'_>> {
+    let end = source.find(']')?;
+    let inner = &source[1..end];
+    let (closing, inner) = match inner.strip_prefix('/') {
+        Some(inner) => (true, inner),
+        None => (false, inner),
+    };
+    let (name, argument) = match inner.split_once('=') {
+        Some((name, argument)) => (name, Some(argument.trim_matches(|c| c == '"' || c == '\''))),
+        None => (inner, None),
+    };
+    let valid = !name.is_empty() && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '*');
+    (valid && !(closing && argument.is_some())).then(|| BbTag {
+        name: name.to_ascii_lowercase(),
+        argument,
+        closing,
+        len: end + 1,
+    })
+}
+
+fn bbcode_to_html(source: &str) -> String {
+    let mut out = String::new();
+    // Open tags with the HTML that closes them.
+    let mut open: Vec<(String, &'static str)> = Vec::new();
+    let mut rest = source;
+
+    while let Some(start) = rest.find('[') {
+        let tag = parse_tag(&rest[start..]);
+        // Line breaks between list items aren't content.
+        let mut text = &rest[..start];
+        if open.last().is_some_and(|(name, _)| name == "list") {
+            text = text.trim();
+        } else if tag
+            .as_ref()
+            .is_some_and(|tag| tag.name == "*" || (tag.closing && tag.name == "list"))
+        {
+            text = text.trim_end();
+        }
+        push_text(&mut out, text);
+        rest = &rest[start..];
+
+        let Some(tag) = tag else {
+            out.push('[');
+            rest = &rest[1..];
+            continue;
+        };
+        let after = &rest[tag.len..];
+
+        if tag.closing {
+            if ignored_tag(&tag.name) {
+                rest = after;
+            } else if let Some(position) = open.iter().rposition(|(name, _)| *name == tag.name) {
+                for (_, close) in open.drain(position..).rev() {
+                    out.push_str(close);
+                }
+                rest = after;
+            } else {
+                push_text(&mut out, &rest[..tag.len]);
+                rest = after;
+            }
+            continue;
+        }
+
+        // Tags whose content is taken literally, up to their closing tag.
+        if matches!(tag.name.as_str(), "code" | "img") || (tag.name == "url" && tag.argument.is_none()) {
+            let close = format!("[/{}]", tag.name);
+            let (content, remaining) = match find_ignore_case(after, &close) {
+                Some(end) => (&after[..end], &after[end + close.len()..]),
+                None => (after, ""),
+            };
+            match tag.name.as_str() {
+                "code" => {
+                    out.push_str("<pre><code>");
+                    push_escaped(&mut out, content.trim_matches('\n'));
+                    out.push_str("</code></pre>");
+                }
+                "img" => match safe_url(content) {
+                    Some(src) => {
+                        out.push_str("<img src=\"");
+                        push_escaped(&mut out, src);
+                        out.push_str("\" alt=\"\">");
+                    }
+                    None => push_text(&mut out, content),
+                },
+                _ => match safe_url(content) {
+                    Some(href) => {
+                        out.push_str("<a href=\"");
+                        push_escaped(&mut out, href);
+                        out.push_str("\">");
+                        push_escaped(&mut out, href);
+                        out.push_str("</a>");
+                    }
+                    None => push_text(&mut out, content),
+                },
+            }
+            rest = remaining;
+            continue;
+        }
+
+        if ignored_tag(&tag.name) {
+            // The content is kept, the closing tag is skipped above.
+        } else if let Some((open_html, close_html)) = simple_tag(&tag.name) {
+            out.push_str(open_html);
+            open.push((tag.name, close_html));
+        } else if tag.name == "url" {
+            // Unsafe links keep their text and lose the link.
+            match tag.argument.and_then(safe_url) {
+                Some(href) => {
+                    let _ = write!(out, "<a href=\"{}\">", escape(href));
+                    open.push((tag.name, "</a>"));
+                }
+                None => open.push((tag.name, "")),
+            }
+        } else if tag.name == "quote" {
+            out.push_str("<blockquote>");
+            if let Some(author) = tag.argument.filter(|author| !author.is_empty()) {
+                let _ = write!(out, "<cite>{} wrote:</cite>", escape(author));
+            }
+            open.push((tag.name, "</blockquote>"));
+        } else if tag.name == "list" {
+            let list = match tag.argument {
+                Some("1") => ("<ol>", "</ol>"),
+                Some("a") => ("<ol type=\"a\">", "</ol>"),
+                _ => ("<ul>", "</ul>"),
+            };
+            out.push_str(list.0);
+            open.push((tag.name, list.1));
+        } else if tag.name == "*" && open.iter().any(|(name, _)| name == "list") {
+            if open.last().is_some_and(|(name, _)| name == "*") {
+                out.push_str("</li>");
+                open.pop();
+            }
+            out.push_str("<li>");
+            open.push((tag.name, "</li>"));
+        } else {
+            push_text(&mut out, &rest[..tag.len]);
+        }
+        rest = after;
+    }
+
+    push_text(&mut out, rest);
+    for (_, close) in open.into_iter().rev() {
+        out.push_str(close);
+    }
+    out
+}
+
+fn find_ignore_case(haystack: &str, needle: &str) -> Option<usize> {
+    haystack
+        .char_indices()
+        .map(|(index, _)| index)
+        .find(|&index| {
+            haystack
+                .get(index..index + needle.len())
+                .is_some_and(|candidate| candidate.eq_ignore_ascii_case(needle))
+        })
+}
+
+/// `url` if following it can't run script: it is relative or uses http,
+/// https or mailto.
+fn safe_url(url: &str) -> Option<&str> {
+    let url = url.trim();
+    let scheme_end = url.find([':', '/', '?', '#']);
+    let safe = match scheme_end {
+        Some(end) if url[end..].starts_with(':') => {
+            let scheme = url[..end].to_ascii_lowercase();
+            matches!(scheme.as_str(), "http" | "https" | "mailto")
+        }
+        _ => true,
+    };
+    (safe && !url.is_empty()).then_some(url)
+}
+
+/// Escapes `text` and turns line breaks into `<br>`.
+fn push_text(out: &mut String, text: &str) {
+    for (index, line) in text.split('\n').enumerate() {
+        if index > 0 {
+            out.push_str("<br>\n");
+        }
+        push_escaped(out, line.trim_end_matches('\r'));
+    }
+}
+
+fn push_escaped(out: &mut String, text: &str) {
+    for c in text.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\'' => out.push_str("&#39;"),
+            c => out.push(c),
+        }
+    }
+}
+
+fn escape(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    push_escaped(&mut out, text);
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bbcode(source: &str) -> String {
+        Markup::Bbcode.to_html(source)
+    }
+
+    fn markdown(source: &str) -> String {
+        Markup::Markdown.to_html(source)
+    }
+
+    #[test]
+    fn text_is_escaped_with_line_breaks() {
+        assert_eq!(Markup::Text.to_html("a < b & \"c\"\r\nd"), "a &lt; b &amp; &quot;c&quot;<br>\nd");
+    }
+
+    #[test]
+    fn bbcode_links_only_allow_safe_schemes() {
+        assert_eq!(
+            bbcode("[url=https://example.com/?a=1&b=2]x[/url]"),
+            r#"<a href="https://example.com/?a=1&amp;b=2">x</a>"#
+        );
+        assert_eq!(bbcode("[url=/faq#top]FAQ[/url]"), r#"<a href="/faq#top">FAQ</a>"#);
+        assert_eq!(bbcode("[url]mailto:a@example.com[/url]"), r#"<a href="mailto:a@example.com">mailto:a@example.com</a>"#);
+
+        for url in [
+            "javascript:alert(1)",
+            "JavaScript:alert(1)",
+            " javascript:alert(1)",
+            "java\tscript:alert(1)",
+            "vbscript:msgbox(1)",
+            "data:text/html;base64,PHNjcmlwdD4=",
+        ] {
+            assert_eq!(bbcode(&format!("[url={url}]x[/url]")), "x", "{url:?}");
+            assert!(!bbcode(&format!("[url]{url}[/url]")).contains("href"), "{url:?}");
+        }
+    }
+
+    #[test]
+    fn bbcode_urls_cannot_break_out_of_the_attribute() {
+        assert_eq!(
+            bbcode(r#"[url=https://example.com/" onmouseover="alert(1)]x[/url]"#),
+            r#"<a href="https://example.com/&quot; onmouseover=&quot;alert(1)">x</a>"#
+        );
+        assert_eq!(
+            bbcode(r#"[img]https://example.com/a.png" onerror="alert(1)[/img]"#),
+            r#"<img src="https://example.com/a.png&quot; onerror=&quot;alert(1)" alt="">"#
+        );
+    }
+
+    #[test]
+    fn bbcode_images_only_allow_safe_schemes() {
+        assert_eq!(bbcode("[img]https://example.com/a.png[/img]"), r#"<img src="https://example.com/a.png" alt="">"#);
+        assert_eq!(bbcode("[img]javascript:alert(1)[/img]"), "javascript:alert(1)");
+        assert_eq!(
+            bbcode("[IMG]data:image/svg+xml;base64,PHN2Zz4=[/img]"),
+            "data:image/svg+xml;base64,PHN2Zz4="
+        );
+    }
+
+    #[test]
+    fn bbcode_escapes_text_and_arguments() {
+        assert_eq!(bbcode("<script>alert(1)</script>"), "&lt;script&gt;alert(1)&lt;/script&gt;");
+        assert_eq!(
+            bbcode(r#"[quote="<b>alice</b>"]hi[/quote]"#),
+            "<blockquote><cite>&lt;b&gt;alice&lt;/b&gt; wrote:</cite>hi</blockquote>"
+        );
+        assert_eq!(bbcode("[color=red]red[/color] [size=200]big[/size]"), "red big");
+    }
+
+    #[test]
+    fn bbcode_closes_unbalanced_tags() {
+        assert_eq!(
+            bbcode("[b]bold [i]both[/b] plain[/i]"),
+            "<strong>bold <em>both</em></strong> plain[/i]"
+        );
+        assert_eq!(bbcode("[quote][b]never closed"), "<blockquote><strong>never closed</strong></blockquote>");
+        assert_eq!(bbcode("[/b] [b"), "[/b] [b");
+        assert_eq!(bbcode("[unknown]x[/unknown]"), "[unknown]x[/unknown]");
+    }
+
+    #[test]
+    fn bbcode_code_is_literal() {
+        assert_eq!(
+            bbcode("[code]\n<b>[b]x[/b]</b>\n[/code]after"),
+            "<pre><code>&lt;b&gt;[b]x[/b]&lt;/b&gt;</code></pre>after"
+        );
+        assert_eq!(bbcode("[code]unterminated [url=x]"), "<pre><code>unterminated [url=x]</code></pre>");
+    }
+
+    #[test]
+    fn bbcode_lists() {
+        assert_eq!(bbcode("[list]\n[*]a\n[*]b\n[/list]"), "<ul><li>a</li><li>b</li></ul>");
+        assert_eq!(bbcode("[list=1][*]a[/list]"), "<ol><li>a</li></ol>");
+        assert_eq!(bbcode("[*]not in a list"), "[*]not in a list");
+    }
+
+    #[test]
+    fn markdown_links_only_allow_safe_schemes() {
+        assert_eq!(markdown("[x](https://example.com)"), "<p><a href=\"https://example.com\">x</a></p>\n");
+        for source in [
+            "[x](javascript:alert(1))",
+            "[x](JAVASCRIPT:alert(1))",
+            "[x][1]\n\n[1]: javascript:alert(1)",
+            "<javascript:alert(1)>",
+            "[x](data:text/html;base64,PHNjcmlwdD4=)",
+        ] {
+            let html = markdown(source);
+            assert!(html.contains(r#"<a href="">"#), "{source:?}: {html}");
+        }
+        let html = markdown("![x](data:image/svg+xml;base64,PHN2Zz4=)");
+        assert!(html.contains(r#"<img src="""#), "{html}");
+    }
+
+    #[test]
+    fn markdown_raw_html_is_escaped() {
+        let html = markdown("<script>alert(1)</script>\n\nhi <img src=x onerror=alert(1)>");
+        assert!(!html.contains("<script") && !html.contains("<img"), "{html}");
+        assert!(html.contains("&lt;script&gt;alert(1)&lt;/script&gt;"), "{html}");
+        assert!(html.contains("hi &lt;img src=x onerror=alert(1)&gt;"), "{html}");
+    }
+}
@@ -0,0 +1,213 @@
+//! Imports from other forum software. Each source is first read into a
+//! [`Forum`], which is also a file format of its own so sources without a
+//! reader here can be converted with a script. A forum file is JSON lines,
+//! one record per line:
+//!
+//! ```text
+//! {"type": "user", "id": "2", "username": "alice"}
+//! {"type": "category", "id": "1", "title": "General", "description": "Anything goes", "markup": "text"}
+//! {"type": "thread", "id": "7", "category": "1", "author": "2", "title": "Hello"}
+//! {"type": "post", "id": "9", "thread": "7", "author": "2", "title": "Hello", "body": "**Hi**", "markup": "markdown"}
+//! ```
+//!
+//! Ids are strings, unique per record type, and only used to link the
+//! records to each other. A thread or post without an `author` is imported
+//! as written by a deleted user. `markup` is one of `text` (the default),
+//! `bbcode` and `markdown`; bodies and descriptions are stored as HTML.
+//! The order of the lines doesn't matter, posts are imported in file order.
+
+pub mod markup;
+pub mod phpbb;
+
+use std::{collections::HashMap, io::Read};
+
+use anyhow::{bail, Context};
+use serde::Deserialize;
+
+pub use markup::Markup;
+
+use crate::{
+    export::{ensure_empty, find_or_create, ImportSummary},
+    search::plain_text,
+    DbPool,
+};
+
+/// Users, categories, threads and posts read from another forum.
+#[derive(Debug, Clone, Default)]
+pub struct Forum {
+    pub users: Vec<ForumUser>,
+    pub categories: Vec<ForumCategory>,
+    pub threads: Vec<ForumThread>,
+    pub posts: Vec<ForumPost>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct ForumUser {
+    pub id: String,
+    pub username: String,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct ForumCategory {
+    pub id: String,
+    pub title: String,
+    #[serde(default)]
+    pub description: Option<String>,
+    #[serde(default)]
+    pub markup: Markup,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct ForumThread {
+    pub id: String,
+    pub category: String,
+    #[serde(default)]
+    pub author: Option<String>,
+    pub title: String,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct ForumPost {
+    pub id: String,
+    pub thread: String,
+    #[serde(default)]
+    pub author: Option<String>,
+    #[serde(default)]
+    pub title: String,
+    pub body: String,
+    #[serde(default)]
+    pub markup: Markup,
+}
+
+/// A line of a forum file.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum Record {
+    User(ForumUser),
+    Category(ForumCategory),
+    Thread(ForumThread),
+    Post(ForumPost),
+}
+
+impl Forum {
+    /// Reads a forum file.
+    pub fn read(mut reader: impl Read) -> anyhow::Result<Forum> {
+        let mut input = String::new();
+        reader.read_to_string(&mut input)?;
+
+        let mut forum = Forum::default();
+        for (number, line) in input.lines().enumerate() {
+            if line.trim().is_empty() {
+                continue;
+            }
+            let record = serde_json::from_str(line).with_context(|| format!("line {}", number + 1))?;
+            match record {
+                Record::User(user) => forum.users.push(user),
+                Record::Category(category) => forum.categories.push(category),
+                Record::Thread(thread) => forum.threads.push(thread),
+                Record::Post(post) => forum.posts.push(post),
+            }
+        }
+        Ok(forum)
+    }
+
+    /// Imports the forum in a single transaction, converting content to HTML.
+    /// Users are matched by name, new ones join `users` and have to reset
+    /// their password before they can log in. Like a dump, a forum is only
+    /// imported into a database without content.
+    pub async fn import(&self, pool: &DbPool) -> anyhow::Result<ImportSummary> {
+        let mut tx = pool.begin().await?;
+        ensure_empty(&mut tx).await?;
+
+        let mut summary = ImportSummary::default();
+
+        let mut users = HashMap::new();
+        for user in &self.users {
+            let (id, created) = find_or_create(
+                &mut tx,
+                "SELECT id FROM users WHERE username = $1",
+                // "!" is never a valid password hash.
+                "INSERT INTO users (username, password) VALUES ($1, '!') RETURNING id",
+                &user.username,
+            )
+            .await?;
+            if created {
+                sqlx::query("INSERT INTO users_groups (user_id, group_id) SELECT $1, id FROM groups WHERE name = 'users'")
+                    .bind(id)
+                    .execute(&mut *tx)
+                    .await?;
+            }
+            users.insert(user.id.as_str(), id);
+            summary.users += created as usize;
+        }
+
+        let mut categories = HashMap::new();
+        for category in &self.categories {
+            let description = category
+                .description
+                .as_deref()
+                .filter(|description| !description.trim().is_empty())
+                .map(|description| category.markup.to_html(description));
+            let id: i64 = sqlx::query_scalar("INSERT INTO categories (title, content) VALUES ($1, $2) RETURNING id")
+                .bind(&category.title)
+                .bind(description)
+                .fetch_one(&mut *tx)
+                .await?;
+            categories.insert(category.id.as_str(), id);
+            summary.categories += 1;
+        }
+
+        let mut threads = HashMap::new();
+        for thread in &self.threads {
+            let Some(category_id) = categories.get(thread.category.as_str()) else {
+                bail!("thread {} is in unknown category {}", thread.id, thread.category);
+            };
+            let id: i64 = sqlx::query_scalar(
+                "INSERT INTO threads (category_id, user_id, title) VALUES ($1, $2, $3) RETURNING id",
+            )
+            .bind(category_id)
+            .bind(author(&users, thread.author.as_deref(), "thread", &thread.id)?)
+            .bind(&thread.title)
+            .fetch_one(&mut *tx)
+            .await?;
+            threads.insert(thread.id.as_str(), id);
+            summary.threads += 1;
+        }
+
+        for post in &self.posts {
+            let Some(thread_id) = threads.get(post.thread.as_str()) else {
+                bail!("post {} is in unknown thread {}", post.id, post.thread);
+            };
+            let content = post.markup.to_html(&post.body);
+            sqlx::query("INSERT INTO posts (thread_id, user_id, title, content, search_text) VALUES ($1, $2, $3, $4, $5)")
+                .bind(thread_id)
+                .bind(author(&users, post.author.as_deref(), "post", &post.id)?)
+                .bind(&post.title)
+                .bind(&content)
+                .bind(plain_text(&content))
+                .execute(&mut *tx)
+                .await?;
+            summary.posts += 1;
+        }
+
+        tx.commit().await?;
+        Ok(summary)
+    }
+}
+
+/// The new id of the author of a thread or post, `None` if it has none.
+fn author(
+    users: &HashMap<&str, i64>,
+    author: Option<&str>,
+    kind: &str,
+    id: &str,
+) -> anyhow::Result<Option<i64>> {
+    author
+        .map(|author| {
+            users
+                .get(author)
+                .copied()
+                .with_context(|| format!("{kind} {id} is by unknown user {author}"))
+        })
+        .transpose()
+}
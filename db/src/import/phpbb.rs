@@ -0,0 +1,619 @@
+//! Reads a phpBB 3 database from a MySQL dump, as written by `mysqldump` or
+//! phpBB's own backup. Only the statements for the users, forums, topics and
+//! posts tables are interpreted; column names come from the `INSERT` column
+//! list or, without one, from the table's `CREATE TABLE`.
+
+use std::{
+    collections::{HashMap, HashSet},
+    sync::OnceLock,
+};
+
+use anyhow::{bail, Context};
+use regex::Regex;
+
+use super::{Forum, ForumCategory, ForumPost, ForumThread, ForumUser, Markup};
+
+/// `user_type` of bots and the anonymous user.
+const USER_IGNORE: i64 = 2;
+/// `forum_type` of forums holding topics, rather than other forums or a link.
+const FORUM_POST: i64 = 1;
+
+/// The columns read from each table, by table name without the prefix.
+const TABLES: &[(&str, &[&str])] = &[
+    ("users", &["user_id", "user_type", "username"]),
+    ("forums", &["forum_id", "forum_type", "forum_name", "forum_desc", "forum_desc_uid"]),
+    ("topics", &["topic_id", "forum_id", "topic_poster", "topic_title", "topic_moved_id"]),
+    (
+        "posts",
+        &["post_id", "topic_id", "poster_id", "post_subject", "post_text", "bbcode_uid"],
+    ),
+];
+
+/// Reads the forum from the dump of a phpBB database whose tables are named
+/// with `prefix`, usually `phpbb_`.
+pub fn read(dump: &str, prefix: &str) -> anyhow::Result<Forum> {
+    let mut tables = parse(dump, prefix)?;
+    let mut table = |name: &str| tables.remove(name).unwrap_or_default();
+
+    let mut forum = Forum::default();
+
+    for row in table("users") {
+        if row.int("user_type")? == USER_IGNORE {
+            continue;
+        }
+        forum.users.push(ForumUser {
+            id: row.text("user_id")?.to_owned(),
+            username: unescape(row.text("username")?),
+        });
+    }
+    let users: HashSet<&str> = forum.users.iter().map(|user| user.id.as_str()).collect();
+    // Posts by users that weren't imported, like guests, have no author.
+    let author = |id: &str| users.contains(id).then(|| id.to_owned());
+
+    for row in table("forums") {
+        if row.int("forum_type")? != FORUM_POST {
+            continue;
+        }
+        forum.categories.push(ForumCategory {
+            id: row.text("forum_id")?.to_owned(),
+            title: unescape(row.text("forum_name")?),
+            description: Some(clean(row.text("forum_desc")?, row.text("forum_desc_uid")?)),
+            markup: Markup::Bbcode,
+        });
+    }
+    let categories: HashSet<&str> = forum.categories.iter().map(|category| category.id.as_str()).collect();
+
+    for row in table("topics") {
+        let category = row.text("forum_id")?;
+        // Moved topics leave a shadow topic behind in the old forum.
+        if row.int("topic_moved_id")? != 0 || !categories.contains(category) {
+            continue;
+        }
+        forum.threads.push(ForumThread {
+            id: row.text("topic_id")?.to_owned(),
+            category: category.to_owned(),
+            author: author(row.text("topic_poster")?),
+            title: unescape(row.text("topic_title")?),
+        });
+    }
+    let threads: HashSet<&str> = forum.threads.iter().map(|thread| thread.id.as_str()).collect();
+
+    let mut posts = table("posts");
+    posts.sort_by_key(|row| row.int("post_id").unwrap_or_default());
+    for row in posts {
+        let thread = row.text("topic_id")?;
+        if !threads.contains(thread) {
+            continue;
+        }
+        forum.posts.push(ForumPost {
+            id: row.text("post_id")?.to_owned(),
+            thread: thread.to_owned(),
+            author: author(row.text("poster_id")?),
+            title: unescape(row.text("post_subject")?),
+            body: clean(row.text("post_text")?, row.text("bbcode_uid")?),
+            markup: Markup::Bbcode,
+        });
+    }
+
+    if forum.users.is_empty() && forum.categories.is_empty() {
+        bail!("the dump has no {prefix}users or {prefix}forums rows, is the table prefix right?");
+    }
+    Ok(forum)
+}
+
+/// Turns phpBB's stored post text back into plain BBCode. phpBB stores text
+/// HTML-escaped, tags suffixed with the post's `bbcode_uid`, and smilies and
+/// automatic links as HTML fragments between comments.
+fn clean(text: &str, uid: &str) -> String {
+    static SMILEY: OnceLock<Regex> = OnceLock::new();
+    static LINK: OnceLock<Regex> = OnceLock::new();
+    static COMMENT: OnceLock<Regex> = OnceLock::new();
+    let smiley = SMILEY.get_or_init(|| {
+        Regex::new(r#"<!-- s\S* --><img [^>]*?alt="([^"]*)"[^>]*><!-- s\S* -->"#).unwrap()
+    });
+    let link = LINK.get_or_init(|| {
+        Regex::new(r#"<!-- [lmwe] --><a [^>]*?href="([^"]*)"[^>]*>(.*?)</a><!-- [lmwe] -->"#).unwrap()
+    });
+    let comment = COMMENT.get_or_init(|| Regex::new(r"<!-- .*? -->").unwrap());
+
+    let mut text = text.to_owned();
+    if !uid.is_empty() {
+        text = text.replace(&format!(":{uid}]"), "]");
+    }
+    let text = text
+        .replace("[/*:m]", "")
+        .replace("[/list:u]", "[/list]")
+        .replace("[/list:o]", "[/list]");
+    let text = smiley.replace_all(&text, "$1");
+    let text = link.replace_all(&text, "[url=$1]$2[/url]");
+    let text = comment.replace_all(&text, "");
+    unescape(&text)
+}
+
+/// Undoes phpBB's `htmlspecialchars`, and the numeric references it uses
+/// to keep `:` and `.` in BBCode URLs from being parsed, like `http&#58;//`.
+/// All references are decoded in one pass, so `&amp;lt;` and `&#38;lt;`
+/// become `&lt;`.
+fn unescape(text: &str) -> String {
+    static REFERENCE: OnceLock<Regex> = OnceLock::new();
+    let reference = REFERENCE.get_or_init(|| Regex::new(r"&(#\d{1,7}|lt|gt|quot|amp);").unwrap());
+
+    reference
+        .replace_all(text, |captures: &regex::Captures| {
+            let decoded = match &captures[1] {
+                "lt" => Some('<'),
+                "gt" => Some('>'),
+                "quot" => Some('"'),
+                "amp" => Some('&'),
+                number => number[1..].parse().ok().and_then(char::from_u32),
+            };
+            decoded.map_or_else(|| captures[0].to_owned(), String::from)
+        })
+        .into_owned()
+}
+
+#[derive(Debug, Clone)]
+enum Value {
+    Null,
+    /// Strings and numbers alike, as written in the dump.
+    Text(String),
+}
+
+/// The columns listed in [`TABLES`] of one row.
+#[derive(Debug, Default)]
+struct Row(HashMap<&'static str, Value>);
+
+impl Row {
+    /// The column as text, empty for `NULL`.
+    fn text(&self, column: &str) -> anyhow::Result<&str> {
+        match self.0.get(column) {
+            Some(Value::Text(text)) => Ok(text),
+            Some(Value::Null) => Ok(""),
+            None => bail!("the dump has no column {column}"),
+        }
+    }
+
+    fn int(&self, column: &str) -> anyhow::Result<i64> {
+        let text = self.text(column)?;
+        if text.is_empty() {
+            return Ok(0);
+        }
+        text.parse().with_context(|| format!("{column} is not a number: {text}"))
+    }
+}
+
+/// The rows of the tables in [`TABLES`], by name without the prefix.
+fn parse(dump: &str, prefix: &str) -> anyhow::Result<HashMap<&'static str, Vec<Row>>> {
+    let mut parser = Parser { input: dump, pos: 0 };
+    let mut columns: HashMap<String, Vec<String>> = HashMap::new();
+    let mut tables: HashMap<&'static str, Vec<Row>> = HashMap::new();
+
+    loop {
+        parser.skip_space();
+        if parser.at_end() {
+            break;
+        }
+        if parser.keyword("CREATE") && parser.keyword("TABLE") {
+            if parser.keyword("IF") {
+                parser.keyword("NOT");
+                parser.keyword("EXISTS");
+            }
+            let name = parser.identifier()?;
+            let names = parser.column_definitions()?;
+            columns.insert(name, names);
+        } else if parser.keyword("INSERT") || parser.keyword("REPLACE") {
+            parser.keyword("IGNORE");
+            parser.keyword("INTO");
+            let name = parser.identifier()?;
+            let wanted = name
+                .strip_prefix(prefix)
+                .and_then(|name| TABLES.iter().find(|(table, _)| *table == name));
+            let Some(&(table, wanted)) = wanted else {
+                parser.skip_statement();
+                continue;
+            };
+
+            parser.skip_space();
+            let names = if parser.peek() == Some(b'(') {
+                parser.identifier_list()?
+            } else {
+                columns
+                    .get(&name)
+                    .cloned()
+                    .with_context(|| format!("INSERT INTO {name} without a column list before its CREATE TABLE"))?
+            };
+            if !(parser.keyword("VALUES") || parser.keyword("VALUE")) {
+                bail!("expected VALUES in INSERT INTO {name} at byte {}", parser.pos);
+            }
+
+            let rows = tables.entry(table).or_default();
+            loop {
+                let values = parser.tuple()?;
+                let mut row = Row::default();
+                for (column, value) in names.iter().zip(values) {
+                    if let Some(&column) = wanted.iter().find(|wanted| **wanted == column.as_str()) {
+                        row.0.insert(column, value);
+                    }
+                }
+                rows.push(row);
+
+                parser.skip_space();
+                if parser.peek() != Some(b',') {
+                    break;
+                }
+                parser.pos += 1;
+            }
+        }
+        parser.skip_statement();
+    }
+    Ok(tables)
+}
+
+/// Just enough of a MySQL parser for dumps: statements are skipped up to
+/// their `;`, except for the parts [`parse`] asks for.
+struct Parser<'a> {
+    input: &'a str,
+    pos: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn at_end(&self) -> bool {
+        self.pos >= self.input.len()
+    }
+
+    fn peek(&self) -> Option<u8> {
+        self.input.as_bytes().get(self.pos).copied()
+    }
+
+    fn rest(&self) -> &'a str {
+        &self.input[self.pos..]
+    }
+
+    /// Skips whitespace and comments, including `/*! ... */` ones.
+    fn skip_space(&mut self) {
+        loop {
+            let rest = self.rest();
+            let trimmed = rest.trim_start();
+            self.pos += rest.len() - trimmed.len();
+
+            let end = if trimmed.starts_with("--") || trimmed.starts_with('#') {
+                trimmed.find('\n').map_or(trimmed.len(), |end| end + 1)
+            } else if trimmed.starts_with("/*") {
+                trimmed.find("*/").map_or(trimmed.len(), |end| end + 2)
+            } else {
+                return;
+            };
+            self.pos += end;
+        }
+    }
+
+    /// Consumes `word` if it comes next, ignoring case.
+    fn keyword(&mut self, word: &str) -> bool {
+        self.skip_space();
+        let matches = self
+            .rest()
+            .get(..word.len())
+            .is_some_and(|next| next.eq_ignore_ascii_case(word))
+            && !self
+                .input
+                .as_bytes()
+                .get(self.pos + word.len())
+                .is_some_and(|&next| is_identifier_byte(next));
+        if matches {
+            self.pos += word.len();
+        }
+        matches
+    }
+
+    fn expect(&mut self, byte: u8) -> anyhow::Result<()> {
+        self.skip_space();
+        if self.peek() != Some(byte) {
+            bail!("expected '{}' at byte {}", byte as char, self.pos);
+        }
+        self.pos += 1;
+        Ok(())
+    }
+
+    /// A plain or backquoted name. Of `schema.table` only the table is kept.
+    fn identifier(&mut self) -> anyhow::Result<String> {
+        self.skip_space();
+        let name = if self.peek() == Some(b'`') {
+            self.quoted(b'`')?
+        } else {
+            let length = self
+                .rest()
+                .bytes()
+                .take_while(|&byte| is_identifier_byte(byte))
+                .count();
+            if length == 0 {
+                bail!("expected a name at byte {}", self.pos);
+            }
+            self.pos += length;
+            self.input[self.pos - length..self.pos].to_owned()
+        };
+        if self.peek() == Some(b'.') {
+            self.pos += 1;
+            return self.identifier();
+        }
+        Ok(name)
+    }
+
+    /// `(a, b, c)`
+    fn identifier_list(&mut self) -> anyhow::Result<Vec<String>> {
+        self.expect(b'(')?;
+        let mut names = Vec::new();
+        loop {
+            names.push(self.identifier()?);
+            self.skip_space();
+            match self.peek() {
+                Some(b',') => self.pos += 1,
+                Some(b')') => {
+                    self.pos += 1;
+                    return Ok(names);
+                }
+                _ => bail!("expected ',' or ')' at byte {}", self.pos),
+            }
+        }
+    }
+
+    /// The column names in the body of a `CREATE TABLE`, skipping keys.
+    fn column_definitions(&mut self) -> anyhow::Result<Vec<String>> {
+        const KEYS: &[&str] =
+            &["PRIMARY", "KEY", "UNIQUE", "INDEX", "FULLTEXT", "SPATIAL", "CONSTRAINT", "FOREIGN", "CHECK"];
+
+        self.expect(b'(')?;
+        let mut names = Vec::new();
+        loop {
+            self.skip_space();
+            let quoted = self.peek() == Some(b'`');
+            let name = self.identifier()?;
+            if quoted || !KEYS.iter().any(|key| name.eq_ignore_ascii_case(key)) {
+                names.push(name);
+            }
+            // The rest of the definition, up to the next top level comma.
+            let mut depth = 0;
+            loop {
+                self.skip_space();
+                match self.peek() {
+                    None => bail!("unterminated CREATE TABLE"),
+                    Some(quote @ (b'\'' | b'"' | b'`')) => {
+                        self.quoted(quote)?;
+                    }
+                    Some(b'(') => {
+                        depth += 1;
+                        self.pos += 1;
+                    }
+                    Some(b')') if depth == 0 => {
+                        self.pos += 1;
+                        return Ok(names);
+                    }
+                    Some(b')') => {
+                        depth -= 1;
+                        self.pos += 1;
+                    }
+                    Some(b',') if depth == 0 => {
+                        self.pos += 1;
+                        break;
+                    }
+                    Some(_) => self.pos += 1,
+                }
+            }
+        }
+    }
+
+    /// `(1, 'text', NULL)`
+    fn tuple(&mut self) -> anyhow::Result<Vec<Value>> {
+        self.expect(b'(')?;
+        let mut values = Vec::new();
+        loop {
+            values.push(self.value()?);
+            self.skip_space();
+            match self.peek() {
+                Some(b',') => self.pos += 1,
+                Some(b')') => {
+                    self.pos += 1;
+                    return Ok(values);
+                }
+                _ => bail!("expected ',' or ')' at byte {}", self.pos),
+            }
+        }
+    }
+
+    fn value(&mut self) -> anyhow::Result<Value> {
+        self.skip_space();
+        if let Some(quote @ (b'\'' | b'"')) = self.peek() {
+            return Ok(Value::Text(self.quoted(quote)?));
+        }
+        let length = self
+            .rest()
+            .bytes()
+            .take_while(|&byte| !matches!(byte, b',' | b')' | b'\'' | b'"') && !byte.is_ascii_whitespace())
+            .count();
+        let token = &self.input[self.pos..self.pos + length];
+        self.pos += length;
+
+        if token.eq_ignore_ascii_case("NULL") {
+            Ok(Value::Null)
+        } else if token.starts_with('_') {
+            // A character set introducer like `_binary 'data'`.
+            self.value()
+        } else {
+            Ok(Value::Text(token.to_owned()))
+        }
+    }
+
+    /// A string in `quote`s, with MySQL's backslash escapes and doubled
+    /// quotes.
+    fn quoted(&mut self, quote: u8) -> anyhow::Result<String> {
+        let start = self.pos;
+        let bytes = self.input.as_bytes();
+        let mut text = Vec::new();
+        self.pos += 1;
+        loop {
+            match bytes.get(self.pos) {
+                None => bail!("unterminated string starting at byte {start}"),
+                Some(&b'\\') if quote != b'`' => {
+                    let escaped = bytes.get(self.pos + 1).copied().unwrap_or(b'\\');
+                    text.push(match escaped {
+                        b'0' => 0,
+                        b'b' => 8,
+                        b'n' => b'\n',
+                        b'r' => b'\r',
+                        b't' => b'\t',
+                        b'Z' => 26,
+                        other => other,
+                    });
+                    self.pos += 2;
+                }
+                Some(&byte) if byte == quote => {
+                    if bytes.get(self.pos + 1) == Some(&quote) {
+                        text.push(quote);
+                        self.pos += 2;
+                    } else {
+                        self.pos += 1;
+                        return Ok(String::from_utf8_lossy(&text).into_owned());
+                    }
+                }
+                Some(&byte) => {
+                    text.push(byte);
+                    self.pos += 1;
+                }
+            }
+        }
+    }
+
+    /// Skips to after the next `;` outside of strings.
+    fn skip_statement(&mut self) {
+        while let Some(byte) = self.peek() {
+            match byte {
+                b';' => {
+                    self.pos += 1;
+                    return;
+                }
+                b'\'' | b'"' | b'`' => {
+                    if self.quoted(byte).is_err() {
+                        self.pos = self.input.len();
+                    }
+                }
+                _ => self.pos += 1,
+            }
+        }
+    }
+}
+
+fn is_identifier_byte(byte: u8) -> bool {
+    byte.is_ascii_alphanumeric() || byte == b'_' || byte == b'$'
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DUMP: &str = include_str!("fixtures/phpbb.sql");
+
+    fn html(post: &ForumPost) -> String {
+        post.markup.to_html(&post.body)
+    }
+
+    #[test]
+    fn reads_users_from_create_table_columns() {
+        let forum = read(DUMP, "phpbb_").unwrap();
+        let users: Vec<_> = forum
+            .users
+            .iter()
+            .map(|user| (user.id.as_str(), user.username.as_str()))
+            .collect();
+        // The anonymous user and bots are skipped.
+        assert_eq!(users, [("2", "admin"), ("48", "O'Brien & Sons"), ("52", "carol")]);
+    }
+
+    #[test]
+    fn reads_forums_holding_topics() {
+        let forum = read(DUMP, "phpbb_").unwrap();
+        assert_eq!(forum.categories.len(), 1);
+        let category = &forum.categories[0];
+        assert_eq!(category.id, "2");
+        assert_eq!(category.title, "General & Off-topic");
+        assert_eq!(category.description.as_deref(), Some("Talk about [b]anything[/b]"));
+    }
+
+    #[test]
+    fn skips_moved_topics_and_topics_outside_forums() {
+        let forum = read(DUMP, "phpbb_").unwrap();
+        let threads: Vec<_> = forum
+            .threads
+            .iter()
+            .map(|thread| (thread.id.as_str(), thread.author.as_deref(), thread.title.as_str()))
+            .collect();
+        assert_eq!(threads, [("1", Some("2"), "Welcome to phpBB3"), ("4", None, "Guests welcome")]);
+    }
+
+    #[test]
+    fn reads_posts_in_order_with_clean_bbcode() {
+        let forum = read(DUMP, "phpbb_").unwrap();
+        let ids: Vec<_> = forum.posts.iter().map(|post| post.id.as_str()).collect();
+        assert_eq!(ids, ["1", "2", "5"]);
+
+        let welcome = &forum.posts[0];
+        assert_eq!(
+            welcome.body,
+            "[b]Bold[/b] and a [url=http://example.com]link[/url]\nSee [url=https://www.phpbb.com/]https://www.phpbb.com/[/url]"
+        );
+        assert_eq!(
+            html(welcome),
+            "<strong>Bold</strong> and a <a href=\"http://example.com\">link</a><br>\n\
+             See <a href=\"https://www.phpbb.com/\">https://www.phpbb.com/</a>"
+        );
+
+        let reply = &forum.posts[1];
+        assert_eq!(reply.author.as_deref(), Some("48"));
+        assert_eq!(
+            reply.body,
+            "[list][*]one\n[*]two[/list]\n<script>alert(\"x\")</script> It's 'quoted'; done"
+        );
+        assert_eq!(
+            html(reply),
+            "<ul><li>one</li><li>two</li></ul><br>\n\
+             &lt;script&gt;alert(&quot;x&quot;)&lt;/script&gt; It&#39;s &#39;quoted&#39;; done"
+        );
+
+        // Written by the anonymous user, with a smiley.
+        let guest = &forum.posts[2];
+        assert_eq!(guest.author, None);
+        assert_eq!(guest.body, "Hello from a guest :)");
+    }
+
+    #[test]
+    fn insert_without_columns_needs_create_table() {
+        let dump = "INSERT INTO phpbb_users VALUES (2,0,'admin');";
+        let error = read(dump, "phpbb_").unwrap_err();
+        assert!(error.to_string().contains("without a column list"), "{error}");
+    }
+
+    #[test]
+    fn wrong_prefix_is_reported() {
+        let error = read(DUMP, "forum_").unwrap_err();
+        assert!(error.to_string().contains("table prefix"), "{error}");
+    }
+
+    #[test]
+    fn clean_strips_the_uid_and_restores_smilies_and_links() {
+        assert_eq!(clean("[i:abc]x[/i:abc] [i]y[/i]", "abc"), "[i]x[/i] [i]y[/i]");
+        assert_eq!(
+            clean(r#"<!-- s:lol: --><img src="{SMILIES_PATH}/icon_lol.gif" alt=":lol:" title="Laughing" /><!-- s:lol: -->"#, ""),
+            ":lol:"
+        );
+        assert_eq!(
+            clean(r#"<!-- w --><a class="postlink" href="http://www.example.com">www.example.com</a><!-- w -->"#, ""),
+            "[url=http://www.example.com]www.example.com[/url]"
+        );
+        assert_eq!(clean("<!-- ia0 -->attachment<!-- ia0 -->", ""), "attachment");
+    }
+
+    #[test]
+    fn unescape_decodes_each_reference_once() {
+        assert_eq!(unescape("&lt;b&gt; &amp;lt; &#039;a&#39; http&#58;//x&#46;y"), "<b> &lt; 'a' http://x.y");
+        assert_eq!(unescape("&#38;lt; &#38;#58;"), "&lt; &#58;");
+        assert_eq!(unescape("&#99999999; &#x41;"), "&#99999999; &#x41;");
+    }
+}
@@ -1,30 +1,171 @@
+//! Models and queries shared by the server and the admin CLI. The backend
+//! is chosen at compile time: SQLite by default, PostgreSQL with the
+//! `postgres` feature. Both run the same queries, written with `$N`
+//! placeholders and SQL the two understand, and keep their migrations in
+//! `migrations/sqlite` and `migrations/postgres` under the same versions.
+
 pub use sqlx;
-pub use sqlx::sqlite::SqlitePool;
-use std::path::Path;
+use std::{collections::HashSet, time::Duration};
 pub mod error;
+pub mod export;
 pub mod user;
+pub mod api_token;
 pub mod article;
+pub mod avatar;
+#[cfg(not(feature = "postgres"))]
+pub mod backup;
+pub mod category;
+pub mod group;
+pub mod identity;
+pub mod import;
+pub mod passkey;
+pub mod post;
+pub mod repo;
+pub mod search;
+pub mod session;
+pub mod thread;
 
 use sqlx::{
     migrate::Migrator,
     pool::PoolConnection,
-    sqlite::{SqliteConnectOptions, SqlitePoolOptions, SqliteRow},
-    Executor, Row, Sqlite, SqliteConnection,
+    Database,
 };
 
-pub type DbPool = SqlitePool;
+#[cfg(not(feature = "postgres"))]
+pub type Db = sqlx::Sqlite;
+#[cfg(feature = "postgres")]
+pub type Db = sqlx::Postgres;
+
+pub type DbPool = sqlx::Pool<Db>;
+pub type DbConnection = <Db as Database>::Connection;
+pub type DbRow = <Db as Database>::Row;
+
+/// How many connections the pool keeps and how long callers wait for them.
+#[derive(Debug, Clone)]
+pub struct PoolSettings {
+    pub max_connections: u32,
+    pub min_connections: u32,
+    /// How long to wait for a free connection before failing.
+    pub acquire_timeout: Duration,
+    /// How long SQLite retries a statement while another connection holds
+    /// the write lock. Ignored by PostgreSQL.
+    pub busy_timeout: Duration,
+}
+
+impl Default for PoolSettings {
+    fn default() -> Self {
+        Self {
+            max_connections: 10,
+            min_connections: 0,
+            acquire_timeout: Duration::from_secs(30),
+            busy_timeout: Duration::from_secs(5),
+        }
+    }
+}
+
+impl PoolSettings {
+    fn options(&self) -> sqlx::pool::PoolOptions<Db> {
+        sqlx::pool::PoolOptions::new()
+            .max_connections(self.max_connections)
+            .min_connections(self.min_connections)
+            .acquire_timeout(self.acquire_timeout)
+    }
+}
+
+/// Opens the database, creating it if needed. Every connection uses the
+/// write-ahead log so readers don't block the writer, and enforces the
+/// `references` in the schema, which SQLite ignores unless asked to.
+#[cfg(not(feature = "postgres"))]
+pub async fn pool(url: &str, settings: &PoolSettings) -> anyhow::Result<DbPool> {
+    use sqlx::sqlite::{SqliteConnectOptions, SqliteJournalMode, SqliteSynchronous};
+    use std::str::FromStr;
+
+    let options = SqliteConnectOptions::from_str(url)?
+        .create_if_missing(true)
+        .journal_mode(SqliteJournalMode::Wal)
+        // Durable enough with the WAL: a power loss can only lose the last
+        // transactions, never corrupt the database.
+        .synchronous(SqliteSynchronous::Normal)
+        .foreign_keys(true)
+        .busy_timeout(settings.busy_timeout);
+    let pool = settings.options().connect_with(options).await?;
+    Ok(pool)
+}
 
-pub async fn pool() -> anyhow::Result<DbPool> {
-    let options = SqliteConnectOptions::new()
-    .filename(&dotenvy::var("DATABASE_URL")?)
-        .create_if_missing(true);
-    let pool = SqlitePool::connect_with(options).await?;
+#[cfg(feature = "postgres")]
+pub async fn pool(url: &str, settings: &PoolSettings) -> anyhow::Result<DbPool> {
+    let pool = settings.options().connect(url).await?;
     Ok(pool)
 }
 
-pub async fn migrate(mut conn: PoolConnection<Sqlite>) -> Result<(), sqlx::Error> {
-    let path = Path::new("db/migrations");
-    let migrator = Migrator::new(path).await?;
-    migrator.run(&mut conn).await?;
+/// Lets SQLite refresh the statistics of the query planner and moves the
+/// write-ahead log back into the database file, so it doesn't grow without
+/// bounds under constant load. Meant to run periodically.
+#[cfg(not(feature = "postgres"))]
+pub async fn optimize(pool: &DbPool) -> Result<(), sqlx::Error> {
+    sqlx::query("PRAGMA optimize").execute(pool).await?;
+    sqlx::query("PRAGMA wal_checkpoint(TRUNCATE)").execute(pool).await?;
     Ok(())
 }
+
+/// Whether `url` can be opened by the backend this crate was built with.
+pub fn supports_url(url: &str) -> bool {
+    if cfg!(feature = "postgres") {
+        url.starts_with("postgres://") || url.starts_with("postgresql://")
+    } else {
+        url.starts_with("sqlite:")
+    }
+}
+
+/// The migrations of the selected backend, embedded at compile time so the
+/// binary doesn't depend on the directory it's started from.
+#[cfg(not(feature = "postgres"))]
+pub static MIGRATOR: Migrator = sqlx::migrate!("./migrations/sqlite");
+#[cfg(feature = "postgres")]
+pub static MIGRATOR: Migrator = sqlx::migrate!("./migrations/postgres");
+
+/// Applies pending migrations, then fills in data they couldn't compute in
+/// SQL.
+pub async fn migrate(mut conn: PoolConnection<Db>) -> Result<(), sqlx::Error> {
+    MIGRATOR.run(&mut conn).await?;
+    search::fill_search_text(&mut conn).await?;
+    Ok(())
+}
+
+#[cfg(not(feature = "postgres"))]
+const MIGRATIONS_TABLE_EXISTS: &str =
+    "SELECT EXISTS (SELECT 1 FROM sqlite_master WHERE type = 'table' AND name = '_sqlx_migrations')";
+#[cfg(feature = "postgres")]
+const MIGRATIONS_TABLE_EXISTS: &str = "SELECT to_regclass('_sqlx_migrations') IS NOT NULL";
+
+/// A known migration and whether it has been applied to the database.
+#[derive(Debug, Clone)]
+pub struct MigrationStatus {
+    pub version: i64,
+    pub description: String,
+    pub applied: bool,
+}
+
+/// Which migrations have been applied, read without changing the database so
+/// probes can call it. A database that was never migrated has none applied.
+pub async fn migration_status(pool: &DbPool) -> Result<Vec<MigrationStatus>, sqlx::Error> {
+    let exists: bool = sqlx::query_scalar(MIGRATIONS_TABLE_EXISTS).fetch_one(pool).await?;
+    let applied: HashSet<i64> = if exists {
+        sqlx::query_scalar("SELECT version FROM _sqlx_migrations WHERE success")
+            .fetch_all(pool)
+            .await?
+            .into_iter()
+            .collect()
+    } else {
+        HashSet::new()
+    };
+
+    Ok(MIGRATOR
+        .iter()
+        .map(|migration| MigrationStatus {
+            version: migration.version,
+            description: migration.description.to_string(),
+            applied: applied.contains(&migration.version),
+        })
+        .collect())
+}
@@ -0,0 +1,86 @@
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+
+use crate::DbPool;
+
+/// A WebAuthn credential registered by a user. The credential itself is kept
+/// as an opaque serialized blob so the `db` crate does not need to know about
+/// the WebAuthn types.
+#[derive(Clone, Serialize, Deserialize, FromRow, Debug)]
+pub struct DbPasskey {
+    pub id: i64,
+    pub user_id: i64,
+    pub credential_id: String,
+    pub name: String,
+    #[serde(skip_serializing)]
+    pub passkey: String,
+    pub created_at: i64,
+    pub last_used_at: Option<i64>,
+}
+
+impl DbPasskey {
+    pub async fn create(
+        user_id: i64,
+        credential_id: String,
+        name: String,
+        passkey: String,
+        created_at: i64,
+        pool: &DbPool,
+    ) -> Result<DbPasskey, sqlx::Error> {
+        sqlx::query_as(
+            r#"INSERT INTO passkeys (user_id, credential_id, name, passkey, created_at) VALUES ($1, $2, $3, $4, $5) RETURNING *"#,
+        )
+        .bind(user_id)
+        .bind(credential_id)
+        .bind(name)
+        .bind(passkey)
+        .bind(created_at)
+        .fetch_one(pool)
+        .await
+    }
+
+    pub async fn find_by_credential_id(
+        credential_id: &str,
+        pool: &DbPool,
+    ) -> Result<Option<DbPasskey>, sqlx::Error> {
+        sqlx::query_as(r#"SELECT * FROM passkeys WHERE credential_id = $1"#)
+            .bind(credential_id)
+            .fetch_optional(pool)
+            .await
+    }
+
+    pub async fn find_by_user(user_id: i64, pool: &DbPool) -> Result<Vec<DbPasskey>, sqlx::Error> {
+        sqlx::query_as(r#"SELECT * FROM passkeys WHERE user_id = $1 ORDER BY created_at"#)
+            .bind(user_id)
+            .fetch_all(pool)
+            .await
+    }
+
+    /// Stores the updated credential (e.g. a bumped signature counter) and
+    /// records when it was last used to log in.
+    pub async fn touch(
+        id: i64,
+        passkey: String,
+        last_used_at: i64,
+        pool: &DbPool,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query(r#"UPDATE passkeys SET passkey = $1, last_used_at = $2 WHERE id = $3"#)
+            .bind(passkey)
+            .bind(last_used_at)
+            .bind(id)
+            .execute(pool)
+            .await?;
+        Ok(())
+    }
+
+    /// Deletes a passkey, scoped to its owner so users can only remove their
+    /// own credentials. Returns whether a row was removed.
+    pub async fn delete(id: i64, user_id: i64, pool: &DbPool) -> Result<bool, sqlx::Error> {
+        let result = sqlx::query("DELETE FROM passkeys WHERE id = $1 AND user_id = $2")
+            .bind(id)
+            .bind(user_id)
+            .execute(pool)
+            .await?;
+        Ok(result.rows_affected() > 0)
+    }
+}
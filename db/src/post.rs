@@ -0,0 +1,104 @@
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+
+use crate::{search::plain_text, DbPool};
+
+#[derive(Clone, Serialize, Deserialize, FromRow, Debug)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct Post {
+    pub id: i64,
+    pub thread_id: Option<i64>,
+    pub user_id: Option<i64>,
+    pub title: String,
+    pub content: String,
+}
+
+/// Optional filters for listing posts. `None` matches everything.
+#[derive(Clone, Debug, Default, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::IntoParams), into_params(parameter_in = Query))]
+pub struct PostFilter {
+    pub thread_id: Option<i64>,
+    pub user_id: Option<i64>,
+}
+
+impl Post {
+    pub async fn create(
+        thread_id: i64,
+        user_id: i64,
+        title: String,
+        content: String,
+        pool: &DbPool,
+    ) -> Result<Post, sqlx::Error> {
+        let search_text = plain_text(&content);
+        sqlx::query_as(
+            r#"INSERT INTO posts (thread_id, user_id, title, content, search_text) VALUES ($1, $2, $3, $4, $5) RETURNING *"#,
+        )
+        .bind(thread_id)
+        .bind(user_id)
+        .bind(title)
+        .bind(content)
+        .bind(search_text)
+        .fetch_one(pool)
+        .await
+    }
+
+    pub async fn find_by_id(id: i64, pool: &DbPool) -> Result<Option<Post>, sqlx::Error> {
+        sqlx::query_as(r#"SELECT * FROM posts WHERE id = $1"#)
+            .bind(id)
+            .fetch_optional(pool)
+            .await
+    }
+
+    pub async fn list(
+        filter: &PostFilter,
+        limit: i64,
+        offset: i64,
+        pool: &DbPool,
+    ) -> Result<Vec<Post>, sqlx::Error> {
+        sqlx::query_as(
+            r#"SELECT * FROM posts
+            WHERE ($1 IS NULL OR thread_id = $1) AND ($2 IS NULL OR user_id = $2)
+            ORDER BY id LIMIT $3 OFFSET $4"#,
+        )
+        .bind(filter.thread_id)
+        .bind(filter.user_id)
+        .bind(limit)
+        .bind(offset)
+        .fetch_all(pool)
+        .await
+    }
+
+    pub async fn count(filter: &PostFilter, pool: &DbPool) -> Result<i64, sqlx::Error> {
+        sqlx::query_scalar(
+            r#"SELECT COUNT(*) FROM posts WHERE ($1 IS NULL OR thread_id = $1) AND ($2 IS NULL OR user_id = $2)"#,
+        )
+        .bind(filter.thread_id)
+        .bind(filter.user_id)
+        .fetch_one(pool)
+        .await
+    }
+
+    pub async fn update(
+        id: i64,
+        title: String,
+        content: String,
+        pool: &DbPool,
+    ) -> Result<Post, sqlx::Error> {
+        let search_text = plain_text(&content);
+        sqlx::query_as(r#"UPDATE posts SET title = $1, content = $2, search_text = $3 WHERE id = $4 RETURNING *"#)
+            .bind(title)
+            .bind(content)
+            .bind(search_text)
+            .bind(id)
+            .fetch_one(pool)
+            .await
+    }
+
+    pub async fn delete(id: i64, pool: &DbPool) -> Result<(), sqlx::Error> {
+        sqlx::query("DELETE FROM posts WHERE id = $1")
+            .bind(id)
+            .execute(pool)
+            .await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,849 @@
+use std::sync::{Mutex, MutexGuard};
+
+use async_trait::async_trait;
+
+use super::{
+    ArticleRepo, AvatarRepo, CategoryRepo, IdentityRepo, PasskeyRepo, PostRepo, RepoResult,
+    SearchRepo, SessionRepo, ThreadRepo, TokenRepo, UserRepo,
+};
+use crate::{
+    api_token::DbApiToken,
+    article::Article,
+    avatar::Avatar,
+    category::Category,
+    error::DbError,
+    identity::{DbIdentity, IdentityLogin, SignIn},
+    passkey::DbPasskey,
+    post::{Post, PostFilter},
+    search::{plain_text, SearchFilter, SearchHit},
+    session::DbSession,
+    thread::{Thread, ThreadFilter},
+    user::{DbPermission, DbUser, PublicUser},
+};
+
+/// In-memory stand-in for [`super::SqlRepository`], for tests. Orderings,
+/// filters and the article upsert behave like the SQL queries. Users,
+/// groups, categories and permissions have no repository methods for
+/// creating them, so tests seed them with [`MemoryRepository::add_user`],
+/// [`MemoryRepository::add_group`], [`MemoryRepository::add_category`] and
+/// [`MemoryRepository::grant`]. Groups only track membership, permissions
+/// are granted to users directly.
+///
+/// There is no session store behind the fake: recorded sessions never expire
+/// and revoking one only forgets it. Search matches rows containing every
+/// word of the query, case-insensitively, without ranking or highlighting.
+#[derive(Default)]
+pub struct MemoryRepository {
+    tables: Mutex<Tables>,
+}
+
+#[derive(Default)]
+struct Tables {
+    next_id: i64,
+    users: Vec<DbUser>,
+    categories: Vec<Category>,
+    threads: Vec<Thread>,
+    posts: Vec<Post>,
+    articles: Vec<Article>,
+    /// Pairs of user id and permission.
+    permissions: Vec<(i64, DbPermission)>,
+    groups: Vec<String>,
+    /// Pairs of user id and group name.
+    memberships: Vec<(i64, String)>,
+    identities: Vec<DbIdentity>,
+    sessions: Vec<DbSession>,
+    tokens: Vec<DbApiToken>,
+    passkeys: Vec<DbPasskey>,
+    avatars: Vec<Avatar>,
+}
+
+impl Tables {
+    /// Ids are unique across all tables, which is stricter than the database
+    /// and catches handlers mixing them up.
+    fn next_id(&mut self) -> i64 {
+        self.next_id += 1;
+        self.next_id
+    }
+}
+
+impl MemoryRepository {
+    pub fn add_user(&self, username: &str, password: &str) -> DbUser {
+        let mut tables = self.tables();
+        let user = DbUser {
+            id: tables.next_id(),
+            username: username.to_owned(),
+            password: password.to_owned(),
+        };
+        tables.users.push(user.clone());
+        user
+    }
+
+    pub fn add_category(&self, title: &str, content: Option<&str>) -> Category {
+        let mut tables = self.tables();
+        let category = Category {
+            id: tables.next_id(),
+            title: title.to_owned(),
+            content: content.map(str::to_owned),
+        };
+        tables.categories.push(category.clone());
+        category
+    }
+
+    pub fn add_group(&self, name: &str) {
+        self.tables().groups.push(name.to_owned());
+    }
+
+    /// The names of the groups the user is in, sorted.
+    pub fn group_names(&self, user_id: i64) -> Vec<String> {
+        let mut names: Vec<String> = self
+            .tables()
+            .memberships
+            .iter()
+            .filter(|(id, _)| *id == user_id)
+            .map(|(_, name)| name.clone())
+            .collect();
+        names.sort();
+        names
+    }
+
+    /// Grants the user a permission, as if a group of theirs had it.
+    pub fn grant(&self, user_id: i64, permission: &str) {
+        self.tables()
+            .permissions
+            .push((user_id, DbPermission::from(permission)));
+    }
+
+    fn tables(&self) -> MutexGuard<'_, Tables> {
+        // A panicking test shouldn't poison the fake for everyone else.
+        self.tables.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// Applies `LIMIT` and `OFFSET` to rows that are already in order.
+fn page<T: Clone>(rows: impl Iterator<Item = T>, limit: i64, offset: i64) -> Vec<T> {
+    rows.skip(offset.max(0) as usize)
+        .take(limit.max(0) as usize)
+        .collect()
+}
+
+fn matches_username(user: &DbUser, prefix: Option<&str>) -> bool {
+    // LIKE is case-insensitive for ASCII in SQLite.
+    prefix.map_or(true, |prefix| {
+        user.username
+            .to_ascii_lowercase()
+            .starts_with(&prefix.to_ascii_lowercase())
+    })
+}
+
+/// The words of a search query, lowercased. Quotes and a trailing `*` are
+/// dropped, phrases match like their words would on their own.
+fn search_terms(query: &str) -> Vec<String> {
+    query
+        .split(|c: char| c.is_whitespace() || c == '"')
+        .map(|word| word.trim_end_matches('*').to_lowercase())
+        .filter(|word| word.chars().any(char::is_alphanumeric))
+        .collect()
+}
+
+fn matches_terms(terms: &[String], title: &str, text: &str) -> bool {
+    let title = title.to_lowercase();
+    let text = text.to_lowercase();
+    terms
+        .iter()
+        .all(|term| title.contains(term.as_str()) || text.contains(term.as_str()))
+}
+
+fn matches_thread(thread: &Thread, filter: &ThreadFilter) -> bool {
+    filter.category_id.map_or(true, |id| thread.category_id == Some(id))
+        && filter.user_id.map_or(true, |id| thread.user_id == Some(id))
+}
+
+fn matches_post(post: &Post, filter: &PostFilter) -> bool {
+    filter.thread_id.map_or(true, |id| post.thread_id == Some(id))
+        && filter.user_id.map_or(true, |id| post.user_id == Some(id))
+}
+
+#[async_trait]
+impl UserRepo for MemoryRepository {
+    async fn find_by_id(&self, id: i64) -> RepoResult<Option<DbUser>> {
+        Ok(self.tables().users.iter().find(|user| user.id == id).cloned())
+    }
+
+    async fn find_by_username(&self, username: &str) -> RepoResult<Option<DbUser>> {
+        Ok(self
+            .tables()
+            .users
+            .iter()
+            .find(|user| user.username == username)
+            .cloned())
+    }
+
+    async fn list(&self, username: Option<&str>, limit: i64, offset: i64) -> RepoResult<Vec<PublicUser>> {
+        let tables = self.tables();
+        let users = tables
+            .users
+            .iter()
+            .filter(|user| matches_username(user, username))
+            .cloned()
+            .map(PublicUser::from);
+        Ok(page(users, limit, offset))
+    }
+
+    async fn count(&self, username: Option<&str>) -> RepoResult<i64> {
+        let tables = self.tables();
+        Ok(tables
+            .users
+            .iter()
+            .filter(|user| matches_username(user, username))
+            .count() as i64)
+    }
+
+    async fn permissions(&self, user_id: i64) -> RepoResult<Vec<DbPermission>> {
+        let tables = self.tables();
+        let mut permissions: Vec<DbPermission> = Vec::new();
+        for (id, permission) in &tables.permissions {
+            if *id == user_id && !permissions.contains(permission) {
+                permissions.push(permission.clone());
+            }
+        }
+        Ok(permissions)
+    }
+}
+
+#[async_trait]
+impl CategoryRepo for MemoryRepository {
+    async fn find_by_id(&self, id: i64) -> RepoResult<Option<Category>> {
+        Ok(self
+            .tables()
+            .categories
+            .iter()
+            .find(|category| category.id == id)
+            .cloned())
+    }
+
+    async fn list(&self, limit: i64, offset: i64) -> RepoResult<Vec<Category>> {
+        Ok(page(self.tables().categories.iter().cloned(), limit, offset))
+    }
+
+    async fn count(&self) -> RepoResult<i64> {
+        Ok(self.tables().categories.len() as i64)
+    }
+}
+
+#[async_trait]
+impl ThreadRepo for MemoryRepository {
+    async fn create(&self, category_id: i64, user_id: i64, title: String) -> RepoResult<Thread> {
+        let mut tables = self.tables();
+        let thread = Thread {
+            id: tables.next_id(),
+            category_id: Some(category_id),
+            user_id: Some(user_id),
+            title,
+        };
+        tables.threads.push(thread.clone());
+        Ok(thread)
+    }
+
+    async fn find_by_id(&self, id: i64) -> RepoResult<Option<Thread>> {
+        Ok(self
+            .tables()
+            .threads
+            .iter()
+            .find(|thread| thread.id == id)
+            .cloned())
+    }
+
+    async fn list(&self, filter: &ThreadFilter, limit: i64, offset: i64) -> RepoResult<Vec<Thread>> {
+        let tables = self.tables();
+        let threads = tables
+            .threads
+            .iter()
+            .rev()
+            .filter(|thread| matches_thread(thread, filter))
+            .cloned();
+        Ok(page(threads, limit, offset))
+    }
+
+    async fn count(&self, filter: &ThreadFilter) -> RepoResult<i64> {
+        let tables = self.tables();
+        Ok(tables
+            .threads
+            .iter()
+            .filter(|thread| matches_thread(thread, filter))
+            .count() as i64)
+    }
+
+    async fn update(&self, id: i64, title: String) -> RepoResult<Thread> {
+        let mut tables = self.tables();
+        let thread = tables
+            .threads
+            .iter_mut()
+            .find(|thread| thread.id == id)
+            .ok_or(DbError::NotFound)?;
+        thread.title = title;
+        Ok(thread.clone())
+    }
+
+    async fn delete(&self, id: i64) -> RepoResult<()> {
+        let mut tables = self.tables();
+        tables.posts.retain(|post| post.thread_id != Some(id));
+        tables.threads.retain(|thread| thread.id != id);
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl PostRepo for MemoryRepository {
+    async fn create(&self, thread_id: i64, user_id: i64, title: String, content: String) -> RepoResult<Post> {
+        let mut tables = self.tables();
+        let post = Post {
+            id: tables.next_id(),
+            thread_id: Some(thread_id),
+            user_id: Some(user_id),
+            title,
+            content,
+        };
+        tables.posts.push(post.clone());
+        Ok(post)
+    }
+
+    async fn find_by_id(&self, id: i64) -> RepoResult<Option<Post>> {
+        Ok(self.tables().posts.iter().find(|post| post.id == id).cloned())
+    }
+
+    async fn list(&self, filter: &PostFilter, limit: i64, offset: i64) -> RepoResult<Vec<Post>> {
+        let tables = self.tables();
+        let posts = tables
+            .posts
+            .iter()
+            .filter(|post| matches_post(post, filter))
+            .cloned();
+        Ok(page(posts, limit, offset))
+    }
+
+    async fn count(&self, filter: &PostFilter) -> RepoResult<i64> {
+        let tables = self.tables();
+        Ok(tables
+            .posts
+            .iter()
+            .filter(|post| matches_post(post, filter))
+            .count() as i64)
+    }
+
+    async fn update(&self, id: i64, title: String, content: String) -> RepoResult<Post> {
+        let mut tables = self.tables();
+        let post = tables
+            .posts
+            .iter_mut()
+            .find(|post| post.id == id)
+            .ok_or(DbError::NotFound)?;
+        post.title = title;
+        post.content = content;
+        Ok(post.clone())
+    }
+
+    async fn delete(&self, id: i64) -> RepoResult<()> {
+        self.tables().posts.retain(|post| post.id != id);
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl ArticleRepo for MemoryRepository {
+    async fn create(
+        &self,
+        user_id: i64,
+        title: String,
+        editor_content: serde_json::Value,
+        content: String,
+    ) -> RepoResult<Article> {
+        let mut tables = self.tables();
+        let article = Article {
+            id: tables.next_id(),
+            user_id,
+            title,
+            editor_content,
+            content,
+        };
+        tables.articles.push(article.clone());
+        Ok(article)
+    }
+
+    async fn upsert(
+        &self,
+        user_id: i64,
+        title: String,
+        editor_content: serde_json::Value,
+        content: String,
+    ) -> RepoResult<Article> {
+        let mut tables = self.tables();
+        let existing = tables
+            .articles
+            .iter_mut()
+            .find(|article| article.user_id == user_id && article.title == title);
+        if let Some(article) = existing {
+            article.editor_content = editor_content;
+            article.content = content;
+            return Ok(article.clone());
+        }
+
+        let article = Article {
+            id: tables.next_id(),
+            user_id,
+            title,
+            editor_content,
+            content,
+        };
+        tables.articles.push(article.clone());
+        Ok(article)
+    }
+
+    async fn find_by_id(&self, id: i64) -> RepoResult<Option<Article>> {
+        Ok(self
+            .tables()
+            .articles
+            .iter()
+            .find(|article| article.id == id)
+            .cloned())
+    }
+
+    async fn list(&self, user_id: Option<i64>, limit: i64, offset: i64) -> RepoResult<Vec<Article>> {
+        let tables = self.tables();
+        let articles = tables
+            .articles
+            .iter()
+            .rev()
+            .filter(|article| user_id.map_or(true, |id| article.user_id == id))
+            .cloned();
+        Ok(page(articles, limit, offset))
+    }
+
+    async fn count(&self, user_id: Option<i64>) -> RepoResult<i64> {
+        let tables = self.tables();
+        Ok(tables
+            .articles
+            .iter()
+            .filter(|article| user_id.map_or(true, |id| article.user_id == id))
+            .count() as i64)
+    }
+
+    async fn update(
+        &self,
+        id: i64,
+        title: String,
+        editor_content: serde_json::Value,
+        content: String,
+    ) -> RepoResult<Article> {
+        let mut tables = self.tables();
+        let article = tables
+            .articles
+            .iter_mut()
+            .find(|article| article.id == id)
+            .ok_or(DbError::NotFound)?;
+        article.title = title;
+        article.editor_content = editor_content;
+        article.content = content;
+        Ok(article.clone())
+    }
+
+    async fn delete(&self, id: i64) -> RepoResult<()> {
+        self.tables().articles.retain(|article| article.id != id);
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl SessionRepo for MemoryRepository {
+    async fn record(
+        &self,
+        session_id: &str,
+        user_id: i64,
+        user_agent: Option<&str>,
+        ip: Option<&str>,
+        now: i64,
+    ) -> RepoResult<()> {
+        let mut tables = self.tables();
+        let existing = tables
+            .sessions
+            .iter_mut()
+            .find(|session| session.session_id == session_id);
+        if let Some(session) = existing {
+            session.user_id = user_id;
+            session.last_seen_at = now;
+            session.user_agent = user_agent.map(str::to_owned);
+            session.ip = ip.map(str::to_owned);
+            return Ok(());
+        }
+
+        let session = DbSession {
+            id: tables.next_id(),
+            session_id: session_id.to_owned(),
+            user_id,
+            created_at: now,
+            last_seen_at: now,
+            user_agent: user_agent.map(str::to_owned),
+            ip: ip.map(str::to_owned),
+        };
+        tables.sessions.push(session);
+        Ok(())
+    }
+
+    async fn last_seen(&self, session_id: &str) -> RepoResult<Option<i64>> {
+        Ok(self
+            .tables()
+            .sessions
+            .iter()
+            .find(|session| session.session_id == session_id)
+            .map(|session| session.last_seen_at))
+    }
+
+    async fn find_active_by_user(&self, user_id: i64, _now: i64) -> RepoResult<Vec<DbSession>> {
+        let mut sessions: Vec<DbSession> = self
+            .tables()
+            .sessions
+            .iter()
+            .filter(|session| session.user_id == user_id)
+            .cloned()
+            .collect();
+        sessions.sort_by_key(|session| std::cmp::Reverse(session.last_seen_at));
+        Ok(sessions)
+    }
+
+    async fn find_by_id(&self, id: i64) -> RepoResult<Option<DbSession>> {
+        Ok(self
+            .tables()
+            .sessions
+            .iter()
+            .find(|session| session.id == id)
+            .cloned())
+    }
+
+    async fn revoke(&self, session: &DbSession) -> RepoResult<()> {
+        self.tables().sessions.retain(|other| other.id != session.id);
+        Ok(())
+    }
+
+    async fn revoke_all_for_user(&self, user_id: i64) -> RepoResult<u64> {
+        let mut tables = self.tables();
+        let before = tables.sessions.len();
+        tables.sessions.retain(|session| session.user_id != user_id);
+        Ok((before - tables.sessions.len()) as u64)
+    }
+}
+
+#[async_trait]
+impl TokenRepo for MemoryRepository {
+    async fn create(
+        &self,
+        user_id: i64,
+        name: &str,
+        token_hash: &str,
+        scopes: &str,
+        created_at: i64,
+    ) -> RepoResult<DbApiToken> {
+        let mut tables = self.tables();
+        let token = DbApiToken {
+            id: tables.next_id(),
+            user_id,
+            name: name.to_owned(),
+            token_hash: token_hash.to_owned(),
+            scopes: scopes.to_owned(),
+            created_at,
+            last_used_at: None,
+        };
+        tables.tokens.push(token.clone());
+        Ok(token)
+    }
+
+    async fn find_by_hash(&self, token_hash: &str) -> RepoResult<Option<DbApiToken>> {
+        Ok(self
+            .tables()
+            .tokens
+            .iter()
+            .find(|token| token.token_hash == token_hash)
+            .cloned())
+    }
+
+    async fn find_by_user(&self, user_id: i64) -> RepoResult<Vec<DbApiToken>> {
+        let mut tokens: Vec<DbApiToken> = self
+            .tables()
+            .tokens
+            .iter()
+            .rev()
+            .filter(|token| token.user_id == user_id)
+            .cloned()
+            .collect();
+        tokens.sort_by_key(|token| std::cmp::Reverse(token.created_at));
+        Ok(tokens)
+    }
+
+    async fn touch(&self, id: i64, last_used_at: i64) -> RepoResult<()> {
+        if let Some(token) = self.tables().tokens.iter_mut().find(|token| token.id == id) {
+            token.last_used_at = Some(last_used_at);
+        }
+        Ok(())
+    }
+
+    async fn delete(&self, id: i64, user_id: i64) -> RepoResult<bool> {
+        let mut tables = self.tables();
+        let before = tables.tokens.len();
+        tables
+            .tokens
+            .retain(|token| token.id != id || token.user_id != user_id);
+        Ok(tables.tokens.len() < before)
+    }
+}
+
+#[async_trait]
+impl PasskeyRepo for MemoryRepository {
+    async fn create(
+        &self,
+        user_id: i64,
+        credential_id: String,
+        name: String,
+        passkey: String,
+        created_at: i64,
+    ) -> RepoResult<DbPasskey> {
+        let mut tables = self.tables();
+        let passkey = DbPasskey {
+            id: tables.next_id(),
+            user_id,
+            credential_id,
+            name,
+            passkey,
+            created_at,
+            last_used_at: None,
+        };
+        tables.passkeys.push(passkey.clone());
+        Ok(passkey)
+    }
+
+    async fn find_by_credential_id(&self, credential_id: &str) -> RepoResult<Option<DbPasskey>> {
+        Ok(self
+            .tables()
+            .passkeys
+            .iter()
+            .find(|passkey| passkey.credential_id == credential_id)
+            .cloned())
+    }
+
+    async fn find_by_user(&self, user_id: i64) -> RepoResult<Vec<DbPasskey>> {
+        let mut passkeys: Vec<DbPasskey> = self
+            .tables()
+            .passkeys
+            .iter()
+            .filter(|passkey| passkey.user_id == user_id)
+            .cloned()
+            .collect();
+        passkeys.sort_by_key(|passkey| passkey.created_at);
+        Ok(passkeys)
+    }
+
+    async fn touch(&self, id: i64, passkey: String, last_used_at: i64) -> RepoResult<()> {
+        let mut tables = self.tables();
+        if let Some(stored) = tables.passkeys.iter_mut().find(|stored| stored.id == id) {
+            stored.passkey = passkey;
+            stored.last_used_at = Some(last_used_at);
+        }
+        Ok(())
+    }
+
+    async fn delete(&self, id: i64, user_id: i64) -> RepoResult<bool> {
+        let mut tables = self.tables();
+        let before = tables.passkeys.len();
+        tables
+            .passkeys
+            .retain(|passkey| passkey.id != id || passkey.user_id != user_id);
+        Ok(tables.passkeys.len() < before)
+    }
+}
+
+#[async_trait]
+impl AvatarRepo for MemoryRepository {
+    async fn find(&self, user_id: i64, size: i64) -> RepoResult<Option<Avatar>> {
+        Ok(self
+            .tables()
+            .avatars
+            .iter()
+            .find(|avatar| avatar.user_id == user_id && avatar.size == size)
+            .cloned())
+    }
+
+    async fn exists(&self, user_id: i64) -> RepoResult<bool> {
+        Ok(self
+            .tables()
+            .avatars
+            .iter()
+            .any(|avatar| avatar.user_id == user_id))
+    }
+
+    async fn replace(&self, user_id: i64, avatars: &[Avatar]) -> RepoResult<()> {
+        let mut tables = self.tables();
+        tables.avatars.retain(|avatar| avatar.user_id != user_id);
+        tables.avatars.extend(avatars.iter().map(|avatar| Avatar {
+            user_id,
+            ..avatar.clone()
+        }));
+        Ok(())
+    }
+
+    async fn delete(&self, user_id: i64) -> RepoResult<bool> {
+        let mut tables = self.tables();
+        let before = tables.avatars.len();
+        tables.avatars.retain(|avatar| avatar.user_id != user_id);
+        Ok(tables.avatars.len() < before)
+    }
+}
+
+#[async_trait]
+impl IdentityRepo for MemoryRepository {
+    async fn sign_in(&self, login: &IdentityLogin<'_>) -> RepoResult<SignIn> {
+        let mut tables = self.tables();
+        let linked = tables
+            .identities
+            .iter()
+            .find(|identity| identity.provider == login.provider && identity.subject == login.subject)
+            .map(|identity| identity.user_id);
+        let (user, created) = match linked {
+            Some(user_id) => {
+                let user = tables.users.iter().find(|user| user.id == user_id).cloned();
+                (user.ok_or(DbError::UserNotFound)?, false)
+            }
+            None => {
+                let mut username = login.preferred_username.to_owned();
+                let mut suffix = 1;
+                while tables.users.iter().any(|user| user.username == username) {
+                    suffix += 1;
+                    username = format!("{}{suffix}", login.preferred_username);
+                }
+                let user = DbUser {
+                    id: tables.next_id(),
+                    username,
+                    password: "!".into(),
+                };
+                let identity = DbIdentity {
+                    id: tables.next_id(),
+                    user_id: user.id,
+                    provider: login.provider.to_owned(),
+                    subject: login.subject.to_owned(),
+                    created_at: login.created_at,
+                };
+                tables.users.push(user.clone());
+                tables.identities.push(identity);
+                tables.join(user.id, "users");
+                (user, true)
+            }
+        };
+
+        let mut unknown_groups = vec![];
+        for (group, member) in login.memberships {
+            if !tables.groups.contains(group) {
+                unknown_groups.push(group.clone());
+            } else if *member {
+                tables.join(user.id, group);
+            } else {
+                tables
+                    .memberships
+                    .retain(|(user_id, name)| *user_id != user.id || name != group);
+            }
+        }
+
+        Ok(SignIn {
+            user,
+            created,
+            unknown_groups,
+        })
+    }
+}
+
+impl Tables {
+    /// Adds the user to the group if it exists and they aren't in it yet.
+    fn join(&mut self, user_id: i64, group: &str) {
+        let membership = (user_id, group.to_owned());
+        if self.groups.iter().any(|name| name == group) && !self.memberships.contains(&membership) {
+            self.memberships.push(membership);
+        }
+    }
+
+    /// Every thread, post and article matching `query` and `filter`, in the
+    /// order the SQL query would return them without ranks.
+    fn search_hits(&self, query: &str, filter: &SearchFilter) -> Vec<SearchHit> {
+        let terms = search_terms(query);
+        if terms.is_empty() {
+            return Vec::new();
+        }
+
+        let username = |user_id: Option<i64>| {
+            self.users
+                .iter()
+                .find(|user| Some(user.id) == user_id)
+                .map(|user| user.username.clone())
+        };
+        let category = |thread_id: Option<i64>| {
+            self.threads
+                .iter()
+                .find(|thread| Some(thread.id) == thread_id)
+                .and_then(|thread| thread.category_id)
+        };
+
+        let threads = self
+            .threads
+            .iter()
+            .filter(|thread| matches_terms(&terms, &thread.title, ""))
+            .map(|thread| SearchHit {
+                kind: "thread".into(),
+                id: thread.id,
+                thread_id: Some(thread.id),
+                user_id: thread.user_id,
+                username: username(thread.user_id),
+                category_id: thread.category_id,
+                title: thread.title.clone(),
+                snippet: String::new(),
+            });
+        let posts = self.posts.iter().filter_map(|post| {
+            let text = plain_text(&post.content);
+            matches_terms(&terms, &post.title, &text).then(|| SearchHit {
+                kind: "post".into(),
+                id: post.id,
+                thread_id: post.thread_id,
+                user_id: post.user_id,
+                username: username(post.user_id),
+                category_id: category(post.thread_id),
+                title: post.title.clone(),
+                snippet: text,
+            })
+        });
+        let articles = self.articles.iter().filter_map(|article| {
+            let text = plain_text(&article.content);
+            matches_terms(&terms, &article.title, &text).then(|| SearchHit {
+                kind: "article".into(),
+                id: article.id,
+                thread_id: None,
+                user_id: Some(article.user_id),
+                username: username(Some(article.user_id)),
+                category_id: None,
+                title: article.title.clone(),
+                snippet: text,
+            })
+        });
+
+        threads
+            .chain(posts)
+            .chain(articles)
+            .filter(|hit| filter.user_id.map_or(true, |id| hit.user_id == Some(id)))
+            .filter(|hit| filter.category_id.map_or(true, |id| hit.category_id == Some(id)))
+            .collect()
+    }
+}
+
+#[async_trait]
+impl SearchRepo for MemoryRepository {
+    async fn search(&self, query: &str, filter: &SearchFilter, limit: i64, offset: i64) -> RepoResult<Vec<SearchHit>> {
+        let hits = self.tables().search_hits(query, filter);
+        Ok(page(hits.into_iter(), limit, offset))
+    }
+
+    async fn count(&self, query: &str, filter: &SearchFilter) -> RepoResult<i64> {
+        Ok(self.tables().search_hits(query, filter).len() as i64)
+    }
+}
@@ -0,0 +1,247 @@
+//! Repository traits over the models handlers use, so they can be exercised
+//! without a database. [`Repositories::sql`] runs the model queries against
+//! a pool; with the `fakes` feature, `Repositories::memory` keeps
+//! everything in a `MemoryRepository` instead.
+
+#[cfg(feature = "fakes")]
+mod memory;
+mod sql;
+
+use std::sync::Arc;
+
+use async_trait::async_trait;
+
+#[cfg(feature = "fakes")]
+pub use memory::MemoryRepository;
+pub use sql::SqlRepository;
+
+use crate::{
+    api_token::DbApiToken,
+    article::Article,
+    avatar::Avatar,
+    category::Category,
+    error::DbError,
+    identity::{IdentityLogin, SignIn},
+    passkey::DbPasskey,
+    post::{Post, PostFilter},
+    search::{SearchFilter, SearchHit},
+    session::DbSession,
+    thread::{Thread, ThreadFilter},
+    user::{DbPermission, DbUser, PublicUser},
+    DbPool,
+};
+
+pub type RepoResult<T> = Result<T, DbError>;
+
+#[async_trait]
+pub trait UserRepo: Send + Sync {
+    async fn find_by_id(&self, id: i64) -> RepoResult<Option<DbUser>>;
+    async fn find_by_username(&self, username: &str) -> RepoResult<Option<DbUser>>;
+    /// Users whose name starts with `username`, ordered by id.
+    async fn list(&self, username: Option<&str>, limit: i64, offset: i64) -> RepoResult<Vec<PublicUser>>;
+    async fn count(&self, username: Option<&str>) -> RepoResult<i64>;
+    /// The permissions granted to the groups the user is in.
+    async fn permissions(&self, user_id: i64) -> RepoResult<Vec<DbPermission>>;
+}
+
+#[async_trait]
+pub trait CategoryRepo: Send + Sync {
+    async fn find_by_id(&self, id: i64) -> RepoResult<Option<Category>>;
+    async fn list(&self, limit: i64, offset: i64) -> RepoResult<Vec<Category>>;
+    async fn count(&self) -> RepoResult<i64>;
+}
+
+#[async_trait]
+pub trait ThreadRepo: Send + Sync {
+    async fn create(&self, category_id: i64, user_id: i64, title: String) -> RepoResult<Thread>;
+    async fn find_by_id(&self, id: i64) -> RepoResult<Option<Thread>>;
+    /// Newest first.
+    async fn list(&self, filter: &ThreadFilter, limit: i64, offset: i64) -> RepoResult<Vec<Thread>>;
+    async fn count(&self, filter: &ThreadFilter) -> RepoResult<i64>;
+    async fn update(&self, id: i64, title: String) -> RepoResult<Thread>;
+    /// Deletes the thread together with its posts.
+    async fn delete(&self, id: i64) -> RepoResult<()>;
+}
+
+#[async_trait]
+pub trait PostRepo: Send + Sync {
+    async fn create(&self, thread_id: i64, user_id: i64, title: String, content: String) -> RepoResult<Post>;
+    async fn find_by_id(&self, id: i64) -> RepoResult<Option<Post>>;
+    /// Oldest first.
+    async fn list(&self, filter: &PostFilter, limit: i64, offset: i64) -> RepoResult<Vec<Post>>;
+    async fn count(&self, filter: &PostFilter) -> RepoResult<i64>;
+    async fn update(&self, id: i64, title: String, content: String) -> RepoResult<Post>;
+    async fn delete(&self, id: i64) -> RepoResult<()>;
+}
+
+#[async_trait]
+pub trait ArticleRepo: Send + Sync {
+    async fn create(
+        &self,
+        user_id: i64,
+        title: String,
+        editor_content: serde_json::Value,
+        content: String,
+    ) -> RepoResult<Article>;
+    /// Creates the article, or replaces the content of the user's article
+    /// with the same title.
+    async fn upsert(
+        &self,
+        user_id: i64,
+        title: String,
+        editor_content: serde_json::Value,
+        content: String,
+    ) -> RepoResult<Article>;
+    async fn find_by_id(&self, id: i64) -> RepoResult<Option<Article>>;
+    /// Newest first.
+    async fn list(&self, user_id: Option<i64>, limit: i64, offset: i64) -> RepoResult<Vec<Article>>;
+    async fn count(&self, user_id: Option<i64>) -> RepoResult<i64>;
+    async fn update(
+        &self,
+        id: i64,
+        title: String,
+        editor_content: serde_json::Value,
+        content: String,
+    ) -> RepoResult<Article>;
+    async fn delete(&self, id: i64) -> RepoResult<()>;
+}
+
+#[async_trait]
+pub trait SessionRepo: Send + Sync {
+    /// Records that a session was used, creating the row on first sight.
+    async fn record(
+        &self,
+        session_id: &str,
+        user_id: i64,
+        user_agent: Option<&str>,
+        ip: Option<&str>,
+        now: i64,
+    ) -> RepoResult<()>;
+    async fn last_seen(&self, session_id: &str) -> RepoResult<Option<i64>>;
+    /// Sessions that haven't expired, most recently used first.
+    async fn find_active_by_user(&self, user_id: i64, now: i64) -> RepoResult<Vec<DbSession>>;
+    async fn find_by_id(&self, id: i64) -> RepoResult<Option<DbSession>>;
+    /// Logs the session out.
+    async fn revoke(&self, session: &DbSession) -> RepoResult<()>;
+    /// Returns the number of sessions that were revoked.
+    async fn revoke_all_for_user(&self, user_id: i64) -> RepoResult<u64>;
+}
+
+#[async_trait]
+pub trait TokenRepo: Send + Sync {
+    async fn create(
+        &self,
+        user_id: i64,
+        name: &str,
+        token_hash: &str,
+        scopes: &str,
+        created_at: i64,
+    ) -> RepoResult<DbApiToken>;
+    async fn find_by_hash(&self, token_hash: &str) -> RepoResult<Option<DbApiToken>>;
+    /// Newest first.
+    async fn find_by_user(&self, user_id: i64) -> RepoResult<Vec<DbApiToken>>;
+    async fn touch(&self, id: i64, last_used_at: i64) -> RepoResult<()>;
+    /// Scoped to the owner, returns whether a token was deleted.
+    async fn delete(&self, id: i64, user_id: i64) -> RepoResult<bool>;
+}
+
+#[async_trait]
+pub trait PasskeyRepo: Send + Sync {
+    async fn create(
+        &self,
+        user_id: i64,
+        credential_id: String,
+        name: String,
+        passkey: String,
+        created_at: i64,
+    ) -> RepoResult<DbPasskey>;
+    async fn find_by_credential_id(&self, credential_id: &str) -> RepoResult<Option<DbPasskey>>;
+    /// Oldest first.
+    async fn find_by_user(&self, user_id: i64) -> RepoResult<Vec<DbPasskey>>;
+    async fn touch(&self, id: i64, passkey: String, last_used_at: i64) -> RepoResult<()>;
+    /// Scoped to the owner, returns whether a passkey was deleted.
+    async fn delete(&self, id: i64, user_id: i64) -> RepoResult<bool>;
+}
+
+#[async_trait]
+pub trait AvatarRepo: Send + Sync {
+    async fn find(&self, user_id: i64, size: i64) -> RepoResult<Option<Avatar>>;
+    async fn exists(&self, user_id: i64) -> RepoResult<bool>;
+    /// Replaces all sizes of the user's avatar with `avatars`.
+    async fn replace(&self, user_id: i64, avatars: &[Avatar]) -> RepoResult<()>;
+    /// Returns whether the user had an avatar.
+    async fn delete(&self, user_id: i64) -> RepoResult<bool>;
+}
+
+#[async_trait]
+pub trait IdentityRepo: Send + Sync {
+    /// Finds or provisions the user linked to an identity provider account
+    /// and syncs their groups, see [`crate::identity::DbIdentity::sign_in`].
+    async fn sign_in(&self, login: &IdentityLogin<'_>) -> RepoResult<SignIn>;
+}
+
+#[async_trait]
+pub trait SearchRepo: Send + Sync {
+    /// Best match first. A query without any terms matches nothing.
+    async fn search(&self, query: &str, filter: &SearchFilter, limit: i64, offset: i64) -> RepoResult<Vec<SearchHit>>;
+    async fn count(&self, query: &str, filter: &SearchFilter) -> RepoResult<i64>;
+}
+
+/// The repositories handlers go through, shared by all requests.
+#[derive(Clone)]
+pub struct Repositories {
+    pub users: Arc<dyn UserRepo>,
+    pub categories: Arc<dyn CategoryRepo>,
+    pub threads: Arc<dyn ThreadRepo>,
+    pub posts: Arc<dyn PostRepo>,
+    pub articles: Arc<dyn ArticleRepo>,
+    pub sessions: Arc<dyn SessionRepo>,
+    pub tokens: Arc<dyn TokenRepo>,
+    pub passkeys: Arc<dyn PasskeyRepo>,
+    pub avatars: Arc<dyn AvatarRepo>,
+    pub identities: Arc<dyn IdentityRepo>,
+    pub search: Arc<dyn SearchRepo>,
+}
+
+impl Repositories {
+    pub fn sql(pool: DbPool) -> Self {
+        Self::from_repository(Arc::new(SqlRepository::new(pool)))
+    }
+
+    /// In-memory repositories for tests. Keep a clone of `repository` to
+    /// seed users, groups, permissions and categories.
+    #[cfg(feature = "fakes")]
+    pub fn memory(repository: Arc<MemoryRepository>) -> Self {
+        Self::from_repository(repository)
+    }
+
+    fn from_repository<R>(repository: Arc<R>) -> Self
+    where
+        R: UserRepo
+            + CategoryRepo
+            + ThreadRepo
+            + PostRepo
+            + ArticleRepo
+            + SessionRepo
+            + TokenRepo
+            + PasskeyRepo
+            + AvatarRepo
+            + IdentityRepo
+            + SearchRepo
+            + 'static,
+    {
+        Self {
+            users: repository.clone(),
+            categories: repository.clone(),
+            threads: repository.clone(),
+            posts: repository.clone(),
+            articles: repository.clone(),
+            sessions: repository.clone(),
+            tokens: repository.clone(),
+            passkeys: repository.clone(),
+            avatars: repository.clone(),
+            identities: repository.clone(),
+            search: repository,
+        }
+    }
+}
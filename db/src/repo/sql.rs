@@ -0,0 +1,308 @@
+use async_trait::async_trait;
+
+use super::{
+    ArticleRepo, AvatarRepo, CategoryRepo, IdentityRepo, PasskeyRepo, PostRepo, RepoResult,
+    SearchRepo, SessionRepo, ThreadRepo, TokenRepo, UserRepo,
+};
+use crate::{
+    api_token::DbApiToken,
+    article::Article,
+    avatar::Avatar,
+    category::Category,
+    identity::{DbIdentity, IdentityLogin, SignIn},
+    passkey::DbPasskey,
+    post::{Post, PostFilter},
+    search::{SearchFilter, SearchHit},
+    session::DbSession,
+    thread::{Thread, ThreadFilter},
+    user::{DbPermission, DbUser, PublicUser},
+    DbPool,
+};
+
+/// The repositories backed by the database, delegating to the model queries.
+#[derive(Clone)]
+pub struct SqlRepository {
+    pool: DbPool,
+}
+
+impl SqlRepository {
+    pub fn new(pool: DbPool) -> Self {
+        Self { pool }
+    }
+}
+
+#[async_trait]
+impl UserRepo for SqlRepository {
+    async fn find_by_id(&self, id: i64) -> RepoResult<Option<DbUser>> {
+        Ok(DbUser::find_by_id(id, &self.pool).await?)
+    }
+
+    async fn find_by_username(&self, username: &str) -> RepoResult<Option<DbUser>> {
+        Ok(DbUser::find_by_username(username, &self.pool).await?)
+    }
+
+    async fn list(&self, username: Option<&str>, limit: i64, offset: i64) -> RepoResult<Vec<PublicUser>> {
+        Ok(PublicUser::list(username, limit, offset, &self.pool).await?)
+    }
+
+    async fn count(&self, username: Option<&str>) -> RepoResult<i64> {
+        Ok(PublicUser::count(username, &self.pool).await?)
+    }
+
+    async fn permissions(&self, user_id: i64) -> RepoResult<Vec<DbPermission>> {
+        Ok(DbPermission::for_user(user_id, &self.pool).await?)
+    }
+}
+
+#[async_trait]
+impl CategoryRepo for SqlRepository {
+    async fn find_by_id(&self, id: i64) -> RepoResult<Option<Category>> {
+        Ok(Category::find_by_id(id, &self.pool).await?)
+    }
+
+    async fn list(&self, limit: i64, offset: i64) -> RepoResult<Vec<Category>> {
+        Ok(Category::list(limit, offset, &self.pool).await?)
+    }
+
+    async fn count(&self) -> RepoResult<i64> {
+        Ok(Category::count(&self.pool).await?)
+    }
+}
+
+#[async_trait]
+impl ThreadRepo for SqlRepository {
+    async fn create(&self, category_id: i64, user_id: i64, title: String) -> RepoResult<Thread> {
+        Ok(Thread::create(category_id, user_id, title, &self.pool).await?)
+    }
+
+    async fn find_by_id(&self, id: i64) -> RepoResult<Option<Thread>> {
+        Ok(Thread::find_by_id(id, &self.pool).await?)
+    }
+
+    async fn list(&self, filter: &ThreadFilter, limit: i64, offset: i64) -> RepoResult<Vec<Thread>> {
+        Ok(Thread::list(filter, limit, offset, &self.pool).await?)
+    }
+
+    async fn count(&self, filter: &ThreadFilter) -> RepoResult<i64> {
+        Ok(Thread::count(filter, &self.pool).await?)
+    }
+
+    async fn update(&self, id: i64, title: String) -> RepoResult<Thread> {
+        Ok(Thread::update(id, title, &self.pool).await?)
+    }
+
+    async fn delete(&self, id: i64) -> RepoResult<()> {
+        Ok(Thread::delete(id, &self.pool).await?)
+    }
+}
+
+#[async_trait]
+impl PostRepo for SqlRepository {
+    async fn create(&self, thread_id: i64, user_id: i64, title: String, content: String) -> RepoResult<Post> {
+        Ok(Post::create(thread_id, user_id, title, content, &self.pool).await?)
+    }
+
+    async fn find_by_id(&self, id: i64) -> RepoResult<Option<Post>> {
+        Ok(Post::find_by_id(id, &self.pool).await?)
+    }
+
+    async fn list(&self, filter: &PostFilter, limit: i64, offset: i64) -> RepoResult<Vec<Post>> {
+        Ok(Post::list(filter, limit, offset, &self.pool).await?)
+    }
+
+    async fn count(&self, filter: &PostFilter) -> RepoResult<i64> {
+        Ok(Post::count(filter, &self.pool).await?)
+    }
+
+    async fn update(&self, id: i64, title: String, content: String) -> RepoResult<Post> {
+        Ok(Post::update(id, title, content, &self.pool).await?)
+    }
+
+    async fn delete(&self, id: i64) -> RepoResult<()> {
+        Ok(Post::delete(id, &self.pool).await?)
+    }
+}
+
+#[async_trait]
+impl ArticleRepo for SqlRepository {
+    async fn create(
+        &self,
+        user_id: i64,
+        title: String,
+        editor_content: serde_json::Value,
+        content: String,
+    ) -> RepoResult<Article> {
+        Ok(Article::new(user_id, title, editor_content, content, &self.pool).await?)
+    }
+
+    async fn upsert(
+        &self,
+        user_id: i64,
+        title: String,
+        editor_content: serde_json::Value,
+        content: String,
+    ) -> RepoResult<Article> {
+        Ok(Article::upsert(user_id, title, editor_content, content, &self.pool).await?)
+    }
+
+    async fn find_by_id(&self, id: i64) -> RepoResult<Option<Article>> {
+        match Article::find_by_id(id, &self.pool).await {
+            Ok(article) => Ok(Some(article)),
+            Err(sqlx::Error::RowNotFound) => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    async fn list(&self, user_id: Option<i64>, limit: i64, offset: i64) -> RepoResult<Vec<Article>> {
+        Ok(Article::list(user_id, limit, offset, &self.pool).await?)
+    }
+
+    async fn count(&self, user_id: Option<i64>) -> RepoResult<i64> {
+        Ok(Article::count(user_id, &self.pool).await?)
+    }
+
+    async fn update(
+        &self,
+        id: i64,
+        title: String,
+        editor_content: serde_json::Value,
+        content: String,
+    ) -> RepoResult<Article> {
+        Ok(Article::update(id, title, editor_content, content, &self.pool).await?)
+    }
+
+    async fn delete(&self, id: i64) -> RepoResult<()> {
+        Ok(Article::delete(id, &self.pool).await?)
+    }
+}
+
+#[async_trait]
+impl SessionRepo for SqlRepository {
+    async fn record(
+        &self,
+        session_id: &str,
+        user_id: i64,
+        user_agent: Option<&str>,
+        ip: Option<&str>,
+        now: i64,
+    ) -> RepoResult<()> {
+        Ok(DbSession::record(session_id, user_id, user_agent, ip, now, &self.pool).await?)
+    }
+
+    async fn last_seen(&self, session_id: &str) -> RepoResult<Option<i64>> {
+        Ok(DbSession::last_seen(session_id, &self.pool).await?)
+    }
+
+    async fn find_active_by_user(&self, user_id: i64, now: i64) -> RepoResult<Vec<DbSession>> {
+        Ok(DbSession::find_active_by_user(user_id, now, &self.pool).await?)
+    }
+
+    async fn find_by_id(&self, id: i64) -> RepoResult<Option<DbSession>> {
+        Ok(DbSession::find_by_id(id, &self.pool).await?)
+    }
+
+    async fn revoke(&self, session: &DbSession) -> RepoResult<()> {
+        Ok(session.revoke(&self.pool).await?)
+    }
+
+    async fn revoke_all_for_user(&self, user_id: i64) -> RepoResult<u64> {
+        Ok(DbSession::revoke_all_for_user(user_id, &self.pool).await?)
+    }
+}
+
+#[async_trait]
+impl TokenRepo for SqlRepository {
+    async fn create(
+        &self,
+        user_id: i64,
+        name: &str,
+        token_hash: &str,
+        scopes: &str,
+        created_at: i64,
+    ) -> RepoResult<DbApiToken> {
+        Ok(DbApiToken::create(user_id, name, token_hash, scopes, created_at, &self.pool).await?)
+    }
+
+    async fn find_by_hash(&self, token_hash: &str) -> RepoResult<Option<DbApiToken>> {
+        Ok(DbApiToken::find_by_hash(token_hash, &self.pool).await?)
+    }
+
+    async fn find_by_user(&self, user_id: i64) -> RepoResult<Vec<DbApiToken>> {
+        Ok(DbApiToken::find_by_user(user_id, &self.pool).await?)
+    }
+
+    async fn touch(&self, id: i64, last_used_at: i64) -> RepoResult<()> {
+        Ok(DbApiToken::touch(id, last_used_at, &self.pool).await?)
+    }
+
+    async fn delete(&self, id: i64, user_id: i64) -> RepoResult<bool> {
+        Ok(DbApiToken::delete(id, user_id, &self.pool).await?)
+    }
+}
+
+#[async_trait]
+impl PasskeyRepo for SqlRepository {
+    async fn create(
+        &self,
+        user_id: i64,
+        credential_id: String,
+        name: String,
+        passkey: String,
+        created_at: i64,
+    ) -> RepoResult<DbPasskey> {
+        Ok(DbPasskey::create(user_id, credential_id, name, passkey, created_at, &self.pool).await?)
+    }
+
+    async fn find_by_credential_id(&self, credential_id: &str) -> RepoResult<Option<DbPasskey>> {
+        Ok(DbPasskey::find_by_credential_id(credential_id, &self.pool).await?)
+    }
+
+    async fn find_by_user(&self, user_id: i64) -> RepoResult<Vec<DbPasskey>> {
+        Ok(DbPasskey::find_by_user(user_id, &self.pool).await?)
+    }
+
+    async fn touch(&self, id: i64, passkey: String, last_used_at: i64) -> RepoResult<()> {
+        Ok(DbPasskey::touch(id, passkey, last_used_at, &self.pool).await?)
+    }
+
+    async fn delete(&self, id: i64, user_id: i64) -> RepoResult<bool> {
+        Ok(DbPasskey::delete(id, user_id, &self.pool).await?)
+    }
+}
+
+#[async_trait]
+impl AvatarRepo for SqlRepository {
+    async fn find(&self, user_id: i64, size: i64) -> RepoResult<Option<Avatar>> {
+        Ok(Avatar::find(user_id, size, &self.pool).await?)
+    }
+
+    async fn exists(&self, user_id: i64) -> RepoResult<bool> {
+        Ok(Avatar::exists(user_id, &self.pool).await?)
+    }
+
+    async fn replace(&self, user_id: i64, avatars: &[Avatar]) -> RepoResult<()> {
+        Ok(Avatar::replace(user_id, avatars, &self.pool).await?)
+    }
+
+    async fn delete(&self, user_id: i64) -> RepoResult<bool> {
+        Ok(Avatar::delete(user_id, &self.pool).await?)
+    }
+}
+
+#[async_trait]
+impl IdentityRepo for SqlRepository {
+    async fn sign_in(&self, login: &IdentityLogin<'_>) -> RepoResult<SignIn> {
+        Ok(DbIdentity::sign_in(login, &self.pool).await?)
+    }
+}
+
+#[async_trait]
+impl SearchRepo for SqlRepository {
+    async fn search(&self, query: &str, filter: &SearchFilter, limit: i64, offset: i64) -> RepoResult<Vec<SearchHit>> {
+        Ok(SearchHit::search(query, filter, limit, offset, &self.pool).await?)
+    }
+
+    async fn count(&self, query: &str, filter: &SearchFilter) -> RepoResult<i64> {
+        Ok(SearchHit::count(query, filter, &self.pool).await?)
+    }
+}
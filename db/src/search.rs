@@ -0,0 +1,400 @@
+use serde::{Deserialize, Serialize};
+use sqlx::{pool::PoolConnection, FromRow};
+
+use crate::{Db, DbPool};
+
+/// Marks the start of a matched term in [`SearchHit::title`] and
+/// [`SearchHit::snippet`]. Control characters are used rather than HTML so
+/// the text can be escaped before the highlights are rendered.
+pub const HIGHLIGHT_START: char = '\u{2}';
+/// Marks the end of a matched term, see [`HIGHLIGHT_START`].
+pub const HIGHLIGHT_END: char = '\u{3}';
+
+/// Matches across the three FTS5 indexes, see the `search` and `search_text`
+/// migrations.
+/// Threads only have a title, so their snippet is empty. `$1` is the match
+/// expression built by [`match_expression`].
+#[cfg(not(feature = "postgres"))]
+const HITS: &str = r#"
+    SELECT 'thread' AS kind, threads.id, threads.id AS thread_id, threads.user_id, threads.category_id,
+        highlight(threads_fts, 0, char(2), char(3)) AS title,
+        '' AS snippet,
+        threads_fts.rank AS rank
+    FROM threads_fts JOIN threads ON threads.id = threads_fts.rowid
+    WHERE threads_fts MATCH $1
+    UNION ALL
+    SELECT 'post', posts.id, posts.thread_id, posts.user_id, threads.category_id,
+        highlight(posts_fts, 0, char(2), char(3)),
+        snippet(posts_fts, 1, char(2), char(3), '…', 24),
+        posts_fts.rank
+    FROM posts_fts JOIN posts ON posts.id = posts_fts.rowid
+    LEFT JOIN threads ON threads.id = posts.thread_id
+    WHERE posts_fts MATCH $1
+    UNION ALL
+    SELECT 'article', articles.id, NULL, articles.user_id, NULL,
+        highlight(articles_fts, 0, char(2), char(3)),
+        snippet(articles_fts, 1, char(2), char(3), '…', 24),
+        articles_fts.rank
+    FROM articles_fts JOIN articles ON articles.id = articles_fts.rowid
+    WHERE articles_fts MATCH $1
+"#;
+
+/// Matches across the `search` columns, see the `search` and `search_text`
+/// migrations. `$1` is
+/// the query as typed, which `websearch_to_tsquery` parses the way
+/// [`match_expression`] does for SQLite, minus prefix matches. Ranks are
+/// negated so the best match sorts first, like FTS5's.
+#[cfg(feature = "postgres")]
+const HITS: &str = r#"
+    SELECT 'thread' AS kind, threads.id, threads.id AS thread_id, threads.user_id, threads.category_id,
+        ts_headline('english', threads.title, query, 'HighlightAll=true, StartSel=' || chr(2) || ', StopSel=' || chr(3)) AS title,
+        '' AS snippet,
+        -ts_rank(threads.search, query) AS rank
+    FROM threads CROSS JOIN websearch_to_tsquery('english', $1) AS query
+    WHERE threads.search @@ query
+    UNION ALL
+    SELECT 'post', posts.id, posts.thread_id, posts.user_id, threads.category_id,
+        ts_headline('english', posts.title, query, 'HighlightAll=true, StartSel=' || chr(2) || ', StopSel=' || chr(3)),
+        ts_headline('english', coalesce(posts.search_text, ''), query, 'MaxWords=24, MinWords=8, StartSel=' || chr(2) || ', StopSel=' || chr(3)),
+        -ts_rank(posts.search, query)
+    FROM posts CROSS JOIN websearch_to_tsquery('english', $1) AS query
+    LEFT JOIN threads ON threads.id = posts.thread_id
+    WHERE posts.search @@ query
+    UNION ALL
+    SELECT 'article', articles.id, NULL, articles.user_id, NULL,
+        ts_headline('english', articles.title, query, 'HighlightAll=true, StartSel=' || chr(2) || ', StopSel=' || chr(3)),
+        ts_headline('english', coalesce(articles.search_text, ''), query, 'MaxWords=24, MinWords=8, StartSel=' || chr(2) || ', StopSel=' || chr(3)),
+        -ts_rank(articles.search, query)
+    FROM articles CROSS JOIN websearch_to_tsquery('english', $1) AS query
+    WHERE articles.search @@ query
+"#;
+
+/// Optional filters for a search. `None` matches everything. Articles are
+/// not in a category and never match a category filter.
+#[derive(Clone, Debug, Default, Deserialize)]
+pub struct SearchFilter {
+    pub user_id: Option<i64>,
+    pub category_id: Option<i64>,
+}
+
+/// A thread, post or article matching a search, best match first.
+#[derive(Clone, Serialize, Deserialize, FromRow, Debug)]
+pub struct SearchHit {
+    /// `thread`, `post` or `article`.
+    pub kind: String,
+    pub id: i64,
+    pub thread_id: Option<i64>,
+    pub user_id: Option<i64>,
+    pub username: Option<String>,
+    pub category_id: Option<i64>,
+    /// The title with matches wrapped in [`HIGHLIGHT_START`] and
+    /// [`HIGHLIGHT_END`].
+    pub title: String,
+    /// An excerpt of the content around the matches, highlighted like
+    /// `title`.
+    pub snippet: String,
+}
+
+impl SearchHit {
+    /// Runs a query as typed by a user: words and `"quoted phrases"` that
+    /// must all match. A query without any terms matches nothing.
+    pub async fn search(
+        query: &str,
+        filter: &SearchFilter,
+        limit: i64,
+        offset: i64,
+        pool: &DbPool,
+    ) -> Result<Vec<SearchHit>, sqlx::Error> {
+        let Some(expression) = expression(query) else {
+            return Ok(Vec::new());
+        };
+
+        sqlx::query_as(&format!(
+            r#"SELECT hits.kind, hits.id, hits.thread_id, hits.user_id, users.username, hits.category_id, hits.title, hits.snippet
+            FROM ({HITS}) AS hits LEFT JOIN users ON users.id = hits.user_id
+            WHERE ($2 IS NULL OR hits.user_id = $2) AND ($3 IS NULL OR hits.category_id = $3)
+            ORDER BY hits.rank LIMIT $4 OFFSET $5"#
+        ))
+        .bind(expression)
+        .bind(filter.user_id)
+        .bind(filter.category_id)
+        .bind(limit)
+        .bind(offset)
+        .fetch_all(pool)
+        .await
+    }
+
+    pub async fn count(query: &str, filter: &SearchFilter, pool: &DbPool) -> Result<i64, sqlx::Error> {
+        let Some(expression) = expression(query) else {
+            return Ok(0);
+        };
+
+        sqlx::query_scalar(&format!(
+            r#"SELECT COUNT(*) FROM ({HITS}) AS hits
+            WHERE ($2 IS NULL OR hits.user_id = $2) AND ($3 IS NULL OR hits.category_id = $3)"#
+        ))
+        .bind(expression)
+        .bind(filter.user_id)
+        .bind(filter.category_id)
+        .fetch_one(pool)
+        .await
+    }
+}
+
+#[cfg(not(feature = "postgres"))]
+fn expression(query: &str) -> Option<String> {
+    match_expression(query)
+}
+
+#[cfg(feature = "postgres")]
+fn expression(query: &str) -> Option<String> {
+    has_token(query).then(|| query.to_owned())
+}
+
+/// Turns a query typed by a user into an FTS5 match expression. Words and
+/// `"quoted phrases"` must all match, and a trailing `*` matches any word
+/// with that prefix. Everything is quoted, so FTS5 operators and stray
+/// punctuation can't cause syntax errors. Returns `None` if no term is left.
+pub fn match_expression(query: &str) -> Option<String> {
+    let mut terms = Vec::new();
+    let mut rest = query;
+
+    loop {
+        rest = rest.trim_start();
+        if rest.is_empty() {
+            break;
+        }
+
+        if let Some(quoted) = rest.strip_prefix('"') {
+            // An unterminated phrase runs to the end of the query.
+            let (phrase, after) = quoted.split_once('"').unwrap_or((quoted, ""));
+            if has_token(phrase) {
+                terms.push(quote(phrase));
+            }
+            rest = after;
+        } else {
+            let end = rest
+                .find(|c: char| c.is_whitespace() || c == '"')
+                .unwrap_or(rest.len());
+            let (word, after) = rest.split_at(end);
+            match word.strip_suffix('*') {
+                Some(prefix) if has_token(prefix) => terms.push(format!("{}*", quote(prefix))),
+                _ if has_token(word) => terms.push(quote(word)),
+                _ => {}
+            }
+            rest = after;
+        }
+    }
+
+    (!terms.is_empty()).then(|| terms.join(" "))
+}
+
+fn has_token(text: &str) -> bool {
+    text.chars().any(char::is_alphanumeric)
+}
+
+fn quote(text: &str) -> String {
+    format!("\"{}\"", text.replace('"', "\"\""))
+}
+
+/// Elements that don't separate words, like `<strong>` in `<strong>S</strong>ome`.
+const INLINE_ELEMENTS: &[&str] = &[
+    "a", "abbr", "b", "bdi", "bdo", "cite", "code", "data", "del", "dfn", "em", "font", "i", "ins",
+    "kbd", "mark", "q", "s", "samp", "small", "span", "strong", "sub", "sup", "time", "u", "var",
+];
+
+/// The text of an HTML fragment as it reads on the page, which is what the
+/// search indexes hold in `search_text`: tags and comments are dropped,
+/// other than inline elements they separate words, the contents of
+/// `<script>` and `<style>` are skipped and character references are
+/// decoded.
+pub fn plain_text(html: &str) -> String {
+    let mut text = String::with_capacity(html.len());
+    let mut rest = html;
+
+    while let Some(start) = rest.find(['<', '&']) {
+        text.push_str(&rest[..start]);
+        rest = &rest[start..];
+
+        if let Some(comment) = rest.strip_prefix("<!--") {
+            rest = comment.find("-->").map_or("", |end| &comment[end + 3..]);
+        } else if rest.starts_with('<') {
+            // A `<` that doesn't start a tag is text, like in `a < b`.
+            if !rest[1..].starts_with(|c: char| c.is_ascii_alphabetic() || c == '/' || c == '!') {
+                text.push('<');
+                rest = &rest[1..];
+                continue;
+            }
+            let end = tag_end(rest);
+            let tag = &rest[1..end];
+            rest = rest.get(end + 1..).unwrap_or("");
+
+            let name = tag
+                .trim_start_matches('/')
+                .split(|c: char| c.is_whitespace() || c == '/')
+                .next()
+                .unwrap_or_default()
+                .to_ascii_lowercase();
+            if !tag.starts_with('/') && (name == "script" || name == "style") {
+                let close = format!("</{name}");
+                rest = rest
+                    .to_ascii_lowercase()
+                    .find(&close)
+                    .map_or("", |end| &rest[end..]);
+            }
+            if !INLINE_ELEMENTS.contains(&name.as_str()) {
+                text.push(' ');
+            }
+        } else {
+            match decode_reference(rest) {
+                Some((c, len)) => {
+                    text.push(c);
+                    rest = &rest[len..];
+                }
+                None => {
+                    text.push('&');
+                    rest = &rest[1..];
+                }
+            }
+        }
+    }
+    text.push_str(rest);
+
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// The index of the `>` closing the tag `html` starts with, skipping quoted
+/// attribute values, or the end of `html` for an unterminated tag.
+fn tag_end(html: &str) -> usize {
+    let mut quote = None;
+    for (index, c) in html.char_indices() {
+        match (quote, c) {
+            (None, '"' | '\'') => quote = Some(c),
+            (Some(open), c) if c == open => quote = None,
+            (None, '>') => return index,
+            _ => {}
+        }
+    }
+    html.len()
+}
+
+/// Decodes the character reference `text` starts with, like `&amp;` or
+/// `&#x27;`, returning the character and the length of the reference.
+fn decode_reference(text: &str) -> Option<(char, usize)> {
+    let end = text.char_indices().take(12).find(|&(_, c)| c == ';')?.0;
+    let c = match &text[1..end] {
+        "amp" => '&',
+        "lt" => '<',
+        "gt" => '>',
+        "quot" => '"',
+        "apos" => '\'',
+        "nbsp" => ' ',
+        name => {
+            let number = name.strip_prefix('#')?;
+            let code = match number.strip_prefix(['x', 'X']) {
+                Some(hex) => u32::from_str_radix(hex, 16).ok()?,
+                None => number.parse().ok()?,
+            };
+            char::from_u32(code)?
+        }
+    };
+    Some((c, end + 1))
+}
+
+/// Fills in `search_text` for posts and articles written before the column
+/// existed, which the migration adding it can't do in SQL. Returns the
+/// number of rows updated.
+pub async fn fill_search_text(conn: &mut PoolConnection<Db>) -> Result<u64, sqlx::Error> {
+    let mut filled = 0;
+    for table in ["posts", "articles"] {
+        let rows: Vec<(i64, String)> =
+            sqlx::query_as(&format!("SELECT id, content FROM {table} WHERE search_text IS NULL"))
+                .fetch_all(&mut **conn)
+                .await?;
+        for (id, content) in rows {
+            sqlx::query(&format!("UPDATE {table} SET search_text = $1 WHERE id = $2"))
+                .bind(plain_text(&content))
+                .bind(id)
+                .execute(&mut **conn)
+                .await?;
+            filled += 1;
+        }
+    }
+    Ok(filled)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn words_and_phrases_are_quoted() {
+        assert_eq!(match_expression("rust forum").as_deref(), Some(r#""rust" "forum""#));
+        assert_eq!(
+            match_expression(r#"say "hello world" twice"#).as_deref(),
+            Some(r#""say" "hello world" "twice""#)
+        );
+    }
+
+    #[test]
+    fn trailing_star_matches_prefixes() {
+        assert_eq!(match_expression("foru*").as_deref(), Some(r#""foru"*"#));
+        // A star on its own or inside a word isn't an operator.
+        assert_eq!(match_expression("*").as_deref(), None);
+        assert_eq!(match_expression("a*b").as_deref(), Some(r#""a*b""#));
+    }
+
+    #[test]
+    fn operators_are_searched_as_words() {
+        assert_eq!(
+            match_expression("cats OR dogs NOT birds").as_deref(),
+            Some(r#""cats" "OR" "dogs" "NOT" "birds""#)
+        );
+        assert_eq!(
+            match_expression("title:rust NEAR(a b)").as_deref(),
+            Some(r#""title:rust" "NEAR(a" "b)""#)
+        );
+        assert_eq!(match_expression("^start -minus").as_deref(), Some(r#""^start" "-minus""#));
+    }
+
+    #[test]
+    fn apostrophes_and_stray_quotes_are_kept_apart() {
+        assert_eq!(match_expression(r#"it's 5'10""#).as_deref(), Some(r#""it's" "5'10""#));
+        assert_eq!(match_expression(r#"say"hi""#).as_deref(), Some(r#""say" "hi""#));
+    }
+
+    #[test]
+    fn unterminated_phrases_run_to_the_end() {
+        assert_eq!(match_expression(r#"a "b c"#).as_deref(), Some(r#""a" "b c""#));
+    }
+
+    #[test]
+    fn queries_without_words_match_nothing() {
+        assert_eq!(match_expression(""), None);
+        assert_eq!(match_expression("   "), None);
+        assert_eq!(match_expression(r#""" ( ) - * """#), None);
+    }
+
+    #[test]
+    fn plain_text_drops_markup() {
+        assert_eq!(
+            plain_text("<p>Hello <strong>wor</strong>ld</p><p>again<br>and<br/>again</p>"),
+            "Hello world again and again"
+        );
+        assert_eq!(plain_text(r#"<a href="/x" title="a > b">link</a>"#), "link");
+        assert_eq!(plain_text("<!-- note --><p>text</p>"), "text");
+        assert_eq!(
+            plain_text("<script>alert('<p>')</script><STYLE>p { }</STYLE>shown"),
+            "shown"
+        );
+    }
+
+    #[test]
+    fn plain_text_decodes_references() {
+        assert_eq!(
+            plain_text("a &lt;b&gt; &amp; &quot;c&quot; &#39;d&#x27; e&nbsp;f"),
+            r#"a <b> & "c" 'd' e f"#
+        );
+        assert_eq!(plain_text("AT&T &unknown; 1 < 2"), "AT&T &unknown; 1 < 2");
+    }
+}
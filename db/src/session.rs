@@ -0,0 +1,145 @@
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+
+use crate::DbPool;
+
+/// The session store's table and its expiry as a unix timestamp. The stores
+/// of the two backends lay out their tables differently.
+#[cfg(not(feature = "postgres"))]
+const STORE: &str = "tower_sessions";
+#[cfg(not(feature = "postgres"))]
+const STORE_EXPIRY: &str = "tower_sessions.expiry_date";
+#[cfg(feature = "postgres")]
+const STORE: &str = "tower_sessions.session";
+#[cfg(feature = "postgres")]
+const STORE_EXPIRY: &str = "extract(epoch from tower_sessions.session.expiry_date)";
+
+/// Metadata about an authenticated session. The session data itself lives in
+/// the table managed by the session store; `session_id` is
+/// the key into that table and is never exposed to templates.
+#[derive(Clone, Serialize, Deserialize, FromRow, Debug)]
+pub struct DbSession {
+    pub id: i64,
+    #[serde(skip_serializing)]
+    pub session_id: String,
+    pub user_id: i64,
+    pub created_at: i64,
+    pub last_seen_at: i64,
+    pub user_agent: Option<String>,
+    pub ip: Option<String>,
+}
+
+impl DbSession {
+    /// Records that a session was used, creating the row on first sight.
+    /// Sessions that are no longer in the session store, because the request
+    /// logged out or revoked them, are not recorded again.
+    pub async fn record(
+        session_id: &str,
+        user_id: i64,
+        user_agent: Option<&str>,
+        ip: Option<&str>,
+        now: i64,
+        pool: &DbPool,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query(&format!(
+            r#"INSERT INTO user_sessions (session_id, user_id, created_at, last_seen_at, user_agent, ip)
+            SELECT $1, $2, $3, $3, $4, $5 WHERE EXISTS (SELECT 1 FROM {STORE} WHERE id = $1)
+            ON CONFLICT (session_id) DO UPDATE SET user_id = excluded.user_id, last_seen_at = excluded.last_seen_at, user_agent = excluded.user_agent, ip = excluded.ip"#
+        ))
+        .bind(session_id)
+        .bind(user_id)
+        .bind(now)
+        .bind(user_agent)
+        .bind(ip)
+        .execute(pool)
+        .await?;
+        Ok(())
+    }
+
+    /// When a session was last recorded, if it has been.
+    pub async fn last_seen(session_id: &str, pool: &DbPool) -> Result<Option<i64>, sqlx::Error> {
+        sqlx::query_scalar(r#"SELECT last_seen_at FROM user_sessions WHERE session_id = $1"#)
+            .bind(session_id)
+            .fetch_optional(pool)
+            .await
+    }
+
+    /// Returns the sessions of a user that have not expired in the session
+    /// store, most recently used first.
+    pub async fn find_active_by_user(
+        user_id: i64,
+        now: i64,
+        pool: &DbPool,
+    ) -> Result<Vec<DbSession>, sqlx::Error> {
+        sqlx::query_as(&format!(
+            r#"SELECT user_sessions.* FROM user_sessions
+            JOIN {STORE} ON {STORE}.id = user_sessions.session_id
+            WHERE user_sessions.user_id = $1 AND {STORE_EXPIRY} > $2
+            ORDER BY user_sessions.last_seen_at DESC"#
+        ))
+        .bind(user_id)
+        .bind(now)
+        .fetch_all(pool)
+        .await
+    }
+
+    pub async fn find_by_id(id: i64, pool: &DbPool) -> Result<Option<DbSession>, sqlx::Error> {
+        sqlx::query_as(r#"SELECT * FROM user_sessions WHERE id = $1"#)
+            .bind(id)
+            .fetch_optional(pool)
+            .await
+    }
+
+    /// Logs a session out by deleting it from the session store.
+    pub async fn revoke(&self, pool: &DbPool) -> Result<(), sqlx::Error> {
+        let mut tx = pool.begin().await?;
+        sqlx::query(&format!("DELETE FROM {STORE} WHERE id = $1"))
+            .bind(&self.session_id)
+            .execute(&mut *tx)
+            .await?;
+        sqlx::query("DELETE FROM user_sessions WHERE id = $1")
+            .bind(self.id)
+            .execute(&mut *tx)
+            .await?;
+        tx.commit().await
+    }
+
+    /// Logs a user out of every session. Returns the number of sessions
+    /// that were revoked.
+    pub async fn revoke_all_for_user(user_id: i64, pool: &DbPool) -> Result<u64, sqlx::Error> {
+        let mut tx = pool.begin().await?;
+        sqlx::query(&format!(
+            "DELETE FROM {STORE} WHERE id IN (SELECT session_id FROM user_sessions WHERE user_id = $1)"
+        ))
+        .bind(user_id)
+        .execute(&mut *tx)
+        .await?;
+        let result = sqlx::query("DELETE FROM user_sessions WHERE user_id = $1")
+            .bind(user_id)
+            .execute(&mut *tx)
+            .await?;
+        tx.commit().await?;
+        Ok(result.rows_affected())
+    }
+
+    /// Counts the sessions that have not expired in the session store, and
+    /// how many of those belong to a signed-in user.
+    pub async fn count_active(now: i64, pool: &DbPool) -> Result<(i64, i64), sqlx::Error> {
+        sqlx::query_as(&format!(
+            r#"SELECT COUNT(*), COUNT(user_sessions.id) FROM {STORE}
+            LEFT JOIN user_sessions ON user_sessions.session_id = {STORE}.id
+            WHERE {STORE_EXPIRY} > $1"#
+        ))
+        .bind(now)
+        .fetch_one(pool)
+        .await
+    }
+
+    /// Removes metadata for sessions the store has already deleted.
+    pub async fn delete_stale(pool: &DbPool) -> Result<(), sqlx::Error> {
+        sqlx::query(&format!("DELETE FROM user_sessions WHERE session_id NOT IN (SELECT id FROM {STORE})"))
+            .execute(pool)
+            .await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,95 @@
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+
+use crate::DbPool;
+
+#[derive(Clone, Serialize, Deserialize, FromRow, Debug)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct Thread {
+    pub id: i64,
+    pub category_id: Option<i64>,
+    pub user_id: Option<i64>,
+    pub title: String,
+}
+
+/// Optional filters for listing threads. `None` matches everything.
+#[derive(Clone, Debug, Default, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::IntoParams), into_params(parameter_in = Query))]
+pub struct ThreadFilter {
+    pub category_id: Option<i64>,
+    pub user_id: Option<i64>,
+}
+
+impl Thread {
+    pub async fn create(
+        category_id: i64,
+        user_id: i64,
+        title: String,
+        pool: &DbPool,
+    ) -> Result<Thread, sqlx::Error> {
+        sqlx::query_as(r#"INSERT INTO threads (category_id, user_id, title) VALUES ($1, $2, $3) RETURNING *"#)
+            .bind(category_id)
+            .bind(user_id)
+            .bind(title)
+            .fetch_one(pool)
+            .await
+    }
+
+    pub async fn find_by_id(id: i64, pool: &DbPool) -> Result<Option<Thread>, sqlx::Error> {
+        sqlx::query_as(r#"SELECT * FROM threads WHERE id = $1"#)
+            .bind(id)
+            .fetch_optional(pool)
+            .await
+    }
+
+    pub async fn list(
+        filter: &ThreadFilter,
+        limit: i64,
+        offset: i64,
+        pool: &DbPool,
+    ) -> Result<Vec<Thread>, sqlx::Error> {
+        sqlx::query_as(
+            r#"SELECT * FROM threads
+            WHERE ($1 IS NULL OR category_id = $1) AND ($2 IS NULL OR user_id = $2)
+            ORDER BY id DESC LIMIT $3 OFFSET $4"#,
+        )
+        .bind(filter.category_id)
+        .bind(filter.user_id)
+        .bind(limit)
+        .bind(offset)
+        .fetch_all(pool)
+        .await
+    }
+
+    pub async fn count(filter: &ThreadFilter, pool: &DbPool) -> Result<i64, sqlx::Error> {
+        sqlx::query_scalar(
+            r#"SELECT COUNT(*) FROM threads WHERE ($1 IS NULL OR category_id = $1) AND ($2 IS NULL OR user_id = $2)"#,
+        )
+        .bind(filter.category_id)
+        .bind(filter.user_id)
+        .fetch_one(pool)
+        .await
+    }
+
+    pub async fn update(id: i64, title: String, pool: &DbPool) -> Result<Thread, sqlx::Error> {
+        sqlx::query_as(r#"UPDATE threads SET title = $1 WHERE id = $2 RETURNING *"#)
+            .bind(title)
+            .bind(id)
+            .fetch_one(pool)
+            .await
+    }
+
+    /// Deletes a thread together with its posts.
+    pub async fn delete(id: i64, pool: &DbPool) -> Result<(), sqlx::Error> {
+        let mut tx = pool.begin().await?;
+        sqlx::query("DELETE FROM posts WHERE thread_id = $1")
+            .bind(id)
+            .execute(&mut *tx)
+            .await?;
+        sqlx::query("DELETE FROM threads WHERE id = $1")
+            .bind(id)
+            .execute(&mut *tx)
+            .await?;
+        tx.commit().await
+    }
+}
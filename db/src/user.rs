@@ -1,6 +1,8 @@
 use serde::{Deserialize, Serialize};
 use sqlx::FromRow;
 
+use crate::DbPool;
+
 #[derive(Clone, Serialize, Deserialize, FromRow)]
 pub struct DbUser {
     pub id: i64,
@@ -8,6 +10,81 @@ pub struct DbUser {
     pub password: String,
 }
 
+impl DbUser {
+    pub async fn create(username: &str, password: &str, pool: &DbPool) -> Result<DbUser, sqlx::Error> {
+        sqlx::query_as(r#"INSERT INTO users (username, password) VALUES ($1, $2) RETURNING *"#)
+            .bind(username)
+            .bind(password)
+            .fetch_one(pool)
+            .await
+    }
+
+    pub async fn find_by_id(id: i64, pool: &DbPool) -> Result<Option<DbUser>, sqlx::Error> {
+        sqlx::query_as(r#"SELECT * FROM users WHERE id = $1"#)
+            .bind(id)
+            .fetch_optional(pool)
+            .await
+    }
+
+    pub async fn set_password(id: i64, password: &str, pool: &DbPool) -> Result<(), sqlx::Error> {
+        sqlx::query(r#"UPDATE users SET password = $1 WHERE id = $2"#)
+            .bind(password)
+            .bind(id)
+            .execute(pool)
+            .await?;
+        Ok(())
+    }
+
+    pub async fn find_by_username(username: &str, pool: &DbPool) -> Result<Option<DbUser>, sqlx::Error> {
+        sqlx::query_as(r#"SELECT * FROM users WHERE username = $1"#)
+            .bind(username)
+            .fetch_optional(pool)
+            .await
+    }
+}
+
+/// The parts of a user that are safe to show to anyone.
+#[derive(Clone, Debug, Serialize, Deserialize, FromRow)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct PublicUser {
+    pub id: i64,
+    pub username: String,
+}
+
+impl From<DbUser> for PublicUser {
+    fn from(user: DbUser) -> Self {
+        Self {
+            id: user.id,
+            username: user.username,
+        }
+    }
+}
+
+impl PublicUser {
+    pub async fn list(
+        username: Option<&str>,
+        limit: i64,
+        offset: i64,
+        pool: &DbPool,
+    ) -> Result<Vec<PublicUser>, sqlx::Error> {
+        sqlx::query_as(
+            r#"SELECT id, username FROM users WHERE ($1 IS NULL OR username LIKE $1 || '%') ORDER BY id LIMIT $2 OFFSET $3"#,
+        )
+        .bind(username)
+        .bind(limit)
+        .bind(offset)
+        .fetch_all(pool)
+        .await
+    }
+
+    pub async fn count(username: Option<&str>, pool: &DbPool) -> Result<i64, sqlx::Error> {
+        sqlx::query_scalar(r#"SELECT COUNT(*) FROM users WHERE ($1 IS NULL OR username LIKE $1 || '%')"#)
+            .bind(username)
+            .fetch_one(pool)
+            .await
+    }
+}
+
 // Here we've implemented `Debug` manually to avoid accidentally logging the
 // password hash.
 impl std::fmt::Debug for DbUser {
@@ -25,6 +102,24 @@ pub struct DbPermission {
     pub name: String,
 }
 
+impl DbPermission {
+    /// The permissions granted to the groups the user is in.
+    pub async fn for_user(user_id: i64, pool: &DbPool) -> Result<Vec<DbPermission>, sqlx::Error> {
+        sqlx::query_as(
+            r#"
+            select distinct permissions.name
+            from users_groups
+            join groups_permissions on users_groups.group_id = groups_permissions.group_id
+            join permissions on groups_permissions.permission_id = permissions.id
+            where users_groups.user_id = $1
+            "#,
+        )
+        .bind(user_id)
+        .fetch_all(pool)
+        .await
+    }
+}
+
 impl From<&str> for DbPermission {
     fn from(name: &str) -> Self {
         DbPermission {
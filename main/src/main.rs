@@ -1,19 +1,355 @@
-use server::Server;
-use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt, EnvFilter};
+use std::path::{Path, PathBuf};
+
+use anyhow::{bail, Context};
+use clap::{Parser, Subcommand, ValueEnum};
+use db::{export::Dump, group::DbGroup, import::Forum, user::DbUser, DbPool, PoolSettings};
+use server::{config::Config, telemetry, Server};
+
+/// Foundry Forum server and administration tool.
+#[derive(Debug, Parser)]
+#[command(version, about)]
+struct Cli {
+    /// Path to the TOML config file. Defaults to `FORUM_CONFIG`, then
+    /// `forum.toml` in the working directory.
+    #[arg(long, short, global = true)]
+    config: Option<PathBuf>,
+
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Debug, Subcommand)]
+enum Command {
+    /// Run the web server (the default).
+    Serve,
+    /// Inspect or apply database migrations.
+    Migrate {
+        #[command(subcommand)]
+        command: MigrateCommand,
+    },
+    /// Create a user, optionally adding them to groups.
+    CreateUser {
+        username: String,
+        /// Groups to add the user to, on top of `users`.
+        #[arg(long = "group", short)]
+        groups: Vec<String>,
+        /// Read the password from this argument instead of prompting.
+        #[arg(long)]
+        password: Option<String>,
+    },
+    /// Add a user to a group.
+    Grant { username: String, group: String },
+    /// Set a new password for a user.
+    ResetPassword {
+        username: String,
+        /// Read the password from this argument instead of prompting.
+        #[arg(long)]
+        password: Option<String>,
+    },
+    /// Write the forum's users, groups, content and avatars to a JSON lines
+    /// file.
+    Export {
+        path: PathBuf,
+        /// Include password hashes, so users can log in after an import.
+        #[arg(long)]
+        passwords: bool,
+    },
+    /// Load a file written by `export` into an empty forum.
+    Import { path: PathBuf },
+    /// Load users, categories, threads and posts from other forum software
+    /// into an empty forum.
+    Convert {
+        path: PathBuf,
+        #[arg(long, value_enum, default_value_t = ConvertFormat::Forum)]
+        format: ConvertFormat,
+        /// The prefix of the phpBB tables.
+        #[arg(long, default_value = "phpbb_")]
+        table_prefix: String,
+    },
+    /// Copy the SQLite database while the forum keeps running. Without a
+    /// path, a snapshot is taken into `backup.dir`.
+    Backup { path: Option<PathBuf> },
+    /// Replace the SQLite database with a backup. Stop the forum first.
+    Restore { path: PathBuf },
+    /// Load and validate the configuration, then exit.
+    CheckConfig,
+}
+
+#[derive(Debug, Subcommand)]
+enum MigrateCommand {
+    /// Apply all pending migrations.
+    Up,
+    /// List migrations and whether they have been applied.
+    Status,
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+enum ConvertFormat {
+    /// The JSON lines format documented in `db::import`.
+    Forum,
+    /// A MySQL dump of a phpBB 3 database.
+    Phpbb,
+}
 
 #[tokio::main]
-async fn main() {
-    tracing_subscriber::registry()
-        .with(EnvFilter::new(std::env::var("RUST_LOG").unwrap_or_else(
-            |_| "foundry-forum=trace,server=trace,axum_login=debug,tower_sessions=debug,sqlx=warn".into(),
-        )))
-        .with(tracing_subscriber::fmt::layer())
-        .try_init()
-        .unwrap();
-
-    let app = Server::new("127.0.0.1:3000").await.unwrap();
-
-    let delete_task = app.get_delete_task();
-    let server_task = app.serve();
-    let _ = tokio::join!(delete_task, server_task);
+async fn main() -> anyhow::Result<()> {
+    let cli = Cli::parse();
+    let config = Config::load(cli.config.as_deref())?;
+    let _telemetry = telemetry::init(&config.log)?;
+
+    match cli.command.unwrap_or(Command::Serve) {
+        Command::Serve => serve(config).await,
+        Command::Migrate { command } => migrate(&config, command).await,
+        Command::CreateUser {
+            username,
+            groups,
+            password,
+        } => create_user(&config, &username, &groups, password).await,
+        Command::Grant { username, group } => grant(&config, &username, &group).await,
+        Command::ResetPassword { username, password } => {
+            reset_password(&config, &username, password).await
+        }
+        Command::Export { path, passwords } => export(&config, &path, passwords).await,
+        Command::Import { path } => import(&config, &path).await,
+        Command::Convert {
+            path,
+            format,
+            table_prefix,
+        } => convert(&config, &path, format, &table_prefix).await,
+        Command::Backup { path } => backup(&config, path.as_deref()).await,
+        Command::Restore { path } => restore(&config, &path).await,
+        Command::CheckConfig => {
+            println!("listen:      {}", config.server.listen);
+            println!("public url:  {}", config.server.public_url);
+            println!("database:    {}", config.database.url);
+            println!("sso:         {}", if config.oidc.is_some() { "enabled" } else { "disabled" });
+            println!("configuration is valid");
+            Ok(())
+        }
+    }
+}
+
+async fn serve(config: Config) -> anyhow::Result<()> {
+    Server::new(config).await?.run().await
+}
+
+/// Connects to the configured database and brings it up to date, so every
+/// admin command works against the current schema.
+async fn connect(config: &Config) -> anyhow::Result<DbPool> {
+    let db = db::pool(&config.database.url, &PoolSettings::from(&config.database)).await?;
+    db::migrate(db.acquire().await?).await?;
+    Ok(db)
+}
+
+async fn migrate(config: &Config, command: MigrateCommand) -> anyhow::Result<()> {
+    let db = db::pool(&config.database.url, &PoolSettings::from(&config.database)).await?;
+
+    if let MigrateCommand::Up = command {
+        db::migrate(db.acquire().await?).await?;
+    }
+
+    for migration in db::migration_status(&db).await? {
+        println!(
+            "{} {:<8} {}",
+            migration.version,
+            if migration.applied { "applied" } else { "pending" },
+            migration.description
+        );
+    }
+    Ok(())
+}
+
+async fn find_user(username: &str, db: &DbPool) -> anyhow::Result<DbUser> {
+    DbUser::find_by_username(username, db)
+        .await?
+        .with_context(|| format!("no user named {username}"))
+}
+
+async fn find_group(name: &str, db: &DbPool) -> anyhow::Result<DbGroup> {
+    DbGroup::find_by_name(name, db)
+        .await?
+        .with_context(|| format!("no group named {name}"))
+}
+
+/// Hashes the given password, prompting for one if it wasn't passed.
+fn password_hash(password: Option<String>) -> anyhow::Result<String> {
+    let password = match password {
+        Some(password) => password,
+        None => {
+            let password = rpassword::prompt_password("Password: ")?;
+            if password != rpassword::prompt_password("Repeat password: ")? {
+                bail!("passwords do not match");
+            }
+            password
+        }
+    };
+
+    if password.is_empty() {
+        bail!("the password must not be empty");
+    }
+    Ok(password_auth::generate_hash(password))
+}
+
+async fn create_user(
+    config: &Config,
+    username: &str,
+    groups: &[String],
+    password: Option<String>,
+) -> anyhow::Result<()> {
+    let db = connect(config).await?;
+
+    if DbUser::find_by_username(username, &db).await?.is_some() {
+        bail!("a user named {username} already exists");
+    }
+
+    let mut memberships = vec![find_group("users", &db).await?];
+    for group in groups {
+        memberships.push(find_group(group, &db).await?);
+    }
+
+    let user = DbUser::create(username, &password_hash(password)?, &db).await?;
+    for group in memberships {
+        DbGroup::add_user(group.id, user.id, &db).await?;
+    }
+
+    println!("created user {} with id {}", user.username, user.id);
+    Ok(())
+}
+
+async fn grant(config: &Config, username: &str, group: &str) -> anyhow::Result<()> {
+    let db = connect(config).await?;
+    let user = find_user(username, &db).await?;
+    let group = find_group(group, &db).await?;
+
+    DbGroup::add_user(group.id, user.id, &db).await?;
+    println!("added {} to {}", user.username, group.name);
+    Ok(())
+}
+
+async fn reset_password(
+    config: &Config,
+    username: &str,
+    password: Option<String>,
+) -> anyhow::Result<()> {
+    let db = connect(config).await?;
+    let user = find_user(username, &db).await?;
+
+    DbUser::set_password(user.id, &password_hash(password)?, &db).await?;
+    println!("password of {} has been reset", user.username);
+    Ok(())
+}
+
+async fn export(config: &Config, path: &Path, passwords: bool) -> anyhow::Result<()> {
+    let db = connect(config).await?;
+    let dump = Dump::export(&db, passwords).await?;
+
+    let file = std::fs::File::create(path)
+        .with_context(|| format!("failed to create {}", path.display()))?;
+    dump.write(std::io::BufWriter::new(file))?;
+
+    println!(
+        "exported {} users, {} categories, {} threads, {} posts, {} articles and {} avatars to {}",
+        dump.users.len(),
+        dump.categories.len(),
+        dump.threads.len(),
+        dump.posts.len(),
+        dump.articles.len(),
+        dump.avatar_users(),
+        path.display()
+    );
+    Ok(())
+}
+
+async fn import(config: &Config, path: &Path) -> anyhow::Result<()> {
+    let db = connect(config).await?;
+
+    let file = std::fs::File::open(path)
+        .with_context(|| format!("failed to open {}", path.display()))?;
+    let dump = Dump::read(file)
+        .with_context(|| format!("{} is not a forum export", path.display()))?;
+
+    let summary = dump.import(&db).await?;
+    println!(
+        "imported {} new users, {} new groups, {} categories, {} threads, {} posts, {} articles and {} avatars",
+        summary.users,
+        summary.groups,
+        summary.categories,
+        summary.threads,
+        summary.posts,
+        summary.articles,
+        summary.avatars
+    );
+    Ok(())
+}
+
+async fn convert(
+    config: &Config,
+    path: &Path,
+    format: ConvertFormat,
+    table_prefix: &str,
+) -> anyhow::Result<()> {
+    let db = connect(config).await?;
+
+    let file = std::fs::File::open(path)
+        .with_context(|| format!("failed to open {}", path.display()))?;
+    let forum = match format {
+        ConvertFormat::Forum => Forum::read(file),
+        ConvertFormat::Phpbb => std::io::read_to_string(file)
+            .map_err(anyhow::Error::from)
+            .and_then(|dump| db::import::phpbb::read(&dump, table_prefix)),
+    }
+    .with_context(|| format!("failed to read {}", path.display()))?;
+
+    let summary = forum.import(&db).await?;
+    println!(
+        "imported {} new users, {} categories, {} threads and {} posts",
+        summary.users, summary.categories, summary.threads, summary.posts
+    );
+    Ok(())
+}
+
+#[cfg(not(feature = "postgres"))]
+async fn backup(config: &Config, path: Option<&Path>) -> anyhow::Result<()> {
+    // Migrating is left to the server, a backup should be of the database as
+    // it is.
+    let db = db::pool(&config.database.url, &PoolSettings::from(&config.database)).await?;
+    let path = match path {
+        Some(path) => {
+            db::backup::backup(&db, path).await?;
+            path.to_path_buf()
+        }
+        None => {
+            let dir = config
+                .backup
+                .dir
+                .as_deref()
+                .context("pass a path or set backup.dir")?;
+            db::backup::snapshot(&db, dir, config.backup.keep).await?
+        }
+    };
+    println!("backed up {} to {}", config.database.url, path.display());
+    Ok(())
+}
+
+#[cfg(not(feature = "postgres"))]
+async fn restore(config: &Config, path: &Path) -> anyhow::Result<()> {
+    let target = db::backup::database_path(&config.database.url)?;
+    let version = db::backup::restore(path, &target).await?;
+    println!(
+        "restored {} (schema version {version}) to {}, the previous database is at {}.before-restore",
+        path.display(),
+        target.display(),
+        target.display()
+    );
+    Ok(())
+}
+
+#[cfg(feature = "postgres")]
+async fn backup(_config: &Config, _path: Option<&Path>) -> anyhow::Result<()> {
+    bail!("backups only work with SQLite, back up PostgreSQL with pg_dump")
+}
+
+#[cfg(feature = "postgres")]
+async fn restore(_config: &Config, _path: &Path) -> anyhow::Result<()> {
+    bail!("restores only work with SQLite, restore PostgreSQL with pg_restore")
 }
@@ -1,2 +1,13 @@
 import "../vendored/htmx.js"
 import "../../build/index.css"
+
+// htmx ignores 4xx/5xx responses by default, which leaves boosted navigation
+// silently stuck on the old page. The server renders proper error pages for
+// boosted requests (see `render_errors`), so swap them in like any other page.
+document.addEventListener("htmx:beforeSwap", (event) => {
+    const detail = (event as CustomEvent).detail
+    if (detail.boosted && detail.xhr.status >= 400) {
+        detail.shouldSwap = true
+        detail.isError = false
+    }
+})
@@ -0,0 +1,213 @@
+use std::sync::Arc;
+
+use axum::{
+    extract::{
+        rejection::{JsonRejection, QueryRejection},
+        Path, Query, State,
+    },
+    http::StatusCode,
+    Json,
+};
+use axum_login::AuthSession;
+use db::article::Article;
+use serde::Deserialize;
+use utoipa::{IntoParams, ToSchema};
+
+use super::{writer, Page, Pagination};
+use crate::{
+    api_error::ApiError,
+    api_token::CurrentUser,
+    auth::{Backend, ContentAction},
+    AppState,
+};
+
+#[derive(Debug, Default, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct ArticleFilter {
+    user_id: Option<i64>,
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct NewArticle {
+    title: String,
+    editor_content: serde_json::Value,
+    content: String,
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct ArticleUpdate {
+    title: Option<String>,
+    editor_content: Option<serde_json::Value>,
+    content: Option<String>,
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/articles",
+    tag = "articles",
+    params(Pagination, ArticleFilter),
+    responses(
+        (status = 200, description = "A page of articles", body = ArticlePage),
+        (status = 400, description = "Invalid query", body = ProblemDetails),
+    )
+)]
+pub async fn list(
+    state: State<Arc<AppState>>,
+    pagination: Result<Query<Pagination>, QueryRejection>,
+    filter: Result<Query<ArticleFilter>, QueryRejection>,
+) -> Result<Json<Page<Article>>, ApiError> {
+    let Query(pagination) = pagination?;
+    let Query(filter) = filter?;
+
+    let items = state
+        .repos
+        .articles
+        .list(filter.user_id, pagination.per_page(), pagination.offset())
+        .await?;
+    let total = state.repos.articles.count(filter.user_id).await?;
+    Ok(Json(Page::new(items, pagination, total)))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/articles/{id}",
+    tag = "articles",
+    params(("id" = i64, Path, description = "Article id")),
+    responses(
+        (status = 200, description = "The article", body = Article),
+        (status = 404, description = "No such article", body = ProblemDetails),
+    )
+)]
+pub async fn get(state: State<Arc<AppState>>, Path(id): Path<i64>) -> Result<Json<Article>, ApiError> {
+    state
+        .repos
+        .articles
+        .find_by_id(id)
+        .await?
+        .map(Json)
+        .ok_or(ApiError::NotFound)
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/articles",
+    tag = "articles",
+    request_body = NewArticle,
+    responses(
+        (status = 201, description = "The created article", body = Article),
+        (status = 400, description = "Invalid article", body = ProblemDetails),
+        (status = 401, description = "Not authenticated", body = ProblemDetails),
+        (status = 403, description = "Token lacks the write scope", body = ProblemDetails),
+    ),
+    security(("session" = []), ("token" = []))
+)]
+pub async fn create(
+    state: State<Arc<AppState>>,
+    current: Option<CurrentUser>,
+    payload: Result<Json<NewArticle>, JsonRejection>,
+) -> Result<(StatusCode, Json<Article>), ApiError> {
+    let current = writer(current)?;
+    let Json(payload) = payload?;
+
+    if payload.title.trim().is_empty() {
+        return Err(ApiError::Validation("title must not be empty".into()));
+    }
+
+    let article = state
+        .repos
+        .articles
+        .create(current.user.0.id, payload.title, payload.editor_content, payload.content)
+        .await?;
+    Ok((StatusCode::CREATED, Json(article)))
+}
+
+#[utoipa::path(
+    patch,
+    path = "/api/v1/articles/{id}",
+    tag = "articles",
+    params(("id" = i64, Path, description = "Article id")),
+    request_body = ArticleUpdate,
+    responses(
+        (status = 200, description = "The updated article", body = Article),
+        (status = 400, description = "Invalid update", body = ProblemDetails),
+        (status = 401, description = "Not authenticated", body = ProblemDetails),
+        (status = 403, description = "Not allowed to edit this article", body = ProblemDetails),
+        (status = 404, description = "No such article", body = ProblemDetails),
+    ),
+    security(("session" = []), ("token" = []))
+)]
+pub async fn update(
+    state: State<Arc<AppState>>,
+    auth_session: AuthSession<Backend>,
+    current: Option<CurrentUser>,
+    Path(id): Path<i64>,
+    payload: Result<Json<ArticleUpdate>, JsonRejection>,
+) -> Result<Json<Article>, ApiError> {
+    let current = writer(current)?;
+    let Json(payload) = payload?;
+
+    let article = state
+        .repos
+        .articles
+        .find_by_id(id)
+        .await?
+        .ok_or(ApiError::NotFound)?;
+    if !auth_session
+        .backend
+        .can_modify(&current.user, Some(article.user_id), ContentAction::Edit)
+        .await?
+    {
+        return Err(ApiError::Forbidden);
+    }
+
+    let article = state
+        .repos
+        .articles
+        .update(
+            id,
+            payload.title.unwrap_or(article.title),
+            payload.editor_content.unwrap_or(article.editor_content),
+            payload.content.unwrap_or(article.content),
+        )
+        .await?;
+    Ok(Json(article))
+}
+
+#[utoipa::path(
+    delete,
+    path = "/api/v1/articles/{id}",
+    tag = "articles",
+    params(("id" = i64, Path, description = "Article id")),
+    responses(
+        (status = 204, description = "The article was deleted"),
+        (status = 401, description = "Not authenticated", body = ProblemDetails),
+        (status = 403, description = "Not allowed to delete this article", body = ProblemDetails),
+        (status = 404, description = "No such article", body = ProblemDetails),
+    ),
+    security(("session" = []), ("token" = []))
+)]
+pub async fn delete(
+    state: State<Arc<AppState>>,
+    auth_session: AuthSession<Backend>,
+    current: Option<CurrentUser>,
+    Path(id): Path<i64>,
+) -> Result<StatusCode, ApiError> {
+    let current = writer(current)?;
+
+    let article = state
+        .repos
+        .articles
+        .find_by_id(id)
+        .await?
+        .ok_or(ApiError::NotFound)?;
+    if !auth_session
+        .backend
+        .can_modify(&current.user, Some(article.user_id), ContentAction::Delete)
+        .await?
+    {
+        return Err(ApiError::Forbidden);
+    }
+
+    state.repos.articles.delete(id).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
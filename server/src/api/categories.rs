@@ -0,0 +1,57 @@
+use std::sync::Arc;
+
+use axum::{
+    extract::{rejection::QueryRejection, Path, Query, State},
+    Json,
+};
+use db::category::Category;
+
+use super::{Page, Pagination};
+use crate::{
+    api_error::ApiError,
+    AppState,
+};
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/categories",
+    tag = "categories",
+    params(Pagination),
+    responses(
+        (status = 200, description = "A page of categories", body = CategoryPage),
+        (status = 400, description = "Invalid query", body = ProblemDetails),
+    )
+)]
+pub async fn list(
+    state: State<Arc<AppState>>,
+    pagination: Result<Query<Pagination>, QueryRejection>,
+) -> Result<Json<Page<Category>>, ApiError> {
+    let Query(pagination) = pagination?;
+    let items = state
+        .repos
+        .categories
+        .list(pagination.per_page(), pagination.offset())
+        .await?;
+    let total = state.repos.categories.count().await?;
+    Ok(Json(Page::new(items, pagination, total)))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/categories/{id}",
+    tag = "categories",
+    params(("id" = i64, Path, description = "Category id")),
+    responses(
+        (status = 200, description = "The category", body = Category),
+        (status = 404, description = "No such category", body = ProblemDetails),
+    )
+)]
+pub async fn get(state: State<Arc<AppState>>, Path(id): Path<i64>) -> Result<Json<Category>, ApiError> {
+    state
+        .repos
+        .categories
+        .find_by_id(id)
+        .await?
+        .map(Json)
+        .ok_or(ApiError::NotFound)
+}
@@ -0,0 +1,198 @@
+//! Versioned JSON API, nested under `/api/v1`. Handlers share the `db`
+//! models and the permission checks of the HTML routes; reads are public,
+//! writes need a logged in user or an API token with the `write` scope.
+//! The OpenAPI document is generated from the handlers and served at
+//! `/api/v1/openapi.json`.
+
+mod articles;
+mod categories;
+mod posts;
+mod threads;
+mod users;
+
+use std::sync::Arc;
+
+use axum::{routing::get, Json, Router};
+use db::{article::Article, category::Category, post::Post, thread::Thread, user::PublicUser};
+use serde::{Deserialize, Serialize};
+use utoipa::{
+    openapi::security::{ApiKey, ApiKeyValue, HttpAuthScheme, HttpBuilder, SecurityScheme},
+    IntoParams, Modify, OpenApi, ToSchema,
+};
+
+use crate::{
+    api_error::{ApiError, ProblemDetails},
+    api_token::{CurrentUser, TokenScope},
+    AppState,
+};
+
+const DEFAULT_PER_PAGE: i64 = 20;
+const MAX_PER_PAGE: i64 = 100;
+
+#[derive(OpenApi)]
+#[openapi(
+    info(title = "Foundry Forum API"),
+    paths(
+        categories::list,
+        categories::get,
+        threads::list,
+        threads::get,
+        threads::create,
+        threads::update,
+        threads::delete,
+        posts::list,
+        posts::get,
+        posts::create,
+        posts::update,
+        posts::delete,
+        articles::list,
+        articles::get,
+        articles::create,
+        articles::update,
+        articles::delete,
+        users::list,
+        users::me,
+        users::get,
+    ),
+    components(schemas(
+        Article,
+        Category,
+        Post,
+        Thread,
+        PublicUser,
+        ArticlePage,
+        CategoryPage,
+        PostPage,
+        ThreadPage,
+        UserPage,
+        ProblemDetails,
+        threads::NewThread,
+        threads::ThreadUpdate,
+        posts::NewPost,
+        posts::PostUpdate,
+        articles::NewArticle,
+        articles::ArticleUpdate,
+    )),
+    modifiers(&SecuritySchemes),
+    tags(
+        (name = "categories"),
+        (name = "threads"),
+        (name = "posts"),
+        (name = "articles"),
+        (name = "users"),
+    )
+)]
+pub struct ApiDoc;
+
+struct SecuritySchemes;
+
+impl Modify for SecuritySchemes {
+    fn modify(&self, openapi: &mut utoipa::openapi::OpenApi) {
+        let components = openapi.components.get_or_insert_with(Default::default);
+        components.add_security_scheme(
+            "session",
+            SecurityScheme::ApiKey(ApiKey::Cookie(ApiKeyValue::new("id"))),
+        );
+        components.add_security_scheme(
+            "token",
+            SecurityScheme::Http(HttpBuilder::new().scheme(HttpAuthScheme::Bearer).build()),
+        );
+    }
+}
+
+/// The OpenAPI document describing this API.
+pub fn openapi() -> utoipa::openapi::OpenApi {
+    ApiDoc::openapi()
+}
+
+pub fn router() -> Router<Arc<AppState>> {
+    Router::new()
+        .route("/openapi.json", get(|| async { Json(openapi()) }))
+        .route("/categories", get(categories::list))
+        .route("/categories/:id", get(categories::get))
+        .route("/threads", get(threads::list).post(threads::create))
+        .route(
+            "/threads/:id",
+            get(threads::get).patch(threads::update).delete(threads::delete),
+        )
+        .route("/posts", get(posts::list).post(posts::create))
+        .route(
+            "/posts/:id",
+            get(posts::get).patch(posts::update).delete(posts::delete),
+        )
+        .route("/articles", get(articles::list).post(articles::create))
+        .route(
+            "/articles/:id",
+            get(articles::get).patch(articles::update).delete(articles::delete),
+        )
+        .route("/users", get(users::list))
+        .route("/users/me", get(users::me))
+        .route("/users/:id", get(users::get))
+        .fallback(|| async { ApiError::NotFound })
+}
+
+/// `?page=&per_page=` query parameters shared by every list endpoint.
+#[derive(Debug, Clone, Copy, Default, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct Pagination {
+    /// 1-based page number.
+    page: Option<i64>,
+    /// Items per page, at most 100.
+    per_page: Option<i64>,
+}
+
+impl Pagination {
+    /// Capped so the offset of the last page still fits an `i64`.
+    pub fn page(&self) -> i64 {
+        self.page.unwrap_or(1).clamp(1, i64::MAX / MAX_PER_PAGE)
+    }
+
+    pub fn per_page(&self) -> i64 {
+        self.per_page
+            .unwrap_or(DEFAULT_PER_PAGE)
+            .clamp(1, MAX_PER_PAGE)
+    }
+
+    pub fn offset(&self) -> i64 {
+        (self.page() - 1) * self.per_page()
+    }
+}
+
+/// A page of results together with what is needed to fetch the others.
+#[derive(Debug, Serialize, ToSchema)]
+#[aliases(
+    ArticlePage = Page<Article>,
+    CategoryPage = Page<Category>,
+    PostPage = Page<Post>,
+    ThreadPage = Page<Thread>,
+    UserPage = Page<PublicUser>,
+)]
+pub struct Page<T> {
+    pub items: Vec<T>,
+    pub page: i64,
+    pub per_page: i64,
+    pub total: i64,
+    pub total_pages: i64,
+}
+
+impl<T> Page<T> {
+    pub fn new(items: Vec<T>, pagination: Pagination, total: i64) -> Self {
+        let per_page = pagination.per_page();
+        Self {
+            items,
+            page: pagination.page(),
+            per_page,
+            total,
+            total_pages: (total + per_page - 1) / per_page,
+        }
+    }
+}
+
+/// Requires an authenticated caller that is allowed to write.
+fn writer(current: Option<CurrentUser>) -> Result<CurrentUser, ApiError> {
+    let current = current.ok_or(ApiError::Unauthorized)?;
+    if !current.allows(TokenScope::Write) {
+        return Err(ApiError::Forbidden);
+    }
+    Ok(current)
+}
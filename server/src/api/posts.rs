@@ -0,0 +1,205 @@
+use std::sync::Arc;
+
+use axum::{
+    extract::{
+        rejection::{JsonRejection, QueryRejection},
+        Path, Query, State,
+    },
+    http::StatusCode,
+    Json,
+};
+use axum_login::AuthSession;
+use db::post::{Post, PostFilter};
+use serde::Deserialize;
+use utoipa::ToSchema;
+
+use super::{writer, Page, Pagination};
+use crate::{
+    api_error::ApiError,
+    api_token::CurrentUser,
+    auth::{Backend, ContentAction},
+    AppState,
+};
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct NewPost {
+    thread_id: i64,
+    title: String,
+    content: String,
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct PostUpdate {
+    title: Option<String>,
+    content: Option<String>,
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/posts",
+    tag = "posts",
+    params(Pagination, PostFilter),
+    responses(
+        (status = 200, description = "A page of posts", body = PostPage),
+        (status = 400, description = "Invalid query", body = ProblemDetails),
+    )
+)]
+pub async fn list(
+    state: State<Arc<AppState>>,
+    pagination: Result<Query<Pagination>, QueryRejection>,
+    filter: Result<Query<PostFilter>, QueryRejection>,
+) -> Result<Json<Page<Post>>, ApiError> {
+    let Query(pagination) = pagination?;
+    let Query(filter) = filter?;
+
+    let items = state
+        .repos
+        .posts
+        .list(&filter, pagination.per_page(), pagination.offset())
+        .await?;
+    let total = state.repos.posts.count(&filter).await?;
+    Ok(Json(Page::new(items, pagination, total)))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/posts/{id}",
+    tag = "posts",
+    params(("id" = i64, Path, description = "Post id")),
+    responses(
+        (status = 200, description = "The post", body = Post),
+        (status = 404, description = "No such post", body = ProblemDetails),
+    )
+)]
+pub async fn get(state: State<Arc<AppState>>, Path(id): Path<i64>) -> Result<Json<Post>, ApiError> {
+    state
+        .repos
+        .posts
+        .find_by_id(id)
+        .await?
+        .map(Json)
+        .ok_or(ApiError::NotFound)
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/posts",
+    tag = "posts",
+    request_body = NewPost,
+    responses(
+        (status = 201, description = "The created post", body = Post),
+        (status = 400, description = "Invalid post", body = ProblemDetails),
+        (status = 401, description = "Not authenticated", body = ProblemDetails),
+        (status = 403, description = "Token lacks the write scope", body = ProblemDetails),
+    ),
+    security(("session" = []), ("token" = []))
+)]
+pub async fn create(
+    state: State<Arc<AppState>>,
+    current: Option<CurrentUser>,
+    payload: Result<Json<NewPost>, JsonRejection>,
+) -> Result<(StatusCode, Json<Post>), ApiError> {
+    let current = writer(current)?;
+    let Json(payload) = payload?;
+
+    if state.repos.threads.find_by_id(payload.thread_id).await?.is_none() {
+        return Err(ApiError::Validation("thread does not exist".into()));
+    }
+
+    let post = state
+        .repos
+        .posts
+        .create(payload.thread_id, current.user.0.id, payload.title, payload.content)
+        .await?;
+    Ok((StatusCode::CREATED, Json(post)))
+}
+
+#[utoipa::path(
+    patch,
+    path = "/api/v1/posts/{id}",
+    tag = "posts",
+    params(("id" = i64, Path, description = "Post id")),
+    request_body = PostUpdate,
+    responses(
+        (status = 200, description = "The updated post", body = Post),
+        (status = 400, description = "Invalid update", body = ProblemDetails),
+        (status = 401, description = "Not authenticated", body = ProblemDetails),
+        (status = 403, description = "Not allowed to edit this post", body = ProblemDetails),
+        (status = 404, description = "No such post", body = ProblemDetails),
+    ),
+    security(("session" = []), ("token" = []))
+)]
+pub async fn update(
+    state: State<Arc<AppState>>,
+    auth_session: AuthSession<Backend>,
+    current: Option<CurrentUser>,
+    Path(id): Path<i64>,
+    payload: Result<Json<PostUpdate>, JsonRejection>,
+) -> Result<Json<Post>, ApiError> {
+    let current = writer(current)?;
+    let Json(payload) = payload?;
+
+    let post = state
+        .repos
+        .posts
+        .find_by_id(id)
+        .await?
+        .ok_or(ApiError::NotFound)?;
+    if !auth_session
+        .backend
+        .can_modify(&current.user, post.user_id, ContentAction::Edit)
+        .await?
+    {
+        return Err(ApiError::Forbidden);
+    }
+
+    let post = state
+        .repos
+        .posts
+        .update(
+            id,
+            payload.title.unwrap_or(post.title),
+            payload.content.unwrap_or(post.content),
+        )
+        .await?;
+    Ok(Json(post))
+}
+
+#[utoipa::path(
+    delete,
+    path = "/api/v1/posts/{id}",
+    tag = "posts",
+    params(("id" = i64, Path, description = "Post id")),
+    responses(
+        (status = 204, description = "The post was deleted"),
+        (status = 401, description = "Not authenticated", body = ProblemDetails),
+        (status = 403, description = "Not allowed to delete this post", body = ProblemDetails),
+        (status = 404, description = "No such post", body = ProblemDetails),
+    ),
+    security(("session" = []), ("token" = []))
+)]
+pub async fn delete(
+    state: State<Arc<AppState>>,
+    auth_session: AuthSession<Backend>,
+    current: Option<CurrentUser>,
+    Path(id): Path<i64>,
+) -> Result<StatusCode, ApiError> {
+    let current = writer(current)?;
+
+    let post = state
+        .repos
+        .posts
+        .find_by_id(id)
+        .await?
+        .ok_or(ApiError::NotFound)?;
+    if !auth_session
+        .backend
+        .can_modify(&current.user, post.user_id, ContentAction::Delete)
+        .await?
+    {
+        return Err(ApiError::Forbidden);
+    }
+
+    state.repos.posts.delete(id).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
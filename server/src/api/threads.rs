@@ -0,0 +1,197 @@
+use std::sync::Arc;
+
+use axum::{
+    extract::{
+        rejection::{JsonRejection, QueryRejection},
+        Path, Query, State,
+    },
+    http::StatusCode,
+    Json,
+};
+use axum_login::AuthSession;
+use db::thread::{Thread, ThreadFilter};
+use serde::Deserialize;
+use utoipa::ToSchema;
+
+use super::{writer, Page, Pagination};
+use crate::{
+    api_error::ApiError,
+    api_token::CurrentUser,
+    auth::{Backend, ContentAction},
+    AppState,
+};
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct NewThread {
+    category_id: i64,
+    title: String,
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct ThreadUpdate {
+    title: String,
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/threads",
+    tag = "threads",
+    params(Pagination, ThreadFilter),
+    responses(
+        (status = 200, description = "A page of threads", body = ThreadPage),
+        (status = 400, description = "Invalid query", body = ProblemDetails),
+    )
+)]
+pub async fn list(
+    state: State<Arc<AppState>>,
+    pagination: Result<Query<Pagination>, QueryRejection>,
+    filter: Result<Query<ThreadFilter>, QueryRejection>,
+) -> Result<Json<Page<Thread>>, ApiError> {
+    let Query(pagination) = pagination?;
+    let Query(filter) = filter?;
+
+    let items = state
+        .repos
+        .threads
+        .list(&filter, pagination.per_page(), pagination.offset())
+        .await?;
+    let total = state.repos.threads.count(&filter).await?;
+    Ok(Json(Page::new(items, pagination, total)))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/threads/{id}",
+    tag = "threads",
+    params(("id" = i64, Path, description = "Thread id")),
+    responses(
+        (status = 200, description = "The thread", body = Thread),
+        (status = 404, description = "No such thread", body = ProblemDetails),
+    )
+)]
+pub async fn get(state: State<Arc<AppState>>, Path(id): Path<i64>) -> Result<Json<Thread>, ApiError> {
+    state
+        .repos
+        .threads
+        .find_by_id(id)
+        .await?
+        .map(Json)
+        .ok_or(ApiError::NotFound)
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/threads",
+    tag = "threads",
+    request_body = NewThread,
+    responses(
+        (status = 201, description = "The created thread", body = Thread),
+        (status = 400, description = "Invalid thread", body = ProblemDetails),
+        (status = 401, description = "Not authenticated", body = ProblemDetails),
+        (status = 403, description = "Token lacks the write scope", body = ProblemDetails),
+    ),
+    security(("session" = []), ("token" = []))
+)]
+pub async fn create(
+    state: State<Arc<AppState>>,
+    current: Option<CurrentUser>,
+    payload: Result<Json<NewThread>, JsonRejection>,
+) -> Result<(StatusCode, Json<Thread>), ApiError> {
+    let current = writer(current)?;
+    let Json(payload) = payload?;
+
+    if payload.title.trim().is_empty() {
+        return Err(ApiError::Validation("title must not be empty".into()));
+    }
+    if state.repos.categories.find_by_id(payload.category_id).await?.is_none() {
+        return Err(ApiError::Validation("category does not exist".into()));
+    }
+
+    let thread = state
+        .repos
+        .threads
+        .create(payload.category_id, current.user.0.id, payload.title)
+        .await?;
+    Ok((StatusCode::CREATED, Json(thread)))
+}
+
+#[utoipa::path(
+    patch,
+    path = "/api/v1/threads/{id}",
+    tag = "threads",
+    params(("id" = i64, Path, description = "Thread id")),
+    request_body = ThreadUpdate,
+    responses(
+        (status = 200, description = "The updated thread", body = Thread),
+        (status = 400, description = "Invalid update", body = ProblemDetails),
+        (status = 401, description = "Not authenticated", body = ProblemDetails),
+        (status = 403, description = "Not allowed to edit this thread", body = ProblemDetails),
+        (status = 404, description = "No such thread", body = ProblemDetails),
+    ),
+    security(("session" = []), ("token" = []))
+)]
+pub async fn update(
+    state: State<Arc<AppState>>,
+    auth_session: AuthSession<Backend>,
+    current: Option<CurrentUser>,
+    Path(id): Path<i64>,
+    payload: Result<Json<ThreadUpdate>, JsonRejection>,
+) -> Result<Json<Thread>, ApiError> {
+    let current = writer(current)?;
+    let Json(payload) = payload?;
+
+    let thread = state
+        .repos
+        .threads
+        .find_by_id(id)
+        .await?
+        .ok_or(ApiError::NotFound)?;
+    if !auth_session
+        .backend
+        .can_modify(&current.user, thread.user_id, ContentAction::Edit)
+        .await?
+    {
+        return Err(ApiError::Forbidden);
+    }
+
+    Ok(Json(state.repos.threads.update(id, payload.title).await?))
+}
+
+#[utoipa::path(
+    delete,
+    path = "/api/v1/threads/{id}",
+    tag = "threads",
+    params(("id" = i64, Path, description = "Thread id")),
+    responses(
+        (status = 204, description = "The thread was deleted"),
+        (status = 401, description = "Not authenticated", body = ProblemDetails),
+        (status = 403, description = "Not allowed to delete this thread", body = ProblemDetails),
+        (status = 404, description = "No such thread", body = ProblemDetails),
+    ),
+    security(("session" = []), ("token" = []))
+)]
+pub async fn delete(
+    state: State<Arc<AppState>>,
+    auth_session: AuthSession<Backend>,
+    current: Option<CurrentUser>,
+    Path(id): Path<i64>,
+) -> Result<StatusCode, ApiError> {
+    let current = writer(current)?;
+
+    let thread = state
+        .repos
+        .threads
+        .find_by_id(id)
+        .await?
+        .ok_or(ApiError::NotFound)?;
+    if !auth_session
+        .backend
+        .can_modify(&current.user, thread.user_id, ContentAction::Delete)
+        .await?
+    {
+        return Err(ApiError::Forbidden);
+    }
+
+    state.repos.threads.delete(id).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
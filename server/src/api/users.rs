@@ -0,0 +1,86 @@
+use std::sync::Arc;
+
+use axum::{
+    extract::{rejection::QueryRejection, Path, Query, State},
+    Json,
+};
+use db::user::PublicUser;
+use serde::Deserialize;
+use utoipa::IntoParams;
+
+use super::{Page, Pagination};
+use crate::{
+    api_error::ApiError,
+    api_token::CurrentUser,
+    AppState,
+};
+
+#[derive(Debug, Default, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct UserFilter {
+    /// Only return users whose name starts with this prefix.
+    username: Option<String>,
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/users",
+    tag = "users",
+    params(Pagination, UserFilter),
+    responses(
+        (status = 200, description = "A page of users", body = UserPage),
+        (status = 400, description = "Invalid query", body = ProblemDetails),
+    )
+)]
+pub async fn list(
+    state: State<Arc<AppState>>,
+    pagination: Result<Query<Pagination>, QueryRejection>,
+    filter: Result<Query<UserFilter>, QueryRejection>,
+) -> Result<Json<Page<PublicUser>>, ApiError> {
+    let Query(pagination) = pagination?;
+    let Query(filter) = filter?;
+    let username = filter.username.as_deref();
+
+    let items = state
+        .repos
+        .users
+        .list(username, pagination.per_page(), pagination.offset())
+        .await?;
+    let total = state.repos.users.count(username).await?;
+    Ok(Json(Page::new(items, pagination, total)))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/users/{id}",
+    tag = "users",
+    params(("id" = i64, Path, description = "User id")),
+    responses(
+        (status = 200, description = "The user", body = PublicUser),
+        (status = 404, description = "No such user", body = ProblemDetails),
+    )
+)]
+pub async fn get(state: State<Arc<AppState>>, Path(id): Path<i64>) -> Result<Json<PublicUser>, ApiError> {
+    state
+        .repos
+        .users
+        .find_by_id(id)
+        .await?
+        .map(|user| Json(user.into()))
+        .ok_or(ApiError::NotFound)
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/users/me",
+    tag = "users",
+    responses(
+        (status = 200, description = "The authenticated user", body = PublicUser),
+        (status = 401, description = "Not authenticated", body = ProblemDetails),
+    ),
+    security(("session" = []), ("token" = []))
+)]
+pub async fn me(current: Option<CurrentUser>) -> Result<Json<PublicUser>, ApiError> {
+    let current = current.ok_or(ApiError::Unauthorized)?;
+    Ok(Json(current.user.0.into()))
+}
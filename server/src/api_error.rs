@@ -1,27 +1,289 @@
+use std::{any::Any, sync::Arc};
+
 use axum::{
-    http::StatusCode,
+    extract::{
+        multipart::MultipartError,
+        rejection::{FormRejection, JsonRejection, PathRejection, QueryRejection},
+        Request, State,
+    },
+    http::{
+        header::{ACCEPT, CONTENT_TYPE},
+        HeaderMap, HeaderValue, StatusCode,
+    },
+    middleware::Next,
     response::{IntoResponse, Response},
+    Json,
 };
+use axum_htmx::HxBoosted;
+use db::{error::DbError, sqlx};
+use minijinja::context;
+use serde::Serialize;
+use utoipa::ToSchema;
+
+use crate::{auth::Backend, AppState};
 
+/// Every error a handler can fail with. Handlers return it with `?`; the
+/// [`render_errors`] middleware then turns it into an HTML error page or a
+/// JSON problem details body, depending on what the client accepts.
 #[derive(Debug)]
 pub enum ApiError {
     TemplateNotFound(String),
     TemplateRender(String),
+    NotFound,
+    Unauthorized,
+    Forbidden,
+    Validation(String),
+    /// The request body is larger than the route accepts.
+    PayloadTooLarge(String),
+    Database(DbError),
+    Session(String),
+    Internal(String),
+}
+
+impl std::fmt::Display for ApiError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::TemplateNotFound(name) => write!(f, "template \"{name}\" does not exist"),
+            Self::TemplateRender(name) => write!(f, "failed to render template \"{name}\""),
+            Self::NotFound => write!(f, "not found"),
+            Self::Unauthorized => write!(f, "authentication required"),
+            Self::Forbidden => write!(f, "permission denied"),
+            Self::Validation(message) | Self::PayloadTooLarge(message) => write!(f, "{message}"),
+            Self::Database(err) => write!(f, "database error: {err}"),
+            Self::Session(err) => write!(f, "session error: {err}"),
+            Self::Internal(err) => write!(f, "{err}"),
+        }
+    }
+}
+
+impl std::error::Error for ApiError {}
+
+impl ApiError {
+    pub fn status(&self) -> StatusCode {
+        match self {
+            Self::TemplateNotFound(_) | Self::NotFound => StatusCode::NOT_FOUND,
+            Self::Unauthorized => StatusCode::UNAUTHORIZED,
+            Self::Forbidden => StatusCode::FORBIDDEN,
+            Self::Validation(_) => StatusCode::BAD_REQUEST,
+            Self::PayloadTooLarge(_) => StatusCode::PAYLOAD_TOO_LARGE,
+            Self::TemplateRender(_)
+            | Self::Database(_)
+            | Self::Session(_)
+            | Self::Internal(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+
+    /// The message shown to the client. Server side failures are not
+    /// described in detail; they are logged instead.
+    fn detail(&self) -> String {
+        match self {
+            Self::Validation(message) | Self::PayloadTooLarge(message) => message.clone(),
+            err if err.status().is_server_error() => "Something went wrong on our end.".into(),
+            err => {
+                let mut detail = err.to_string();
+                detail[..1].make_ascii_uppercase();
+                detail + "."
+            }
+        }
+    }
+}
+
+/// An RFC 7807 problem details body.
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct ProblemDetails {
+    #[serde(rename = "type")]
+    pub kind: String,
+    pub title: String,
+    pub status: u16,
+    pub detail: String,
+}
+
+impl ProblemDetails {
+    pub fn new(status: StatusCode, detail: impl Into<String>) -> Self {
+        Self {
+            kind: "about:blank".into(),
+            title: status.canonical_reason().unwrap_or("Error").into(),
+            status: status.as_u16(),
+            detail: detail.into(),
+        }
+    }
+}
+
+/// Attached to error responses so [`render_errors`] can render them once the
+/// request context is known. `source` is the full error for the logs.
+#[derive(Debug, Clone)]
+pub struct ErrorReport {
+    pub problem: ProblemDetails,
+    pub source: String,
 }
 
 impl IntoResponse for ApiError {
     fn into_response(self) -> Response {
-        let (status_code, message) = match self {
-            Self::TemplateNotFound(template_name) => (
-                StatusCode::NOT_FOUND,
-                format!("template \"{template_name}\" does not exist"),
-            ),
-            Self::TemplateRender(template_name) => (
-                StatusCode::INTERNAL_SERVER_ERROR,
-                format!("failed to render template \"{template_name}\""),
-            ),
+        let status = self.status();
+        let report = ErrorReport {
+            problem: ProblemDetails::new(status, self.detail()),
+            source: self.to_string(),
         };
 
-        (status_code, message).into_response()
+        // The plain text body is only seen if the middleware isn't installed.
+        let mut response = (status, report.problem.detail.clone()).into_response();
+        response.extensions_mut().insert(report);
+        response
+    }
+}
+
+impl From<DbError> for ApiError {
+    fn from(err: DbError) -> Self {
+        match err {
+            DbError::NotFound | DbError::UserNotFound => Self::NotFound,
+            DbError::Sqlx(sqlx::Error::RowNotFound) => Self::NotFound,
+            err => Self::Database(err),
+        }
+    }
+}
+
+impl From<sqlx::Error> for ApiError {
+    fn from(err: sqlx::Error) -> Self {
+        DbError::from(err).into()
+    }
+}
+
+impl From<axum_login::Error<Backend>> for ApiError {
+    fn from(err: axum_login::Error<Backend>) -> Self {
+        match err {
+            axum_login::Error::Backend(err) => err.into(),
+            axum_login::Error::Session(err) => Self::Session(err.to_string()),
+        }
+    }
+}
+
+impl From<tower_sessions::session::Error> for ApiError {
+    fn from(err: tower_sessions::session::Error) -> Self {
+        Self::Session(err.to_string())
+    }
+}
+
+impl From<serde_json::Error> for ApiError {
+    fn from(err: serde_json::Error) -> Self {
+        Self::Internal(format!("serialization error: {err}"))
+    }
+}
+
+impl From<JsonRejection> for ApiError {
+    fn from(rejection: JsonRejection) -> Self {
+        Self::Validation(rejection.body_text())
+    }
+}
+
+impl From<QueryRejection> for ApiError {
+    fn from(rejection: QueryRejection) -> Self {
+        Self::Validation(rejection.body_text())
+    }
+}
+
+impl From<FormRejection> for ApiError {
+    fn from(rejection: FormRejection) -> Self {
+        Self::Validation(rejection.body_text())
+    }
+}
+
+impl From<PathRejection> for ApiError {
+    fn from(rejection: PathRejection) -> Self {
+        Self::Validation(rejection.body_text())
+    }
+}
+
+impl From<MultipartError> for ApiError {
+    fn from(err: MultipartError) -> Self {
+        match err.status() {
+            StatusCode::PAYLOAD_TOO_LARGE => Self::PayloadTooLarge(err.body_text()),
+            _ => Self::Validation(err.body_text()),
+        }
+    }
+}
+
+fn wants_json(headers: &HeaderMap, path: &str) -> bool {
+    path.starts_with("/api/")
+        || headers
+            .get(ACCEPT)
+            .and_then(|accept| accept.to_str().ok())
+            .is_some_and(|accept| {
+                accept.contains("application/json") || accept.contains("application/problem+json")
+            })
+}
+
+/// Turns a panicking handler into a regular 500 so it gets an error page
+/// instead of a dropped connection.
+pub fn panic_response(panic: Box<dyn Any + Send + 'static>) -> Response {
+    let message = panic
+        .downcast_ref::<String>()
+        .map(String::as_str)
+        .or_else(|| panic.downcast_ref::<&str>().copied())
+        .unwrap_or("unknown panic");
+
+    ApiError::Internal(format!("handler panicked: {message}")).into_response()
+}
+
+/// Renders errors produced by [`ApiError`] for the client: a problem details
+/// body for API clients, an error page for browsers.
+///
+/// The page is `<status>.html` when such a template exists, `error.html`
+/// otherwise. htmx doesn't swap error responses by default, so boosted
+/// requests get the page without the layout and are retargeted at the main
+/// content area, which the client script then swaps in.
+pub async fn render_errors(
+    State(state): State<Arc<AppState>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let method = request.method().clone();
+    let path = request.uri().path().to_owned();
+    let json = wants_json(request.headers(), &path);
+    let boosted = request.headers().contains_key("hx-boosted");
+
+    let mut response = next.run(request).await;
+    let Some(report) = response.extensions_mut().remove::<ErrorReport>() else {
+        return response;
+    };
+
+    let status = response.status();
+    if status.is_server_error() {
+        tracing::error!(%method, %path, %status, error = %report.source, "request failed");
+    } else {
+        tracing::debug!(%method, %path, %status, error = %report.source, "request rejected");
+    }
+
+    if json {
+        let mut response = (status, Json(report.problem)).into_response();
+        response.headers_mut().insert(
+            CONTENT_TYPE,
+            HeaderValue::from_static("application/problem+json"),
+        );
+        return response;
+    }
+
+    let templates = [format!("{}.html", status.as_u16()), "error.html".to_owned()];
+    let page = templates.iter().find_map(|template| {
+        state
+            .render_with_context(
+                HxBoosted(boosted),
+                template,
+                context! {
+                    problem => report.problem.clone(),
+                },
+            )
+            .ok()
+    });
+
+    let Some(page) = page else {
+        return (status, report.problem.detail).into_response();
+    };
+
+    let mut response = (status, page).into_response();
+    if boosted {
+        let headers = response.headers_mut();
+        headers.insert("hx-retarget", HeaderValue::from_static("#content"));
+        headers.insert("hx-reswap", HeaderValue::from_static("innerHTML"));
     }
+    response
 }
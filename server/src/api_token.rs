@@ -0,0 +1,152 @@
+use std::{collections::HashSet, sync::Arc};
+
+use axum::{
+    extract::{FromRequestParts, Request, State},
+    http::{header::AUTHORIZATION, request::Parts},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use axum_login::AuthSession;
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::{
+    api_error::ApiError,
+    auth::{now, Backend, User},
+    telemetry, AppState,
+};
+
+/// Prefix of every issued token, so leaked tokens are easy to recognise.
+const TOKEN_PREFIX: &str = "ffp_";
+
+/// What an API token is allowed to do.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TokenScope {
+    Read,
+    Write,
+}
+
+impl TokenScope {
+    pub const ALL: [TokenScope; 2] = [TokenScope::Read, TokenScope::Write];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            TokenScope::Read => "read",
+            TokenScope::Write => "write",
+        }
+    }
+
+    pub fn parse(scope: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|s| s.as_str() == scope)
+    }
+}
+
+pub fn parse_scopes(scopes: &str) -> HashSet<TokenScope> {
+    scopes.split_whitespace().filter_map(TokenScope::parse).collect()
+}
+
+/// Generates a new random token, returning the plaintext to show to the user
+/// and the hash to store.
+pub fn generate_token() -> (String, String) {
+    let mut bytes = [0u8; 32];
+    rand::thread_rng().fill_bytes(&mut bytes);
+    let token = format!("{TOKEN_PREFIX}{}", URL_SAFE_NO_PAD.encode(bytes));
+    let hash = hash_token(&token);
+    (token, hash)
+}
+
+/// Tokens carry 256 bits of randomness, so a plain SHA-256 is enough to
+/// protect them at rest and keeps lookups cheap.
+pub fn hash_token(token: &str) -> String {
+    format!("{:x}", Sha256::digest(token.as_bytes()))
+}
+
+/// The user a request is made on behalf of, authenticated either through the
+/// session cookie or an API token. Session users may do everything, token
+/// users are limited to the token's scopes.
+#[derive(Debug, Clone)]
+pub struct CurrentUser {
+    pub user: User,
+    pub scopes: Option<HashSet<TokenScope>>,
+}
+
+impl CurrentUser {
+    pub fn allows(&self, scope: TokenScope) -> bool {
+        self.scopes
+            .as_ref()
+            .map_or(true, |scopes| scopes.contains(&scope))
+    }
+}
+
+#[axum::async_trait]
+impl<S> FromRequestParts<S> for CurrentUser
+where
+    S: Send + Sync,
+{
+    type Rejection = ApiError;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        if let Some(current) = parts.extensions.get::<CurrentUser>() {
+            return Ok(current.clone());
+        }
+
+        let auth_session = AuthSession::<Backend>::from_request_parts(parts, state)
+            .await
+            .map_err(|(_, message)| ApiError::Session(message.to_owned()))?;
+
+        auth_session
+            .user
+            .map(|user| CurrentUser { user, scopes: None })
+            .ok_or(ApiError::Unauthorized)
+    }
+}
+
+/// Authenticates requests carrying an `Authorization: Bearer` header. On
+/// success the token's user is made available through [`CurrentUser`];
+/// an unknown token is rejected outright rather than falling back to the
+/// session.
+pub async fn bearer_auth(
+    State(state): State<Arc<AppState>>,
+    mut request: Request,
+    next: Next,
+) -> Response {
+    let Some(header) = request.headers().get(AUTHORIZATION) else {
+        return next.run(request).await;
+    };
+
+    let Some(token) = header
+        .to_str()
+        .ok()
+        .and_then(|value| value.strip_prefix("Bearer "))
+    else {
+        return ApiError::Unauthorized.into_response();
+    };
+
+    let stored = match state.repos.tokens.find_by_hash(&hash_token(token.trim())).await {
+        Ok(Some(stored)) => stored,
+        Ok(None) => return ApiError::Unauthorized.into_response(),
+        Err(e) => return ApiError::from(e).into_response(),
+    };
+
+    let user = match state.repos.users.find_by_id(stored.user_id).await {
+        Ok(Some(user)) => User(user),
+        Ok(None) => return ApiError::Unauthorized.into_response(),
+        Err(e) => return ApiError::from(e).into_response(),
+    };
+
+    telemetry::record_user(user.0.id);
+
+    if let Err(e) = state.repos.tokens.touch(stored.id, now()).await {
+        tracing::warn!("failed to record API token use: {e}");
+    }
+
+    request.extensions_mut().insert(CurrentUser {
+        user,
+        scopes: Some(parse_scopes(&stored.scopes)),
+    });
+
+    next.run(request).await
+}
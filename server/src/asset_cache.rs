@@ -6,8 +6,7 @@ use axum_cc::MimeType;
 use bytes::Bytes;
 use tokio::io::AsyncWriteExt;
 
-/// A shared reference to the static asset cache.
-pub type SharedAssetCache = &'static AssetCache;
+use crate::embedded::Source;
 
 const HASH_SPLIT_CHAR: char = '.';
 
@@ -15,7 +14,8 @@ const HASH_SPLIT_CHAR: char = '.';
 /// is used to serve static assets from the build directory without reading from
 /// disk, as the cache stays in RAM for the life of the server.
 ///
-/// This type should be accessed via the `cache` property in `AppState`.
+/// This type should be accessed through the [`Frontend`](crate::frontend::Frontend)
+/// in `AppState`.
 pub struct AssetCache(HashMap<String, StaticAsset>);
 
 impl AssetCache {
@@ -31,10 +31,12 @@ impl AssetCache {
         self.get(&key)
     }
 
-    /// Loads the assets and leaks the allocation, returning a &'static AssetCache.
-    pub async fn load_static() -> &'static AssetCache {
-        let asset_cache = AssetCache::load_files().await;
-        Box::leak(Box::new(asset_cache))
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
     }
 
     fn get_cache_key(path: &str) -> String {
@@ -46,30 +48,16 @@ impl AssetCache {
         format!("{}.{}", basename, ext)
     }
 
-    async fn load_files() -> Self {
+    pub async fn load(source: &Source) -> anyhow::Result<Self> {
         let mut cache = HashMap::default();
 
-        let assets: Vec<_> = std::fs::read_dir("server/build")
-            .unwrap_or_else(|e| panic!("failed to read build directory: {}", e))
-            .filter_map(Result::ok)
-            .filter_map(|file| {
-                let path = file.path();
-                let filename = path.file_name()?.to_str()?;
-                let ext = path.extension()?.to_str()?;
-
-                let stored_path = path
-                    .clone()
-                    .into_os_string()
-                    .into_string()
-                    .ok()?
-                    .replace("server/build", "assets")
-                    .replace("\\", "/");
-
-             
-
-                std::fs::read(&path)
-                    .ok()
-                    .map(|bytes| (stored_path, bytes, ext.to_string(), filename.to_string()))
+        let assets: Vec<_> = source
+            .assets()?
+            .into_iter()
+            .filter_map(|(filename, bytes)| {
+                let ext = std::path::Path::new(&filename).extension()?.to_str()?.to_owned();
+                let stored_path = format!("assets/{filename}");
+                Some((stored_path, bytes, ext, filename))
             })
             .collect();
 
@@ -94,7 +82,7 @@ impl AssetCache {
             tracing::debug!("{} -> {}", key, asset.path);
         }
 
-        Self(cache)
+        Ok(Self(cache))
     }
 
     /// Returns an iterator over the static assets in the cache.
@@ -1,14 +1,18 @@
-use std::collections::HashSet;
+use std::{collections::HashSet, sync::Arc};
 
 use axum_login::{AuthUser, AuthnBackend, AuthzBackend, UserId};
 use db::{
     error::DbError,
-    sqlx,
+    repo::Repositories,
     user::{DbPermission, DbUser},
-    SqlitePool,
 };
 use password_auth::verify_password;
 use serde::{Deserialize, Serialize};
+use crate::oidc::{Oidc, OidcCredentials};
+use webauthn_rs::prelude::{
+    CredentialID, DiscoverableAuthentication, DiscoverableKey, Passkey, PublicKeyCredential, Uuid,
+    Webauthn,
+};
 
 #[derive(Clone, Debug, Deserialize, Serialize)]
 pub struct User(pub DbUser);
@@ -34,26 +38,151 @@ impl AsRef<DbUser> for User {
     }
 }
 
+// The different ways a user can prove who they are. Each variant is handled by
+// `Backend::authenticate`, so every login path ends up in the same auth session.
+#[derive(Debug, Clone)]
+pub enum Credentials {
+    Password(PasswordCredentials),
+    Passkey(PasskeyCredentials),
+    Oidc(OidcCredentials),
+}
+
 // This allows us to extract the authentication fields from forms. We use this
 // to authenticate requests with the backend.
 #[derive(Debug, Clone, Deserialize)]
-pub struct Credentials {
+pub struct PasswordCredentials {
     pub username: String,
     pub password: String,
     pub next: Option<String>,
+    // Checkboxes are only submitted when checked.
+    pub remember_me: Option<String>,
 }
 
+// The signed assertion returned by the browser together with the challenge
+// state we stored in the session when the login ceremony was started.
 #[derive(Debug, Clone)]
+pub struct PasskeyCredentials {
+    pub state: DiscoverableAuthentication,
+    pub response: PublicKeyCredential,
+}
+
+#[derive(Clone)]
 pub struct Backend {
-    db: SqlitePool,
+    repos: Repositories,
+    webauthn: Arc<Webauthn>,
+    oidc: Option<Arc<Oidc>>,
+}
+
+// Neither the repositories nor `Webauthn` implement `Debug`, so we leave
+// them out.
+impl std::fmt::Debug for Backend {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Backend")
+            .field("oidc", &self.oidc)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Backend {
+    pub fn new(repos: Repositories, webauthn: Arc<Webauthn>, oidc: Option<Arc<Oidc>>) -> Self {
+        Self { repos, webauthn, oidc }
+    }
+
+    async fn authenticate_password(
+        &self,
+        creds: PasswordCredentials,
+    ) -> Result<Option<User>, DbError> {
+        let user = self.repos.users.find_by_username(&creds.username).await?;
+
+        if let Some(user) = user {
+            if verify_password(creds.password, &user.password).is_ok() {
+                return Ok(Some(User(user)));
+            } else {
+                Err(DbError::PasswordIncorrect)
+            }
+        } else {
+            Err(DbError::UserNotFound)
+        }
+    }
+
+    async fn authenticate_passkey(&self, creds: PasskeyCredentials) -> Result<Option<User>, DbError> {
+        let (_, credential_id) = self
+            .webauthn
+            .identify_discoverable_authentication(&creds.response)
+            .map_err(|e| DbError::CredentialRejected(e.to_string()))?;
+
+        let Some(stored) =
+            self.repos.passkeys.find_by_credential_id(&encode_credential_id(credential_id)).await?
+        else {
+            return Err(DbError::CredentialRejected("unknown passkey".into()));
+        };
+
+        let mut passkey: Passkey = serde_json::from_str(&stored.passkey)
+            .map_err(|e| DbError::Other(e.into()))?;
+
+        let result = self
+            .webauthn
+            .finish_discoverable_authentication(
+                &creds.response,
+                creds.state,
+                &[DiscoverableKey::from(&passkey)],
+            )
+            .map_err(|e| DbError::CredentialRejected(e.to_string()))?;
+
+        // The authenticator may have bumped its signature counter or backup
+        // state, which we have to persist to detect cloned credentials.
+        passkey.update_credential(&result);
+        let serialized = serde_json::to_string(&passkey).map_err(|e| DbError::Other(e.into()))?;
+        self.repos.passkeys.touch(stored.id, serialized, now()).await?;
+
+        let user = self.repos.users.find_by_id(stored.user_id).await?;
+        Ok(user.map(User))
+    }
+}
+
+/// Something a user wants to do to a piece of content they may or may not own.
+#[derive(Debug, Clone, Copy)]
+pub enum ContentAction {
+    Edit,
+    Delete,
 }
 
 impl Backend {
-    pub fn new(db: SqlitePool) -> Self {
-        Self { db }
+    /// Checks the `*_own_post` and `*_any_post` permissions of `user` against
+    /// content owned by `owner_id`.
+    pub async fn can_modify(
+        &self,
+        user: &User,
+        owner_id: Option<i64>,
+        action: ContentAction,
+    ) -> Result<bool, DbError> {
+        let permissions = self.get_all_permissions(user).await?;
+        let (own, any) = match action {
+            ContentAction::Edit => ("edit_own_post", "edit_any_post"),
+            ContentAction::Delete => ("delete_own_post", "delete_any_post"),
+        };
+
+        Ok(permissions.contains(&DbPermission::from(any))
+            || (owner_id == Some(user.0.id) && permissions.contains(&DbPermission::from(own))))
     }
 }
 
+/// WebAuthn wants a stable, opaque handle per user. We derive it from the
+/// user id so no extra column is needed.
+pub fn webauthn_user_id(user_id: i64) -> Uuid {
+    Uuid::from_u64_pair(0, user_id as u64)
+}
+
+/// Encodes a raw credential id the same way it is stored in the `passkeys`
+/// table.
+pub fn encode_credential_id(credential_id: &[u8]) -> String {
+    CredentialID::from(credential_id.to_vec()).to_string()
+}
+
+pub fn now() -> i64 {
+    time::OffsetDateTime::now_utc().unix_timestamp()
+}
+
 #[axum::async_trait]
 impl AuthnBackend for Backend {
     type User = User;
@@ -64,28 +193,20 @@ impl AuthnBackend for Backend {
         &self,
         creds: Self::Credentials,
     ) -> Result<Option<Self::User>, Self::Error> {
-        let user: Option<DbUser> = sqlx::query_as("select * from users where username = ? ")
-            .bind(creds.username)
-            .fetch_optional(&self.db)
-            .await?;
-
-        if let Some(user) = user {
-            if verify_password(creds.password, &user.password).is_ok() {
-                return Ok(Some(User(user)));
-            } else {
-                Err(DbError::PasswordIncorrect)
+        match creds {
+            Credentials::Password(creds) => self.authenticate_password(creds).await,
+            Credentials::Passkey(creds) => self.authenticate_passkey(creds).await,
+            Credentials::Oidc(creds) => {
+                let Some(oidc) = &self.oidc else {
+                    return Err(DbError::CredentialRejected("single sign-on is not configured".into()));
+                };
+                oidc.authenticate(creds).await.map(|user| Some(User(user)))
             }
-        } else {
-            Err(DbError::UserNotFound)
         }
     }
 
     async fn get_user(&self, user_id: &UserId<Self>) -> Result<Option<Self::User>, Self::Error> {
-        let user: Option<DbUser> = sqlx::query_as("select * from users where id = ?")
-            .bind(user_id)
-            .fetch_optional(&self.db)
-            .await?;
-
+        let user = self.repos.users.find_by_id(*user_id).await?;
         Ok(user.map(User))
     }
 }
@@ -98,20 +219,7 @@ impl AuthzBackend for Backend {
         &self,
         user: &Self::User,
     ) -> Result<HashSet<Self::Permission>, Self::Error> {
-        let permissions: Vec<Self::Permission> = sqlx::query_as(
-            r#"
-            select distinct permissions.name
-            from users
-            join users_groups on users.id = users_groups.user_id
-            join groups_permissions on users_groups.group_id = groups_permissions.group_id
-            join permissions on groups_permissions.permission_id = permissions.id
-            where users.id = ?
-            "#,
-        )
-        .bind(user.0.id)
-        .fetch_all(&self.db)
-        .await?;
-
+        let permissions = self.repos.users.permissions(user.0.id).await?;
         Ok(permissions.into_iter().collect())
     }
 }
@@ -0,0 +1,145 @@
+//! Avatar images. Uploads are decoded, cropped to a square and re-encoded in
+//! each of [`SIZES`], which also drops EXIF and any other metadata. Users
+//! without an avatar get an identicon derived from their id. Avatars stored
+//! as uploaded, see [`ORIGINAL_SIZE`], are resized when the server starts.
+
+use std::io::Cursor;
+
+use db::{
+    avatar::{Avatar, ORIGINAL_SIZE},
+    DbPool,
+};
+use image::{
+    codecs::{jpeg::JpegEncoder, png::PngEncoder},
+    imageops::FilterType,
+    DynamicImage, ImageDecoder, ImageFormat, ImageReader, Limits,
+};
+use sha2::{Digest, Sha256};
+
+use crate::api_error::ApiError;
+
+/// The sizes every avatar is stored in, in pixels.
+pub const SIZES: [u32; 4] = [32, 64, 128, 256];
+/// The size served when none is asked for.
+pub const DEFAULT_SIZE: u32 = 128;
+
+/// Uploads with a larger width or height are rejected before decoding, so a
+/// small file can't expand into a huge image.
+const MAX_DIMENSION: u32 = 4096;
+const JPEG_QUALITY: u8 = 85;
+
+/// The stored size to serve for a requested one: the smallest that is at
+/// least as large, or the largest.
+pub fn standard_size(requested: Option<u32>) -> u32 {
+    let requested = requested.unwrap_or(DEFAULT_SIZE);
+    SIZES
+        .into_iter()
+        .find(|&size| size >= requested)
+        .unwrap_or(SIZES[SIZES.len() - 1])
+}
+
+/// Decodes an uploaded PNG, JPEG, GIF or WebP image and resizes it to each
+/// of [`SIZES`]. Images with transparency are stored as PNG, others as JPEG.
+/// This is CPU bound, run it with `spawn_blocking`.
+pub fn resize(user_id: i64, upload: &[u8]) -> Result<Vec<Avatar>, ApiError> {
+    let mut reader = ImageReader::new(Cursor::new(upload))
+        .with_guessed_format()
+        .map_err(|e| ApiError::Internal(format!("failed to read an avatar upload: {e}")))?;
+    if !matches!(
+        reader.format(),
+        Some(ImageFormat::Png | ImageFormat::Jpeg | ImageFormat::Gif | ImageFormat::WebP)
+    ) {
+        return Err(ApiError::Validation(
+            "Avatars must be PNG, JPEG, GIF or WebP images.".into(),
+        ));
+    }
+
+    let mut limits = Limits::default();
+    limits.max_image_width = Some(MAX_DIMENSION);
+    limits.max_image_height = Some(MAX_DIMENSION);
+    reader.limits(limits);
+
+    let unreadable = |_| ApiError::Validation(format!(
+        "The image could not be read. Avatars can be at most {MAX_DIMENSION}×{MAX_DIMENSION} pixels."
+    ));
+    let mut decoder = reader.into_decoder().map_err(unreadable)?;
+    // Phones store photos sideways and say so in EXIF, which is dropped.
+    let orientation = decoder.orientation().map_err(unreadable)?;
+    let mut image = DynamicImage::from_decoder(decoder).map_err(unreadable)?;
+    image.apply_orientation(orientation);
+
+    let side = image.width().min(image.height());
+    let image = image.crop_imm(
+        (image.width() - side) / 2,
+        (image.height() - side) / 2,
+        side,
+        side,
+    );
+    let transparent = image.color().has_alpha();
+
+    SIZES
+        .into_iter()
+        .map(|size| {
+            let resized = image.resize_exact(size, size, FilterType::Lanczos3);
+            let mut img_data = Vec::new();
+            let (content_type, encoded) = if transparent {
+                let encoder = PngEncoder::new(&mut img_data);
+                ("image/png", DynamicImage::ImageRgba8(resized.to_rgba8()).write_with_encoder(encoder))
+            } else {
+                let encoder = JpegEncoder::new_with_quality(&mut img_data, JPEG_QUALITY);
+                ("image/jpeg", DynamicImage::ImageRgb8(resized.to_rgb8()).write_with_encoder(encoder))
+            };
+            encoded.map_err(|e| ApiError::Internal(format!("failed to encode an avatar: {e}")))?;
+
+            Ok(Avatar {
+                user_id,
+                size: size.into(),
+                content_type: content_type.into(),
+                img_data,
+            })
+        })
+        .collect()
+}
+
+/// Resizes every avatar still stored at [`ORIGINAL_SIZE`]. Ones that can't be
+/// read are deleted, so their users get an identicon instead.
+pub async fn resize_originals(db: &DbPool) -> anyhow::Result<()> {
+    for original in Avatar::originals(db).await? {
+        let user_id = original.user_id;
+        match tokio::task::spawn_blocking(move || resize(user_id, &original.img_data)).await? {
+            Ok(avatars) => Avatar::replace(user_id, &avatars, db).await?,
+            Err(e) => {
+                tracing::warn!(user_id, "deleting an avatar that can't be resized: {e}");
+                Avatar::delete(user_id, db).await?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// A symmetric 5×5 pattern in a color derived from the user id, as SVG.
+pub fn identicon(user_id: i64, size: u32) -> String {
+    let hash = Sha256::digest(user_id.to_be_bytes());
+    let hue = u16::from_be_bytes([hash[0], hash[1]]) % 360;
+
+    let mut svg = format!(
+        r##"<svg xmlns="http://www.w3.org/2000/svg" width="{size}" height="{size}" viewBox="0 0 5 5" shape-rendering="crispEdges"><rect width="5" height="5" fill="#f0f0f0"/><g fill="hsl({hue}, 55%, 50%)">"##
+    );
+    // The left three columns decide the pattern, the right two mirror them.
+    for column in 0..3 {
+        for row in 0..5 {
+            let bit = column * 5 + row;
+            if hash[2 + bit / 8] >> (bit % 8) & 1 == 0 {
+                continue;
+            }
+            for x in [column, 4 - column] {
+                svg.push_str(&format!(r#"<rect x="{x}" y="{row}" width="1" height="1"/>"#));
+                if x == 2 {
+                    break;
+                }
+            }
+        }
+    }
+    svg.push_str("</g></svg>");
+    svg
+}
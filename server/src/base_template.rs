@@ -1,8 +1,7 @@
+use anyhow::Context;
 use serde::Serialize;
 
-use crate::asset_cache::SharedAssetCache;
-
-pub type SharedBaseTemplateData = &'static BaseTemplateData;
+use crate::asset_cache::AssetCache;
 
 #[derive(Clone, Serialize)]
 pub struct BaseTemplateData {
@@ -11,25 +10,19 @@ pub struct BaseTemplateData {
 }
 
 impl BaseTemplateData {
-    pub fn new(assets: SharedAssetCache, css: &str, js: &str) -> Self {
+    pub fn new(assets: &AssetCache, css: &str, js: &str) -> anyhow::Result<Self> {
         let styles = assets
             .get(css)
-            .expect(format!("failed to build base template data: {}", css).as_str())
+            .with_context(|| format!("failed to build base template data: {}", css))?
             .path
             .clone();
 
         let scripts = assets
             .get(js)
-            .expect(format!("failed to build base template data: {}", js).as_str())
+            .with_context(|| format!("failed to build base template data: {}", js))?
             .path
             .clone();
 
-        Self { styles, scripts }
-    }
-
-    /// Loads the assets and leaks the allocation, returning a &'static AssetCache.
-    pub fn load_static(assets: SharedAssetCache, css: &str, js: &str) -> &'static BaseTemplateData {
-        let template_data = BaseTemplateData::new(assets, css, js);
-        Box::leak(Box::new(template_data))
+        Ok(Self { styles, scripts })
     }
 }
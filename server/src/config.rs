@@ -0,0 +1,428 @@
+use std::{
+    net::SocketAddr,
+    path::{Path, PathBuf},
+    str::FromStr,
+    time::Duration,
+};
+
+use anyhow::{bail, ensure, Context};
+use db::PoolSettings;
+use serde::Deserialize;
+use url::Url;
+
+pub use crate::oidc::OidcConfig;
+use crate::SessionExpiry;
+
+/// Read when neither `--config` nor `FORUM_CONFIG` name a config file. It is
+/// fine for it not to exist, the defaults are meant for local development.
+pub const DEFAULT_CONFIG_PATH: &str = "forum.toml";
+
+/// Everything the server can be configured with. Values come from the
+/// built-in defaults, then the TOML config file, then the environment, each
+/// layer overriding the previous one.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub server: ServerConfig,
+    pub database: DatabaseConfig,
+    pub backup: BackupConfig,
+    pub session: SessionConfig,
+    pub webauthn: WebauthnConfig,
+    pub oidc: Option<OidcConfig>,
+    pub avatars: AvatarConfig,
+    pub log: LogConfig,
+    pub dev: DevConfig,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ServerConfig {
+    /// Address the HTTP listener binds to.
+    pub listen: SocketAddr,
+    /// URL the forum is reached at by browsers. Used as the allowed CORS
+    /// origin and the WebAuthn origin.
+    pub public_url: Url,
+    /// Only send the session cookie over HTTPS.
+    pub secure_cookies: bool,
+    /// Brotli quality of compressed responses, 0 to 11.
+    pub compression_level: i32,
+    /// Seconds in-flight requests and background jobs get to finish on
+    /// shutdown.
+    pub shutdown_timeout_secs: u64,
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        Self {
+            listen: SocketAddr::from(([127, 0, 0, 1], 3000)),
+            public_url: Url::parse("http://localhost:3000").expect("valid default URL"),
+            secure_cookies: false,
+            compression_level: 4,
+            shutdown_timeout_secs: 30,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct DatabaseConfig {
+    pub url: String,
+    pub max_connections: u32,
+    /// Connections kept open even when idle.
+    pub min_connections: u32,
+    /// Seconds a request waits for a free connection before failing.
+    pub acquire_timeout_secs: u64,
+    /// Milliseconds SQLite waits for another connection to finish writing.
+    pub busy_timeout_ms: u64,
+    /// Seconds between runs of `PRAGMA optimize` and a WAL checkpoint on
+    /// SQLite, 0 to never run them.
+    pub optimize_interval_secs: u64,
+}
+
+impl Default for DatabaseConfig {
+    fn default() -> Self {
+        let pool = PoolSettings::default();
+        Self {
+            url: if cfg!(feature = "postgres") {
+                "postgres://localhost/forum".into()
+            } else {
+                "sqlite:test.db".into()
+            },
+            max_connections: pool.max_connections,
+            min_connections: pool.min_connections,
+            acquire_timeout_secs: pool.acquire_timeout.as_secs(),
+            busy_timeout_ms: pool.busy_timeout.as_millis() as u64,
+            optimize_interval_secs: 3600,
+        }
+    }
+}
+
+impl From<&DatabaseConfig> for PoolSettings {
+    fn from(config: &DatabaseConfig) -> Self {
+        Self {
+            max_connections: config.max_connections,
+            min_connections: config.min_connections,
+            acquire_timeout: Duration::from_secs(config.acquire_timeout_secs),
+            busy_timeout: Duration::from_millis(config.busy_timeout_ms),
+        }
+    }
+}
+
+/// Scheduled snapshots of the SQLite database, see `db::backup`.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct BackupConfig {
+    /// Directory snapshots are written to. Nothing is backed up unless set.
+    pub dir: Option<PathBuf>,
+    /// Hours between snapshots.
+    pub interval_hours: u64,
+    /// How many snapshots to keep, older ones are deleted.
+    pub keep: usize,
+}
+
+impl Default for BackupConfig {
+    fn default() -> Self {
+        Self {
+            dir: None,
+            interval_hours: 24,
+            keep: 7,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SessionConfig {
+    /// Minutes of inactivity after which a regular login expires.
+    pub inactivity_minutes: i64,
+    /// Days of inactivity after which a "remember me" login expires.
+    pub remember_me_days: i64,
+}
+
+impl Default for SessionConfig {
+    fn default() -> Self {
+        Self {
+            inactivity_minutes: 60,
+            remember_me_days: 30,
+        }
+    }
+}
+
+impl From<&SessionConfig> for SessionExpiry {
+    fn from(config: &SessionConfig) -> Self {
+        Self {
+            inactivity: time::Duration::minutes(config.inactivity_minutes),
+            remember_me: time::Duration::days(config.remember_me_days),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct WebauthnConfig {
+    /// Relying party id passkeys are bound to. Defaults to the host of
+    /// `server.public_url`.
+    pub rp_id: Option<String>,
+    pub rp_name: String,
+}
+
+impl Default for WebauthnConfig {
+    fn default() -> Self {
+        Self {
+            rp_id: None,
+            rp_name: "Foundry Forum".into(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct AvatarConfig {
+    /// Largest accepted upload in KiB. Uploads are resized, so this only
+    /// bounds what has to be received and decoded.
+    pub max_upload_kib: usize,
+}
+
+impl Default for AvatarConfig {
+    fn default() -> Self {
+        Self { max_upload_kib: 2048 }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LogFormat {
+    /// Human readable lines, for terminals.
+    #[default]
+    Pretty,
+    /// One JSON object per event, for log collectors.
+    Json,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LogConfig {
+    /// `tracing_subscriber` filter directives, e.g. `server=debug,sqlx=warn`.
+    pub filter: String,
+    pub format: LogFormat,
+    /// OTLP/gRPC endpoint of an OpenTelemetry collector to export spans to.
+    /// Spans are only exported when this is set.
+    pub otlp_endpoint: Option<Url>,
+    /// `service.name` of the exported spans.
+    pub service_name: String,
+}
+
+impl Default for LogConfig {
+    fn default() -> Self {
+        Self {
+            filter: "foundry-forum=trace,server=trace,axum_login=debug,tower_sessions=debug,sqlx=warn".into(),
+            format: LogFormat::Pretty,
+            otlp_endpoint: None,
+            service_name: "foundry-forum".into(),
+        }
+    }
+}
+
+/// Settings only meant for working on the forum itself.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct DevConfig {
+    /// Path of the `server` crate. When set, templates and built assets are
+    /// read from its `templates` and `build` directories instead of the
+    /// copies embedded in the binary.
+    pub source_dir: Option<PathBuf>,
+    /// Watch `source_dir` and reload templates and assets when they change,
+    /// refreshing open pages.
+    pub reload: bool,
+}
+
+impl Config {
+    /// Loads the config file at `path`, falling back to `FORUM_CONFIG` and
+    /// then [`DEFAULT_CONFIG_PATH`], applies the environment overrides and
+    /// validates the result.
+    pub fn load(path: Option<&Path>) -> anyhow::Result<Self> {
+        let explicit = path
+            .map(Path::to_path_buf)
+            .or_else(|| dotenvy::var("FORUM_CONFIG").ok().map(PathBuf::from));
+
+        let mut config = match explicit {
+            Some(path) => Self::from_file(&path)?,
+            None if Path::new(DEFAULT_CONFIG_PATH).exists() => {
+                Self::from_file(Path::new(DEFAULT_CONFIG_PATH))?
+            }
+            None => Self::default(),
+        };
+
+        config.apply_env()?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    pub fn from_file(path: &Path) -> anyhow::Result<Self> {
+        let contents = std::fs::read_to_string(path)
+            .with_context(|| format!("failed to read config file {}", path.display()))?;
+        toml::from_str(&contents)
+            .with_context(|| format!("invalid config file {}", path.display()))
+    }
+
+    /// Overrides settings with the environment, including a `.env` file.
+    /// The variable names predate the config file and are kept as is.
+    fn apply_env(&mut self) -> anyhow::Result<()> {
+        env_override("LISTEN_ADDR", &mut self.server.listen)?;
+        env_override("PUBLIC_URL", &mut self.server.public_url)?;
+        env_override("SECURE_COOKIES", &mut self.server.secure_cookies)?;
+        env_override("COMPRESSION_LEVEL", &mut self.server.compression_level)?;
+        env_override("SHUTDOWN_TIMEOUT_SECS", &mut self.server.shutdown_timeout_secs)?;
+        env_override("DATABASE_URL", &mut self.database.url)?;
+        env_override("DATABASE_MAX_CONNECTIONS", &mut self.database.max_connections)?;
+        env_override("DATABASE_MIN_CONNECTIONS", &mut self.database.min_connections)?;
+        env_override("DATABASE_ACQUIRE_TIMEOUT_SECS", &mut self.database.acquire_timeout_secs)?;
+        env_override("DATABASE_BUSY_TIMEOUT_MS", &mut self.database.busy_timeout_ms)?;
+        env_override("DATABASE_OPTIMIZE_INTERVAL_SECS", &mut self.database.optimize_interval_secs)?;
+        if let Ok(dir) = dotenvy::var("BACKUP_DIR") {
+            self.backup.dir = Some(dir.into());
+        }
+        env_override("BACKUP_INTERVAL_HOURS", &mut self.backup.interval_hours)?;
+        env_override("BACKUP_KEEP", &mut self.backup.keep)?;
+        env_override("SESSION_INACTIVITY_MINUTES", &mut self.session.inactivity_minutes)?;
+        env_override("SESSION_REMEMBER_ME_DAYS", &mut self.session.remember_me_days)?;
+        if let Ok(rp_id) = dotenvy::var("WEBAUTHN_RP_ID") {
+            self.webauthn.rp_id = Some(rp_id);
+        }
+        env_override("WEBAUTHN_RP_NAME", &mut self.webauthn.rp_name)?;
+        self.oidc = OidcConfig::apply_env(self.oidc.take())?;
+        env_override("AVATARS_MAX_UPLOAD_KIB", &mut self.avatars.max_upload_kib)?;
+        env_override("RUST_LOG", &mut self.log.filter)?;
+        if let Ok(format) = dotenvy::var("LOG_FORMAT") {
+            self.log.format = match format.as_str() {
+                "pretty" => LogFormat::Pretty,
+                "json" => LogFormat::Json,
+                _ => bail!("invalid value for LOG_FORMAT: {format}, expected pretty or json"),
+            };
+        }
+        if let Ok(endpoint) = dotenvy::var("OTEL_EXPORTER_OTLP_ENDPOINT") {
+            let endpoint = endpoint
+                .parse()
+                .with_context(|| format!("invalid value for OTEL_EXPORTER_OTLP_ENDPOINT: {endpoint}"))?;
+            self.log.otlp_endpoint = Some(endpoint);
+        }
+        env_override("OTEL_SERVICE_NAME", &mut self.log.service_name)?;
+        if let Ok(dir) = dotenvy::var("DEV_SOURCE_DIR") {
+            self.dev.source_dir = Some(dir.into());
+        }
+        env_override("DEV_RELOAD", &mut self.dev.reload)?;
+        Ok(())
+    }
+
+    /// Catches settings that would only fail once a request comes in.
+    pub fn validate(&self) -> anyhow::Result<()> {
+        let public_url = &self.server.public_url;
+        ensure!(
+            matches!(public_url.scheme(), "http" | "https") && public_url.host_str().is_some(),
+            "server.public_url must be an http or https URL, got {public_url}"
+        );
+        if self.server.secure_cookies
+            && public_url.scheme() == "http"
+            && public_url.host_str() != Some("localhost")
+        {
+            bail!("server.secure_cookies is set but {public_url} is not served over https");
+        }
+        ensure!(
+            (0..=11).contains(&self.server.compression_level),
+            "server.compression_level must be between 0 and 11, got {}",
+            self.server.compression_level
+        );
+        ensure!(!self.database.url.is_empty(), "database.url must be set");
+        let backend = if cfg!(feature = "postgres") { "postgres://" } else { "sqlite:" };
+        ensure!(
+            db::supports_url(&self.database.url),
+            "database.url must be a {backend} URL in this build, got {}",
+            self.database.url
+        );
+        ensure!(
+            self.database.max_connections > 0,
+            "database.max_connections must be positive"
+        );
+        ensure!(
+            self.database.min_connections <= self.database.max_connections,
+            "database.min_connections must not exceed database.max_connections"
+        );
+        if self.backup.dir.is_some() {
+            ensure!(
+                !cfg!(feature = "postgres"),
+                "backup.dir only works with SQLite, back up PostgreSQL with pg_dump"
+            );
+            ensure!(self.backup.interval_hours > 0, "backup.interval_hours must be positive");
+            ensure!(self.backup.keep > 0, "backup.keep must be positive");
+        }
+        ensure!(
+            self.session.inactivity_minutes > 0,
+            "session.inactivity_minutes must be positive"
+        );
+        ensure!(
+            self.session.remember_me_days > 0,
+            "session.remember_me_days must be positive"
+        );
+        ensure!(self.avatars.max_upload_kib > 0, "avatars.max_upload_kib must be positive");
+
+        let rp_id = self.rp_id();
+        let host = public_url.host_str().unwrap_or_default();
+        ensure!(
+            host == rp_id || host.ends_with(&format!(".{rp_id}")),
+            "webauthn.rp_id {rp_id} must be {host} or one of its parent domains"
+        );
+
+        if let Some(oidc) = &self.oidc {
+            Url::parse(&oidc.issuer_url).context("oidc.issuer_url is not a valid URL")?;
+            oidc.callback_url(public_url).context("oidc.redirect_url is not a valid URL")?;
+            ensure!(!oidc.client_id.is_empty(), "oidc.client_id must be set");
+        }
+
+        if let Some(endpoint) = &self.log.otlp_endpoint {
+            ensure!(
+                matches!(endpoint.scheme(), "http" | "https"),
+                "log.otlp_endpoint must be an http or https URL, got {endpoint}"
+            );
+        }
+
+        ensure!(
+            !self.dev.reload || self.dev.source_dir.is_some(),
+            "dev.reload needs dev.source_dir, embedded templates can't change"
+        );
+        if let Some(dir) = &self.dev.source_dir {
+            ensure!(
+                dir.join("templates").is_dir(),
+                "dev.source_dir {} has no templates directory",
+                dir.display()
+            );
+        }
+
+        Ok(())
+    }
+
+    pub fn rp_id(&self) -> &str {
+        self.webauthn
+            .rp_id
+            .as_deref()
+            .or_else(|| self.server.public_url.host_str())
+            .unwrap_or("localhost")
+    }
+
+    /// The `scheme://host:port` of the public URL, as sent in `Origin`.
+    pub fn public_origin(&self) -> String {
+        self.server.public_url.origin().ascii_serialization()
+    }
+}
+
+fn env_override<T>(name: &str, value: &mut T) -> anyhow::Result<()>
+where
+    T: FromStr,
+    T::Err: std::error::Error + Send + Sync + 'static,
+{
+    if let Ok(raw) = dotenvy::var(name) {
+        *value = raw
+            .parse()
+            .with_context(|| format!("invalid value for {name}: {raw}"))?;
+    }
+    Ok(())
+}
@@ -0,0 +1,88 @@
+use std::{
+    ffi::OsStr,
+    path::{Path, PathBuf},
+};
+
+use anyhow::Context;
+use include_dir::{include_dir, Dir};
+
+static TEMPLATES: Dir<'static> = include_dir!("$CARGO_MANIFEST_DIR/templates");
+static BUILD: Dir<'static> = include_dir!("$CARGO_MANIFEST_DIR/build");
+
+/// Where templates and built assets are loaded from. Release deployments use
+/// the copies embedded at compile time; during development `dev.source_dir`
+/// points at the `server` crate so edits show up without a rebuild. Tests
+/// hand in their own files with [`Source::Memory`].
+#[derive(Debug, Clone)]
+pub enum Source {
+    Embedded,
+    Disk(PathBuf),
+    Memory {
+        /// `(name, source)` pairs, like [`Source::templates`] returns.
+        templates: Vec<(String, String)>,
+        /// `(file name, contents)` pairs, like [`Source::assets`] returns.
+        assets: Vec<(String, Vec<u8>)>,
+    },
+}
+
+impl Source {
+    pub fn new(source_dir: Option<&Path>) -> Self {
+        match source_dir {
+            Some(dir) => Self::Disk(dir.to_path_buf()),
+            None => Self::Embedded,
+        }
+    }
+
+    /// Returns the `.html` templates as `(name, source)` pairs.
+    pub fn templates(&self) -> anyhow::Result<Vec<(String, String)>> {
+        match self {
+            Self::Embedded => TEMPLATES
+                .files()
+                .filter(|file| file.path().extension() == Some(OsStr::new("html")))
+                .map(|file| {
+                    let source = file
+                        .contents_utf8()
+                        .with_context(|| format!("template {} is not utf-8", file.path().display()))?;
+                    Ok((file_name(file.path())?, source.to_owned()))
+                })
+                .collect(),
+            Self::Disk(dir) => read_dir(&dir.join("templates"))?
+                .into_iter()
+                .filter(|path| path.extension() == Some(OsStr::new("html")))
+                .map(|path| Ok((file_name(&path)?, std::fs::read_to_string(&path)?)))
+                .collect(),
+            Self::Memory { templates, .. } => Ok(templates.clone()),
+        }
+    }
+
+    /// Returns the built assets as `(file name, contents)` pairs.
+    pub fn assets(&self) -> anyhow::Result<Vec<(String, Vec<u8>)>> {
+        match self {
+            Self::Embedded => BUILD
+                .files()
+                .map(|file| Ok((file_name(file.path())?, file.contents().to_vec())))
+                .collect(),
+            Self::Disk(dir) => read_dir(&dir.join("build"))?
+                .into_iter()
+                .map(|path| Ok((file_name(&path)?, std::fs::read(&path)?)))
+                .collect(),
+            Self::Memory { assets, .. } => Ok(assets.clone()),
+        }
+    }
+}
+
+fn read_dir(dir: &Path) -> anyhow::Result<Vec<PathBuf>> {
+    Ok(std::fs::read_dir(dir)
+        .with_context(|| format!("failed to read {}", dir.display()))?
+        .filter_map(Result::ok)
+        .map(|entry| entry.path())
+        .filter(|path| path.is_file())
+        .collect())
+}
+
+fn file_name(path: &Path) -> anyhow::Result<String> {
+    path.file_name()
+        .and_then(OsStr::to_str)
+        .map(str::to_owned)
+        .ok_or_else(|| anyhow::anyhow!("failed to get filename of {}", path.display()))
+}
@@ -0,0 +1,150 @@
+use std::{
+    path::PathBuf,
+    sync::{Arc, RwLock},
+    time::Duration,
+};
+
+use axum::{
+    extract::{Query, State},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+};
+use minijinja::Environment;
+use notify::{RecursiveMode, Watcher};
+use serde::Deserialize;
+use tokio_util::sync::CancellationToken;
+
+use crate::{
+    api_error::ApiError, asset_cache::AssetCache, base_template::BaseTemplateData,
+    embedded::Source, static_file_handler::import_templates, AppState,
+};
+
+/// Templates and built assets loaded from a [`Source`], along with the asset
+/// paths the layouts link to. Always replaced as a whole so a page never
+/// mixes templates and assets from different builds.
+pub struct Frontend {
+    /// Incremented on every reload, starting at 1.
+    pub generation: u64,
+    pub env: Environment<'static>,
+    pub assets: AssetCache,
+    pub base: BaseTemplateData,
+    pub quill: BaseTemplateData,
+    pub lexical: BaseTemplateData,
+}
+
+impl Frontend {
+    async fn load(source: &Source, generation: u64) -> anyhow::Result<Self> {
+        let env = import_templates(source)?;
+        let assets = AssetCache::load(source).await?;
+
+        Ok(Self {
+            generation,
+            base: BaseTemplateData::new(&assets, "index.css", "index.js")?,
+            quill: BaseTemplateData::new(&assets, "snow.css", "quill.js")?,
+            lexical: BaseTemplateData::new(&assets, "lexical.css", "lexical_editor.js")?,
+            env,
+            assets,
+        })
+    }
+}
+
+/// The current [`Frontend`], shared by all requests.
+#[derive(Clone)]
+pub struct SharedFrontend {
+    source: Source,
+    current: Arc<RwLock<Arc<Frontend>>>,
+}
+
+impl SharedFrontend {
+    pub async fn load(source: Source) -> anyhow::Result<Self> {
+        let frontend = Frontend::load(&source, 1).await?;
+        Ok(Self {
+            source,
+            current: Arc::new(RwLock::new(Arc::new(frontend))),
+        })
+    }
+
+    pub fn get(&self) -> Arc<Frontend> {
+        self.current.read().expect("frontend lock poisoned").clone()
+    }
+
+    /// Loads everything from the source again. On failure, e.g. a template
+    /// with a syntax error, the previous frontend stays in use.
+    pub async fn reload(&self) -> anyhow::Result<u64> {
+        let generation = self.get().generation + 1;
+        let frontend = Frontend::load(&self.source, generation).await?;
+        *self.current.write().expect("frontend lock poisoned") = Arc::new(frontend);
+        Ok(generation)
+    }
+}
+
+/// Watches the `templates` and `build` directories below `dir` and reloads
+/// the frontend when they change, until `shutdown` is cancelled. `build` is
+/// recreated by every build, so the whole crate directory is watched and
+/// events are filtered instead.
+pub async fn watch(
+    frontend: SharedFrontend,
+    dir: PathBuf,
+    shutdown: CancellationToken,
+) -> anyhow::Result<()> {
+    let dir = dir.canonicalize()?;
+    let templates = dir.join("templates");
+    let build = dir.join("build");
+
+    let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+    let mut watcher = notify::recommended_watcher(move |event: notify::Result<notify::Event>| {
+        let Ok(event) = event else { return };
+        let relevant = !event.kind.is_access()
+            && event
+                .paths
+                .iter()
+                .any(|path| path.starts_with(&templates) || path.starts_with(&build));
+        if relevant {
+            let _ = tx.send(());
+        }
+    })?;
+    // Dropping the watcher stops it, so it lives until this function returns.
+    watcher.watch(&dir, RecursiveMode::Recursive)?;
+
+    tracing::info!("watching {} for template and asset changes", dir.display());
+
+    loop {
+        tokio::select! {
+            event = rx.recv() => {
+                if event.is_none() {
+                    anyhow::bail!("file watcher stopped");
+                }
+            }
+            _ = shutdown.cancelled() => return Ok(()),
+        }
+
+        // Builds write many files at once, let them settle first.
+        tokio::time::sleep(Duration::from_millis(250)).await;
+        while rx.try_recv().is_ok() {}
+
+        match frontend.reload().await {
+            Ok(generation) => tracing::info!("reloaded templates and assets (generation {generation})"),
+            Err(e) => tracing::warn!("failed to reload templates and assets: {e:#}"),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ReloadQuery {
+    generation: u64,
+}
+
+/// Polled by pages rendered in dev mode. Tells htmx to refresh the page once
+/// the frontend it was rendered with has been replaced.
+pub async fn poll_reload(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<ReloadQuery>,
+) -> Response {
+    if !state.live_reload {
+        return ApiError::NotFound.into_response();
+    }
+    if state.frontend.get().generation == query.generation {
+        return StatusCode::NO_CONTENT.into_response();
+    }
+    (StatusCode::OK, [("hx-refresh", "true")]).into_response()
+}
@@ -0,0 +1,148 @@
+use std::{
+    sync::{Arc, Mutex},
+    time::Instant,
+};
+
+use axum::{
+    extract::{MatchedPath, Request, State},
+    http::{header::CONTENT_TYPE, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+    Json,
+};
+use db::{session::DbSession, sqlx};
+use metrics_exporter_prometheus::{Matcher, PrometheusBuilder, PrometheusHandle};
+use serde::Serialize;
+
+use crate::{auth::now, AppState};
+
+const REQUEST_DURATION: &str = "http_request_duration_seconds";
+
+/// Histogram buckets for request latencies, from 5ms to 10s.
+const DURATION_BUCKETS: &[f64] = &[
+    0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0,
+];
+
+static RECORDER: Mutex<Option<PrometheusHandle>> = Mutex::new(None);
+
+/// Installs the global Prometheus recorder on first use. The recorder is
+/// process wide, so every [`Server`](crate::Server) shares it; the lock keeps
+/// servers starting at the same time from both trying to install it.
+pub fn recorder() -> anyhow::Result<PrometheusHandle> {
+    let mut recorder = RECORDER.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+    if let Some(handle) = &*recorder {
+        return Ok(handle.clone());
+    }
+    let handle = PrometheusBuilder::new()
+        .set_buckets_for_metric(Matcher::Full(REQUEST_DURATION.into()), DURATION_BUCKETS)?
+        .install_recorder()?;
+    *recorder = Some(handle.clone());
+    Ok(handle)
+}
+
+/// Liveness probe: answers as long as the process is serving requests.
+pub async fn healthz() -> &'static str {
+    "ok"
+}
+
+#[derive(Debug, Serialize)]
+pub struct Readiness {
+    database: Check,
+    migrations: Check,
+    assets: Check,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(tag = "status", content = "error", rename_all = "lowercase")]
+enum Check {
+    Ok,
+    Failed(String),
+}
+
+impl Check {
+    fn is_ok(&self) -> bool {
+        matches!(self, Self::Ok)
+    }
+}
+
+/// Readiness probe: the database answers, every migration has been applied
+/// and the frontend has assets to serve. Responds with 503 and the failing
+/// checks otherwise.
+pub async fn readyz(State(state): State<Arc<AppState>>) -> Response {
+    let database = match sqlx::query("SELECT 1").execute(&state.db).await {
+        Ok(_) => Check::Ok,
+        Err(e) => Check::Failed(e.to_string()),
+    };
+
+    let migrations = match db::migration_status(&state.db).await {
+        Ok(status) => match status.iter().filter(|m| !m.applied).count() {
+            0 => Check::Ok,
+            pending if pending == status.len() => Check::Failed("not migrated".into()),
+            pending => Check::Failed(format!("{pending} pending migrations")),
+        },
+        Err(e) => Check::Failed(e.to_string()),
+    };
+
+    let assets = match state.frontend.get().assets.is_empty() {
+        false => Check::Ok,
+        true => Check::Failed("no assets loaded".into()),
+    };
+
+    let readiness = Readiness {
+        database,
+        migrations,
+        assets,
+    };
+    let status = if readiness.database.is_ok() && readiness.migrations.is_ok() && readiness.assets.is_ok() {
+        StatusCode::OK
+    } else {
+        StatusCode::SERVICE_UNAVAILABLE
+    };
+    (status, Json(readiness)).into_response()
+}
+
+/// Prometheus scrape endpoint. Pool and session gauges are sampled here,
+/// request metrics are recorded by [`track_requests`].
+pub async fn metrics(State(state): State<Arc<AppState>>) -> Response {
+    metrics::gauge!("db_pool_connections").set(state.db.size() as f64);
+    metrics::gauge!("db_pool_idle_connections").set(state.db.num_idle() as f64);
+
+    match DbSession::count_active(now(), &state.db).await {
+        Ok((active, authenticated)) => {
+            metrics::gauge!("sessions_active").set(active as f64);
+            metrics::gauge!("sessions_authenticated").set(authenticated as f64);
+        }
+        Err(e) => tracing::warn!("failed to count sessions for metrics: {e}"),
+    }
+
+    (
+        [(CONTENT_TYPE, "text/plain; version=0.0.4")],
+        state.metrics.render(),
+    )
+        .into_response()
+}
+
+/// Counts requests and records their latency, labelled by method, route
+/// pattern and status. Requests that match no route share the `unmatched`
+/// label so probing random paths can't blow up the number of series.
+pub async fn track_requests(request: Request, next: Next) -> Response {
+    let path = request
+        .extensions()
+        .get::<MatchedPath>()
+        .map(|path| path.as_str().to_owned())
+        .unwrap_or_else(|| "unmatched".into());
+    let method = request.method().to_string();
+    let start = Instant::now();
+
+    let response = next.run(request).await;
+
+    let labels = [
+        ("method", method),
+        ("path", path),
+        ("status", response.status().as_u16().to_string()),
+    ];
+    metrics::counter!("http_requests_total", &labels).increment(1);
+    metrics::histogram!(REQUEST_DURATION, &labels).record(start.elapsed().as_secs_f64());
+
+    response
+}
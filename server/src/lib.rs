@@ -1,63 +1,134 @@
+mod api;
 mod api_error;
+mod api_token;
 mod asset_cache;
 mod auth;
+mod avatar;
 mod base_template;
+pub mod config;
+pub mod embedded;
+mod frontend;
+mod health;
+mod oidc;
 mod routes;
+mod session_tracking;
 mod static_file_handler;
+mod supervisor;
+pub mod telemetry;
 use crate::{
     auth::Backend,
-    routes::{about, draft, index, lexical, login, logout, post_login, register},
+    routes::{
+        about, avatars, draft, index, lexical, login, logout, not_found, oidc as oidc_routes,
+        passkey, post_login, register, search, sessions, tokens,
+    },
 };
+pub use api::openapi;
 use api_error::ApiError;
-use asset_cache::{AssetCache, SharedAssetCache};
 use axum::{http::{
-    header::{ACCEPT, CONNECTION, CONTENT_TYPE}, HeaderName, HeaderValue, Method, StatusCode
-}, routing::post};
+    header::{ACCEPT, AUTHORIZATION, CONNECTION, CONTENT_TYPE}, HeaderName, HeaderValue, Method, StatusCode
+}, extract::DefaultBodyLimit, middleware, routing::post};
 use axum::{response::Html, routing::get, serve, Router};
 use axum_cc::CacheControlLayer;
 use axum_htmx::HxBoosted;
 use axum_login::{
-    tower_sessions::{ExpiredDeletion, Expiry, SessionManagerLayer},
+    tower_sessions::{ExpiredDeletion, Expiry, MemoryStore, SessionManagerLayer},
     AuthManagerLayerBuilder,
 };
 use axum_messages::MessagesManagerLayer;
-use base_template::{BaseTemplateData, SharedBaseTemplateData};
-use db::DbPool;
+use db::{repo::Repositories, DbPool, PoolSettings};
+use metrics_exporter_prometheus::PrometheusHandle;
 use minijinja::{context, Value};
-use static_file_handler::{import_templates, static_file_handler};
-use std::sync::Arc;
+use config::Config;
+use embedded::Source;
+use frontend::{Frontend, SharedFrontend};
+use oidc::Oidc;
+use static_file_handler::static_file_handler;
+use std::{future::IntoFuture, net::SocketAddr, sync::Arc};
 use std::time::Duration;
+use supervisor::{shutdown_signal, Supervisor};
 use tokio::net::TcpListener;
+use tokio_util::sync::CancellationToken;
 use tower_http::{
+    catch_panic::CatchPanicLayer,
     compression::{predicate::SizeAbove, CompressionLayer},
     cors::{Any, CorsLayer},
+    request_id::{MakeRequestUuid, PropagateRequestIdLayer, SetRequestIdLayer},
+    trace::TraceLayer,
     CompressionLevel,
 };
-use tower_sessions_sqlx_store::SqliteStore;
+#[cfg(feature = "postgres")]
+use tower_sessions_sqlx_store::PostgresStore as SessionStore;
+#[cfg(not(feature = "postgres"))]
+use tower_sessions_sqlx_store::SqliteStore as SessionStore;
+use url::Url;
+use webauthn_rs::{Webauthn, WebauthnBuilder};
 
 pub type BoxedError = Box<dyn std::error::Error>;
 
+/// Room for the multipart boundaries and headers around an avatar upload.
+const MULTIPART_OVERHEAD: usize = 16 * 1024;
+
 #[derive(Clone)]
 pub struct AppState {
     db: db::DbPool,
-    pub env: minijinja::Environment<'static>,
-    asset_cache: SharedAssetCache,
-    base_template_data: SharedBaseTemplateData,
-    quill_template_data: SharedBaseTemplateData,
-    lexical_template_data: SharedBaseTemplateData,
+    /// What handlers read and write through, see [`db::repo`]. `db` is only
+    /// used by the probes and background jobs.
+    repos: Repositories,
+    frontend: SharedFrontend,
+    /// Whether pages poll for frontend reloads, see [`frontend::poll_reload`].
+    live_reload: bool,
+    webauthn: Arc<Webauthn>,
+    oidc: Option<Arc<Oidc>>,
+    session_expiry: SessionExpiry,
+    /// `server.public_url`, which redirects have to stay on.
+    public_url: Url,
+    /// Largest avatar upload in bytes.
+    avatar_max_upload: usize,
+    metrics: PrometheusHandle,
 }
 
 impl AppState {
+    fn new(
+        config: &Config,
+        db: DbPool,
+        repos: Repositories,
+        frontend: SharedFrontend,
+        oidc: Option<Arc<Oidc>>,
+    ) -> anyhow::Result<Self> {
+        let webauthn = WebauthnBuilder::new(config.rp_id(), &config.server.public_url)?
+            .rp_name(&config.webauthn.rp_name)
+            .build()?;
+
+        Ok(Self {
+            db,
+            repos,
+            frontend,
+            live_reload: config.dev.reload,
+            webauthn: Arc::new(webauthn),
+            oidc,
+            session_expiry: SessionExpiry::from(&config.session),
+            public_url: config.server.public_url.clone(),
+            avatar_max_upload: config.avatars.max_upload_kib * 1024,
+            metrics: health::recorder()?,
+        })
+    }
+
     pub fn get_db(&self) -> db::DbPool {
         self.db.clone()
     }
 
+    /// The generation to poll for in `_base.html`, if live reload is on.
+    fn reload_generation(&self, frontend: &Frontend) -> Option<u64> {
+        self.live_reload.then_some(frontend.generation)
+    }
+
     pub fn render(
         &self,
         HxBoosted(boosted): HxBoosted,
         template: &str,
     ) -> Result<Html<String>, ApiError> {
-        let template = self
+        let frontend = self.frontend.get();
+        let template = frontend
             .env
             .get_template(template)
             .map_err(|_| ApiError::TemplateNotFound(template.into()))?;
@@ -70,7 +141,8 @@ impl AppState {
         }
 
         match template.render(context! {
-            base => Some(self.base_template_data )
+            base => Some(&frontend.base),
+            reload => self.reload_generation(&frontend),
         }) {
             Ok(rendered) => Ok(Html(rendered)),
             Err(_) => Err(ApiError::TemplateRender(template.name().into())),
@@ -83,7 +155,8 @@ impl AppState {
         template: &str,
         ctx: Value,
     ) -> Result<Html<String>, ApiError> {
-        let template = self
+        let frontend = self.frontend.get();
+        let template = frontend
             .env
             .get_template(template)
             .map_err(|_| ApiError::TemplateNotFound(template.into()))?;
@@ -97,7 +170,9 @@ impl AppState {
         }
 
         match template.render(context! {
-            base => Some(self.base_template_data), ..ctx
+            base => Some(&frontend.base),
+            reload => self.reload_generation(&frontend),
+            ..ctx
         }) {
             Ok(rendered) => Ok(Html(rendered)),
             Err(_) => Err(ApiError::TemplateRender(template.name().into())),
@@ -111,7 +186,8 @@ impl AppState {
         editor: Editor,
         ctx: Value,
     ) -> Result<Html<String>, ApiError> {
-        let template = self
+        let frontend = self.frontend.get();
+        let template = frontend
             .env
             .get_template(template)
             .map_err(|_| ApiError::TemplateNotFound(template.into()))?;
@@ -126,16 +202,18 @@ impl AppState {
 
         match editor {
             Editor::Quill => match template.render(context! {
-                base => Some(self.base_template_data), 
-                editor => Some(self.quill_template_data),
+                base => Some(&frontend.base),
+                editor => Some(&frontend.quill),
+                reload => self.reload_generation(&frontend),
                 ..ctx
             }) {
                 Ok(rendered) => Ok(Html(rendered)),
                 Err(_) => Err(ApiError::TemplateRender(template.name().into())),
             },
             Editor::Lexical => match template.render(context! {
-                base => Some(self.base_template_data), 
-                lexical => Some(self.lexical_template_data),
+                base => Some(&frontend.base),
+                lexical => Some(&frontend.lexical),
+                reload => self.reload_generation(&frontend),
                 ..ctx
             }) {
                 Ok(rendered) => Ok(Html(rendered)),
@@ -151,112 +229,359 @@ pub enum Editor {
     Lexical,
 }
 
+/// How long a session may be inactive before it expires.
+#[derive(Debug, Clone, Copy)]
+pub struct SessionExpiry {
+    /// Expiry of a regular login.
+    pub inactivity: time::Duration,
+    /// Expiry of a login with "remember me" checked.
+    pub remember_me: time::Duration,
+}
+
 pub struct Server {
-    pub session_store: SqliteStore,
+    pub config: Config,
+    pub session_store: SessionStore,
     pub listener: TcpListener,
     pub state: Arc<AppState>,
+    pub supervisor: Supervisor,
 }
 
 impl Server {
-    pub async fn new(listener: &str) -> anyhow::Result<Self> {
-        let db = db::pool().await?;
-        db::migrate(db.acquire().await.unwrap()).await.unwrap();
+    pub async fn new(config: Config) -> anyhow::Result<Self> {
+        let source = Source::new(config.dev.source_dir.as_deref());
+        Self::with_source(config, source).await
+    }
 
-        let session_store = SqliteStore::new(db.clone());
+    /// Like [`Server::new`], but loads templates and assets from `source`
+    /// instead of where `config.dev.source_dir` says.
+    pub async fn with_source(config: Config, source: Source) -> anyhow::Result<Self> {
+        let db = db::pool(&config.database.url, &PoolSettings::from(&config.database)).await?;
+        db::migrate(db.acquire().await?).await?;
+        avatar::resize_originals(&db).await?;
+
+        let session_store = SessionStore::new(db.clone());
         session_store.migrate().await?;
-        let asset_cache = AssetCache::load_static().await;
-        let listener = TcpListener::bind(listener).await?;
-        let env = import_templates()?;
-        let state = AppState {
-            db,
-            env,
-            asset_cache,
-            base_template_data: BaseTemplateData::load_static(asset_cache, "index.css", "index.js"),
-            quill_template_data: BaseTemplateData::load_static(asset_cache, "snow.css", "quill.js"),
-            lexical_template_data: BaseTemplateData::load_static(asset_cache, "lexical.css", "lexical_editor.js"),
-        };
+        let frontend = SharedFrontend::load(source).await?;
+
+        let mut supervisor = Supervisor::new();
+        supervisor.spawn("session cleanup", {
+            let session_store = session_store.clone();
+            let db = db.clone();
+            move |shutdown| delete_expired_sessions(session_store.clone(), db.clone(), shutdown)
+        });
+        #[cfg(not(feature = "postgres"))]
+        if config.database.optimize_interval_secs > 0 {
+            let db = db.clone();
+            let period = Duration::from_secs(config.database.optimize_interval_secs);
+            supervisor.spawn("database optimize", move |shutdown| {
+                optimize_database(db.clone(), period, shutdown)
+            });
+        }
+        #[cfg(not(feature = "postgres"))]
+        if let Some(dir) = &config.backup.dir {
+            let db = db.clone();
+            let dir = dir.clone();
+            let period = Duration::from_secs(config.backup.interval_hours * 3600);
+            let keep = config.backup.keep;
+            supervisor.spawn("database backup", move |shutdown| {
+                snapshot_database(db.clone(), dir.clone(), period, keep, shutdown)
+            });
+        }
+        if let (true, Some(dir)) = (config.dev.reload, &config.dev.source_dir) {
+            let frontend = frontend.clone();
+            let dir = dir.clone();
+            supervisor.spawn("frontend reload", move |shutdown| {
+                frontend::watch(frontend.clone(), dir.clone(), shutdown)
+            });
+        }
+        let listener = TcpListener::bind(config.server.listen).await?;
+
+        let repos = Repositories::sql(db.clone());
+        let oidc = discover_oidc(&config, &repos).await?;
+        let state = AppState::new(&config, db, repos, frontend, oidc)?;
 
         Ok(Self {
+            config,
             session_store,
             listener,
             state: Arc::new(state),
+            supervisor,
         })
     }
 
-    pub fn get_delete_task(
-        &self,
-    ) -> tokio::task::JoinHandle<Result<(), tower_sessions::session_store::Error>> {
-        tokio::task::spawn(
-            self.session_store
-                .clone()
-                .continuously_delete_expired(tokio::time::Duration::from_secs(60)),
+    /// Serves requests until Ctrl+C or SIGTERM. On shutdown the listener
+    /// stops accepting connections, in-flight requests get
+    /// `server.shutdown_timeout_secs` to finish, background jobs are stopped
+    /// and the database pool is closed.
+    pub async fn run(self) -> anyhow::Result<()> {
+        let router = self.router();
+        let Server {
+            config,
+            listener,
+            state,
+            supervisor,
+            ..
+        } = self;
+
+        tracing::debug!("listening on {}", listener.local_addr()?);
+
+        let shutdown = supervisor.token();
+        let mut server = tokio::spawn(
+            serve(listener, router.into_make_service_with_connect_info::<SocketAddr>())
+                .with_graceful_shutdown(shutdown.clone().cancelled_owned())
+                .into_future(),
+        );
+
+        let timeout = Duration::from_secs(config.server.shutdown_timeout_secs);
+        let result = tokio::select! {
+            result = &mut server => flatten(result),
+            _ = shutdown_signal() => {
+                tracing::info!("shutting down, waiting up to {timeout:?} for requests to finish");
+                shutdown.cancel();
+                match tokio::time::timeout(timeout, &mut server).await {
+                    Ok(result) => flatten(result),
+                    Err(_) => {
+                        tracing::warn!("requests still running after {timeout:?}, dropping them");
+                        server.abort();
+                        Ok(())
+                    }
+                }
+            }
+        };
+
+        supervisor.shutdown(timeout).await;
+        state.db.close().await;
+        tracing::info!("shutdown complete");
+        result
+    }
+
+    /// Every route with its middleware, as served by [`Server::run`].
+    pub fn router(&self) -> Router {
+        app(&self.config, self.state.clone(), self.session_store.clone())
+    }
+}
+
+/// Every route like [`Server::router`], but handlers go through `repos` and
+/// sessions are kept in memory, so they can be tested against
+/// `Repositories::memory` without a database. Nothing is migrated and no
+/// background jobs run. Only the readiness probe and metrics use
+/// `config.database.url`, connecting when first asked.
+pub async fn router_with_repositories(
+    config: Config,
+    source: Source,
+    repos: Repositories,
+) -> anyhow::Result<Router> {
+    let db = DbPool::connect_lazy(&config.database.url)?;
+    let frontend = SharedFrontend::load(source).await?;
+    let oidc = discover_oidc(&config, &repos).await?;
+    let state = AppState::new(&config, db, repos, frontend, oidc)?;
+    Ok(app(&config, Arc::new(state), MemoryStore::default()))
+}
+
+/// The single sign-on client, if `[oidc]` is configured.
+async fn discover_oidc(config: &Config, repos: &Repositories) -> anyhow::Result<Option<Arc<Oidc>>> {
+    let Some(oidc) = &config.oidc else {
+        return Ok(None);
+    };
+    let callback_url = oidc.callback_url(&config.server.public_url)?;
+    Ok(Some(Arc::new(Oidc::discover(oidc.clone(), callback_url, repos.clone()).await?)))
+}
+
+fn app<Store>(config: &Config, state: Arc<AppState>, session_store: Store) -> Router
+where
+    Store: axum_login::tower_sessions::SessionStore + Clone,
+{
+    let session_layer = SessionManagerLayer::new(session_store)
+        .with_secure(config.server.secure_cookies)
+        .with_expiry(Expiry::OnInactivity(state.session_expiry.inactivity));
+
+    let backend = Backend::new(state.repos.clone(), state.webauthn.clone(), state.oidc.clone());
+    let auth_layer = AuthManagerLayerBuilder::new(backend, session_layer).build();
+
+    let main_router = Router::new()
+        .route("/", get(index))
+        .route("/draft", post(draft))
+        .route("/about", get(about))
+        .route("/lexical", get(lexical))
+        .route("/remove", get(|| async {
+            (StatusCode::OK, "")
+        }))
+        .route("/login", get(login).post(post_login))
+        .route("/logout", get(logout))
+        .route("/register", get(register))
+        .route("/search", get(search::search))
+        .route("/sessions", get(sessions::sessions))
+        .route("/sessions/:id/revoke", post(sessions::revoke_session))
+        .route("/sessions/revoke-all", post(sessions::revoke_all_sessions))
+        .route("/admin/users/:user_id/sessions", get(sessions::admin_user_sessions))
+        .route(
+            "/admin/users/:user_id/sessions/revoke-all",
+            post(sessions::admin_revoke_all_sessions),
+        )
+        .route("/admin/sessions/:id/revoke", post(sessions::admin_revoke_session))
+        .route("/tokens", get(tokens::tokens).post(tokens::create_token))
+        .route("/tokens/:id/revoke", post(tokens::revoke_token))
+        .route("/profile", get(avatars::profile))
+        .route(
+            "/profile/avatar",
+            post(avatars::upload_avatar)
+                .layer(DefaultBodyLimit::max(state.avatar_max_upload + MULTIPART_OVERHEAD)),
+        )
+        .route("/profile/avatar/delete", post(avatars::delete_avatar))
+        .nest("/api/v1", api::router())
+        .route("/login/passkey/start", post(passkey::start_login))
+        .route("/login/passkey/finish", post(passkey::finish_login))
+        .route("/login/oidc", get(oidc_routes::start_login))
+        .route("/login/oidc/callback", get(oidc_routes::callback))
+        .route("/passkeys", get(passkey::passkeys))
+        .route("/passkeys/register/start", post(passkey::start_registration))
+        .route("/passkeys/register/finish", post(passkey::finish_registration))
+        .route("/passkeys/:id/delete", post(passkey::delete_passkey))
+        .route("/dev/reload", get(frontend::poll_reload))
+        .fallback(not_found)
+        .layer(middleware::from_fn_with_state(
+            state.clone(),
+            session_tracking::track_session,
+        ))
+        .layer(middleware::from_fn_with_state(
+            state.clone(),
+            api_token::bearer_auth,
+        ))
+        .layer(MessagesManagerLayer)
+        .layer(auth_layer)
+        .with_state(state.clone())
+        .layer(CacheControlLayer::new());
+
+    // Probes stay outside the session layers so they never create sessions.
+    let probes = Router::new()
+        .route("/healthz", get(health::healthz))
+        .route("/readyz", get(health::readyz))
+        .route("/metrics", get(health::metrics))
+        .with_state(state.clone());
+
+    // Avatars are public and cached by browsers, they don't need a session
+    // either.
+    let public = Router::new()
+        .route("/avatars/:user_id", get(avatars::avatar))
+        .with_state(state.clone());
+
+    let router = Router::new()
+        .merge(main_router)
+        .merge(probes)
+        .merge(public)
+        .nest("/assets", static_file_handler(state.clone()))
+        .layer(CatchPanicLayer::custom(api_error::panic_response))
+        .layer(middleware::from_fn_with_state(
+            state.clone(),
+            api_error::render_errors,
+        ))
+        .layer(middleware::from_fn(health::track_requests))
+        .layer(PropagateRequestIdLayer::new(telemetry::REQUEST_ID_HEADER))
+        .layer(
+            TraceLayer::new_for_http()
+                .make_span_with(telemetry::request_span)
+                .on_response(telemetry::record_response),
+        )
+        .layer(SetRequestIdLayer::new(
+            telemetry::REQUEST_ID_HEADER,
+            MakeRequestUuid,
+        ));
+
+    let allowed_origin = HeaderValue::from_str(&config.public_origin())
+        .expect("origin of a validated URL is a valid header value");
+    let compression_level = config.server.compression_level;
+
+    router
+        .layer(
+            CorsLayer::new()
+                .allow_credentials(true)
+                .allow_headers([
+                    ACCEPT,
+                    AUTHORIZATION,
+                    CONTENT_TYPE,
+                    CONNECTION,
+                    HeaderName::from_static("csrf-token"),
+                ])
+                .max_age(Duration::from_secs(86400))
+                .allow_origin(allowed_origin)
+                .allow_methods([
+                    Method::GET,
+                    Method::POST,
+                    Method::PUT,
+                    Method::DELETE,
+                    Method::OPTIONS,
+                    Method::HEAD,
+                    Method::PATCH,
+                    Method::CONNECT,
+                ]),
         )
+        .layer(
+            CompressionLayer::new()
+                .quality(CompressionLevel::Precise(compression_level))
+                .compress_when(SizeAbove::new(512)),
+        )
+}
+
+fn flatten(result: Result<std::io::Result<()>, tokio::task::JoinError>) -> anyhow::Result<()> {
+    Ok(result??)
+}
+
+/// Periodically removes expired sessions along with their metadata.
+async fn delete_expired_sessions(
+    session_store: SessionStore,
+    db: DbPool,
+    shutdown: CancellationToken,
+) -> anyhow::Result<()> {
+    let mut interval = tokio::time::interval(Duration::from_secs(60));
+    loop {
+        tokio::select! {
+            _ = interval.tick() => {}
+            _ = shutdown.cancelled() => return Ok(()),
+        }
+        session_store.delete_expired().await?;
+        // Drop the metadata of sessions the store just removed.
+        db::session::DbSession::delete_stale(&db).await?;
     }
+}
 
-    pub fn serve(self) -> tokio::task::JoinHandle<Result<(), std::io::Error>> {
-        tracing::debug!("listening on {}", self.listener.local_addr().unwrap());
-
-        let session_layer = SessionManagerLayer::new(self.session_store.clone())
-            .with_secure(false)
-            .with_expiry(Expiry::OnInactivity(time::Duration::hours(1)));
-
-        let backend = Backend::new(self.state.get_db());
-        let auth_layer = AuthManagerLayerBuilder::new(backend, session_layer).build();
-
-        let main_router = Router::new()
-            .route("/", get(index))
-            .route("/draft", post(draft))
-            .route("/about", get(about))
-            .route("/lexical", get(lexical))
-            .route("/remove", get(|| async {
-                (StatusCode::OK, "")
-            }))
-            .route("/login", get(login).post(post_login))
-            .route("/logout", get(logout))
-            .route("/register", get(register))
-            .layer(MessagesManagerLayer)
-            .layer(auth_layer)
-            .with_state(self.state.clone())
-            .layer(CacheControlLayer::new());
-
-        let router = Router::new()
-            .merge(main_router)
-            .nest("/assets", static_file_handler(self.state));
-
-        tokio::spawn(async move {
-            serve(
-                self.listener,
-                router
-                    .layer(
-                        CorsLayer::new()
-                            .allow_credentials(true)
-                            .allow_headers([
-                                ACCEPT,
-                                CONTENT_TYPE,
-                                CONNECTION,
-                                HeaderName::from_static("csrf-token"),
-                            ])
-                            .max_age(Duration::from_secs(86400))
-                            .allow_origin( "http://localhost:3000".parse::<HeaderValue>().unwrap(),)
-                            .allow_methods([
-                                Method::GET,
-                                Method::POST,
-                                Method::PUT,
-                                Method::DELETE,
-                                Method::OPTIONS,
-                                Method::HEAD,
-                                Method::PATCH,
-                                Method::CONNECT,
-                            ]),
-                    )
-                    .layer(
-                        CompressionLayer::new()
-                            .quality(CompressionLevel::Precise(4))
-                            .compress_when(SizeAbove::new(512)),
-                    )
-                    .into_make_service(),
-            )
-            .await
-        })
+/// Periodically lets SQLite tune itself and checkpoints the write-ahead log,
+/// see [`db::optimize`].
+#[cfg(not(feature = "postgres"))]
+async fn optimize_database(
+    db: DbPool,
+    period: Duration,
+    shutdown: CancellationToken,
+) -> anyhow::Result<()> {
+    let mut interval = tokio::time::interval(period);
+    // The first tick completes immediately, there is nothing to do yet.
+    interval.tick().await;
+    loop {
+        tokio::select! {
+            _ = interval.tick() => {}
+            _ = shutdown.cancelled() => return Ok(()),
+        }
+        db::optimize(&db).await?;
+    }
+}
+
+/// Periodically snapshots the database into `dir`, keeping the newest `keep`.
+#[cfg(not(feature = "postgres"))]
+async fn snapshot_database(
+    db: DbPool,
+    dir: std::path::PathBuf,
+    period: Duration,
+    keep: usize,
+    shutdown: CancellationToken,
+) -> anyhow::Result<()> {
+    let mut interval = tokio::time::interval(period);
+    // Don't snapshot on every restart.
+    interval.tick().await;
+    loop {
+        tokio::select! {
+            _ = interval.tick() => {}
+            _ = shutdown.cancelled() => return Ok(()),
+        }
+        let path = db::backup::snapshot(&db, &dir, keep).await?;
+        tracing::info!(path = %path.display(), "backed up the database");
     }
 }
@@ -0,0 +1,301 @@
+use std::collections::HashMap;
+
+use anyhow::Context;
+use db::{error::DbError, identity::IdentityLogin, repo::Repositories, user::DbUser};
+use openidconnect::{
+    core::{CoreAuthenticationFlow, CoreClient, CoreIdTokenClaims, CoreProviderMetadata},
+    reqwest::async_http_client,
+    AuthorizationCode, ClientId, ClientSecret, CsrfToken, IssuerUrl, Nonce, RedirectUrl, Scope,
+    TokenResponse,
+};
+use serde::{Deserialize, Serialize};
+use url::Url;
+
+use crate::auth::now;
+
+/// Settings for single sign-on through an OpenID Connect identity provider,
+/// the `[oidc]` table of the config file.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct OidcConfig {
+    /// Name under which linked identities are stored, e.g. `company-sso`.
+    #[serde(default = "default_provider")]
+    pub provider: String,
+    pub issuer_url: String,
+    pub client_id: String,
+    #[serde(default)]
+    pub client_secret: Option<String>,
+    /// Defaults to `/login/oidc/callback` under `server.public_url`.
+    #[serde(default)]
+    pub redirect_url: Option<String>,
+    #[serde(default = "default_scopes")]
+    pub scopes: Vec<String>,
+    /// Claim holding the user's groups at the identity provider.
+    #[serde(default = "default_groups_claim")]
+    pub groups_claim: String,
+    /// Maps identity provider groups to forum groups.
+    #[serde(default)]
+    pub group_map: HashMap<String, String>,
+}
+
+fn default_provider() -> String {
+    "oidc".into()
+}
+
+fn default_scopes() -> Vec<String> {
+    vec!["profile".into(), "email".into()]
+}
+
+fn default_groups_claim() -> String {
+    "groups".into()
+}
+
+impl OidcConfig {
+    /// Layers the `OIDC_*` environment variables over the settings from the
+    /// config file. Setting `OIDC_ISSUER_URL` enables SSO even without an
+    /// `[oidc]` table, in which case `OIDC_CLIENT_ID` is required too.
+    ///
+    /// `OIDC_GROUP_MAP` is a comma separated list of `idp-group=forum-group`
+    /// pairs.
+    pub fn apply_env(config: Option<Self>) -> anyhow::Result<Option<Self>> {
+        let mut config = match (config, dotenvy::var("OIDC_ISSUER_URL")) {
+            (Some(mut config), Ok(issuer_url)) => {
+                config.issuer_url = issuer_url;
+                config
+            }
+            (Some(config), Err(_)) => config,
+            (None, Ok(issuer_url)) => Self {
+                provider: default_provider(),
+                issuer_url,
+                client_id: dotenvy::var("OIDC_CLIENT_ID").context("OIDC_CLIENT_ID must be set")?,
+                client_secret: None,
+                redirect_url: None,
+                scopes: default_scopes(),
+                groups_claim: default_groups_claim(),
+                group_map: HashMap::new(),
+            },
+            (None, Err(_)) => return Ok(None),
+        };
+
+        if let Ok(provider) = dotenvy::var("OIDC_PROVIDER") {
+            config.provider = provider;
+        }
+        if let Ok(client_id) = dotenvy::var("OIDC_CLIENT_ID") {
+            config.client_id = client_id;
+        }
+        if let Ok(client_secret) = dotenvy::var("OIDC_CLIENT_SECRET") {
+            config.client_secret = Some(client_secret);
+        }
+        if let Ok(redirect_url) = dotenvy::var("OIDC_REDIRECT_URL") {
+            config.redirect_url = Some(redirect_url);
+        }
+        if let Ok(scopes) = dotenvy::var("OIDC_SCOPES") {
+            config.scopes = scopes.split_whitespace().map(str::to_owned).collect();
+        }
+        if let Ok(claim) = dotenvy::var("OIDC_GROUPS_CLAIM") {
+            config.groups_claim = claim;
+        }
+        if let Ok(map) = dotenvy::var("OIDC_GROUP_MAP") {
+            config.group_map = parse_group_map(&map)?;
+        }
+
+        Ok(Some(config))
+    }
+
+    /// Where the identity provider sends users back to: `redirect_url`, or
+    /// the callback route under `public_url`.
+    pub fn callback_url(&self, public_url: &Url) -> Result<Url, url::ParseError> {
+        match &self.redirect_url {
+            Some(url) => Url::parse(url),
+            None => public_url.join("/login/oidc/callback"),
+        }
+    }
+}
+
+pub fn parse_group_map(map: &str) -> anyhow::Result<HashMap<String, String>> {
+    map.split(',')
+        .map(str::trim)
+        .filter(|pair| !pair.is_empty())
+        .map(|pair| {
+            let (external, local) = pair
+                .split_once('=')
+                .with_context(|| format!("invalid group mapping \"{pair}\""))?;
+            Ok((external.trim().to_owned(), local.trim().to_owned()))
+        })
+        .collect()
+}
+
+/// The state of an authorization code flow that was started by redirecting
+/// the user to the identity provider. Stored in the session until the
+/// callback arrives.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OidcFlow {
+    pub csrf_state: CsrfToken,
+    pub nonce: Nonce,
+    pub next: Option<String>,
+}
+
+// Extracted from the query string of the identity provider's redirect.
+#[derive(Debug, Clone, Deserialize)]
+pub struct OidcCallback {
+    pub code: String,
+    pub state: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct OidcCredentials {
+    pub code: String,
+    pub nonce: Nonce,
+}
+
+/// An OIDC client discovered from the configured issuer.
+#[derive(Clone)]
+pub struct Oidc {
+    client: CoreClient,
+    config: OidcConfig,
+    /// Where linked identities live and users are provisioned.
+    repos: Repositories,
+}
+
+impl std::fmt::Debug for Oidc {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Oidc")
+            .field("client", &self.client)
+            .field("config", &self.config)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Oidc {
+    pub async fn discover(config: OidcConfig, callback_url: Url, repos: Repositories) -> anyhow::Result<Self> {
+        let issuer = IssuerUrl::new(config.issuer_url.clone())?;
+        let metadata = CoreProviderMetadata::discover_async(issuer, async_http_client)
+            .await
+            .context("failed to discover OIDC provider metadata")?;
+
+        let client = CoreClient::from_provider_metadata(
+            metadata,
+            ClientId::new(config.client_id.clone()),
+            config.client_secret.clone().map(ClientSecret::new),
+        )
+        .set_redirect_uri(RedirectUrl::from_url(callback_url));
+
+        Ok(Self { client, config, repos })
+    }
+
+    /// Returns the identity provider URL to send the user to, along with the
+    /// state needed to validate the callback.
+    pub fn authorize_url(&self, next: Option<String>) -> (String, OidcFlow) {
+        let mut request = self.client.authorize_url(
+            CoreAuthenticationFlow::AuthorizationCode,
+            CsrfToken::new_random,
+            Nonce::new_random,
+        );
+        for scope in &self.config.scopes {
+            request = request.add_scope(Scope::new(scope.clone()));
+        }
+        let (url, csrf_state, nonce) = request.url();
+
+        (
+            url.to_string(),
+            OidcFlow {
+                csrf_state,
+                nonce,
+                next,
+            },
+        )
+    }
+
+    /// Exchanges the authorization code, verifies the ID token and returns
+    /// the linked local user, provisioning one on first login.
+    pub async fn authenticate(&self, creds: OidcCredentials) -> Result<DbUser, DbError> {
+        let token_response = self
+            .client
+            .exchange_code(AuthorizationCode::new(creds.code))
+            .request_async(async_http_client)
+            .await
+            .map_err(|e| DbError::CredentialRejected(e.to_string()))?;
+
+        let id_token = token_response
+            .id_token()
+            .ok_or_else(|| DbError::CredentialRejected("no id token in response".into()))?;
+        let claims: &CoreIdTokenClaims = id_token
+            .claims(&self.client.id_token_verifier(), &creds.nonce)
+            .map_err(|e| DbError::CredentialRejected(e.to_string()))?;
+
+        let subject = claims.subject().as_str();
+        let preferred_username = claims
+            .preferred_username()
+            .map(|name| name.as_str().to_owned())
+            .or_else(|| claims.email().map(|email| email.as_str().to_owned()))
+            .unwrap_or_else(|| subject.to_owned());
+
+        // The signature was checked above, so the raw payload can be trusted
+        // for claims the core types don't model, like groups.
+        let groups = id_token_groups(&id_token.to_string(), &self.config.groups_claim);
+        let memberships = self.memberships(&groups);
+
+        let sign_in = self
+            .repos
+            .identities
+            .sign_in(&IdentityLogin {
+                provider: &self.config.provider,
+                subject,
+                preferred_username: &preferred_username,
+                memberships: &memberships,
+                created_at: now(),
+            })
+            .await?;
+
+        for group in &sign_in.unknown_groups {
+            tracing::warn!(group = %group, "OIDC group mapping refers to an unknown group");
+        }
+        if sign_in.created {
+            tracing::info!(
+                user_id = sign_in.user.id,
+                username = %sign_in.user.username,
+                provider = %self.config.provider,
+                subject,
+                "provisioned user"
+            );
+        }
+        Ok(sign_in.user)
+    }
+
+    /// Which mapped forum groups the user should be in, given the groups
+    /// asserted by the identity provider. A forum group is kept as long as
+    /// any identity provider group mapped to it is asserted; groups that are
+    /// not part of the mapping are left alone.
+    fn memberships(&self, external: &[String]) -> Vec<(String, bool)> {
+        let mut memberships: Vec<(String, bool)> = vec![];
+        for (external_group, local_group) in &self.config.group_map {
+            let member = external.contains(external_group);
+            match memberships.iter_mut().find(|(group, _)| group == local_group) {
+                Some((_, current)) => *current |= member,
+                None => memberships.push((local_group.clone(), member)),
+            }
+        }
+        memberships
+    }
+}
+
+/// Reads a list of group names from the given claim of a compact JWT. The
+/// claim may be a single string or an array of strings.
+fn id_token_groups(jwt: &str, claim: &str) -> Vec<String> {
+    use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+
+    let payload = jwt
+        .split('.')
+        .nth(1)
+        .and_then(|payload| URL_SAFE_NO_PAD.decode(payload.trim_end_matches('=')).ok())
+        .and_then(|payload| serde_json::from_slice::<serde_json::Value>(&payload).ok());
+
+    match payload.as_ref().and_then(|payload| payload.get(claim)) {
+        Some(serde_json::Value::String(group)) => vec![group.clone()],
+        Some(serde_json::Value::Array(groups)) => groups
+            .iter()
+            .filter_map(|group| group.as_str().map(str::to_owned))
+            .collect(),
+        _ => vec![],
+    }
+}
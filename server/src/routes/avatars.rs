@@ -0,0 +1,154 @@
+use std::sync::Arc;
+
+use axum::{
+    extract::{multipart::MultipartError, Multipart, Path, Query, State},
+    http::{
+        header::{CACHE_CONTROL, CONTENT_TYPE, ETAG, IF_NONE_MATCH},
+        HeaderMap, StatusCode,
+    },
+    response::{IntoResponse, Redirect, Response},
+};
+use axum_htmx::HxBoosted;
+use axum_login::AuthSession;
+use axum_messages::Messages;
+use minijinja::context;
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use tracing::info;
+
+use crate::{api_error::ApiError, auth::Backend, avatar, AppState};
+
+/// Browsers reuse an avatar for a few minutes, then revalidate it with its
+/// ETag.
+const AVATAR_CACHE_CONTROL: &str = "public, max-age=300";
+
+pub async fn profile(
+    auth_session: AuthSession<Backend>,
+    boosted: HxBoosted,
+    state: State<Arc<AppState>>,
+) -> Result<Response, ApiError> {
+    let Some(user) = auth_session.user else {
+        return Ok(Redirect::to("/login?next=/profile").into_response());
+    };
+
+    let has_avatar = state.repos.avatars.exists(user.0.id).await?;
+
+    Ok(state
+        .render_with_context(
+            boosted,
+            "profile.html",
+            context! {
+                user => user.0,
+                has_avatar,
+                max_upload_kib => state.avatar_max_upload / 1024,
+            },
+        )?
+        .into_response())
+}
+
+pub async fn upload_avatar(
+    auth_session: AuthSession<Backend>,
+    state: State<Arc<AppState>>,
+    messages: Messages,
+    mut multipart: Multipart,
+) -> Result<Redirect, ApiError> {
+    let user = auth_session.user.ok_or(ApiError::Unauthorized)?;
+
+    // Bodies over the limit of the route fail while the fields are read.
+    let upload_error = |err: MultipartError| match ApiError::from(err) {
+        ApiError::PayloadTooLarge(_) => too_large(state.avatar_max_upload),
+        err => err,
+    };
+
+    let mut upload = None;
+    while let Some(field) = multipart.next_field().await.map_err(upload_error)? {
+        if field.name() == Some("avatar") {
+            upload = Some(field.bytes().await.map_err(upload_error)?);
+        }
+    }
+    let upload = upload
+        .filter(|upload| !upload.is_empty())
+        .ok_or_else(|| ApiError::Validation("Choose an image to upload.".into()))?;
+    if upload.len() > state.avatar_max_upload {
+        return Err(too_large(state.avatar_max_upload));
+    }
+
+    let user_id = user.0.id;
+    let avatars = tokio::task::spawn_blocking(move || avatar::resize(user_id, &upload))
+        .await
+        .map_err(|e| ApiError::Internal(format!("avatar resizing failed: {e}")))??;
+    state.repos.avatars.replace(user_id, &avatars).await?;
+
+    info!(user_id, "avatar updated");
+    messages.success("Avatar updated.".to_string());
+    Ok(Redirect::to("/profile"))
+}
+
+fn too_large(max_upload: usize) -> ApiError {
+    ApiError::PayloadTooLarge(format!("Avatars can be at most {} KiB.", max_upload / 1024))
+}
+
+pub async fn delete_avatar(
+    auth_session: AuthSession<Backend>,
+    state: State<Arc<AppState>>,
+    messages: Messages,
+) -> Result<Redirect, ApiError> {
+    let user = auth_session.user.ok_or(ApiError::Unauthorized)?;
+
+    if state.repos.avatars.delete(user.0.id).await? {
+        messages.success("Avatar removed.".to_string());
+    }
+    Ok(Redirect::to("/profile"))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct AvatarQuery {
+    /// Width and height in pixels, rounded up to a stored size.
+    size: Option<u32>,
+}
+
+/// The avatar of a user, or their identicon if they haven't uploaded one.
+pub async fn avatar(
+    state: State<Arc<AppState>>,
+    Path(user_id): Path<i64>,
+    Query(query): Query<AvatarQuery>,
+    request_headers: HeaderMap,
+) -> Result<Response, ApiError> {
+    let size = avatar::standard_size(query.size);
+
+    let (content_type, body) = match state.repos.avatars.find(user_id, size.into()).await? {
+        Some(avatar) => (avatar.content_type, avatar.img_data),
+        None => {
+            state
+                .repos
+                .users
+                .find_by_id(user_id)
+                .await?
+                .ok_or(ApiError::NotFound)?;
+            (
+                "image/svg+xml".to_string(),
+                avatar::identicon(user_id, size).into_bytes(),
+            )
+        }
+    };
+
+    let etag = format!("\"{:x}\"", Sha256::digest(&body));
+    let matches = request_headers
+        .get(IF_NONE_MATCH)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| {
+            value
+                .split(',')
+                .map(|tag| tag.trim().trim_start_matches("W/"))
+                .any(|tag| tag == etag || tag == "*")
+        });
+
+    let headers = [
+        (ETAG, etag),
+        (CACHE_CONTROL, AVATAR_CACHE_CONTROL.to_string()),
+    ];
+    if matches {
+        return Ok((StatusCode::NOT_MODIFIED, headers).into_response());
+    }
+    Ok((headers, [(CONTENT_TYPE, content_type)], body).into_response())
+}
@@ -1,9 +1,16 @@
+pub mod avatars;
+pub mod oidc;
+pub mod passkey;
+pub mod search;
+pub mod sessions;
+pub mod tokens;
+
 use std::{sync::Arc, vec};
 
 use axum::{
-    extract::{Query, State},
+    extract::{Path, Query, State},
     http::StatusCode,
-    response::{IntoResponse, Redirect},
+    response::{IntoResponse, Redirect, Response},
     Form, Json,
 };
 use axum_htmx::HxBoosted;
@@ -11,10 +18,15 @@ use axum_login::AuthSession;
 use axum_messages::Messages;
 use minijinja::context;
 use serde::Deserialize;
+use tower_sessions::{Expiry, Session};
 use tracing::info;
+use url::{Position, Url};
 
 use crate::{
-    auth::{self, Backend, Credentials},
+    api_error::ApiError,
+    api_token::{CurrentUser, TokenScope},
+    auth::{self, Backend, Credentials, PasswordCredentials},
+    session_tracking::REMEMBER_ME_KEY,
     AppState,
 };
 
@@ -25,6 +37,21 @@ pub struct NextUrl {
     next: Option<String>,
 }
 
+/// Where to send the user after logging in: `next` if it is a URL on this
+/// site, so login links can't be used to redirect elsewhere. `next` is
+/// resolved against `public_url` the way a browser would resolve it, which
+/// also catches `//host`, `/\host` and control characters browsers strip.
+pub(crate) fn local_redirect(public_url: &Url, next: Option<&str>) -> String {
+    let local = next
+        .filter(|next| !next.contains(|c: char| c.is_ascii_control() || c == '\\'))
+        .and_then(|next| public_url.join(next).ok())
+        .filter(|url| url.origin() == public_url.origin());
+    match local {
+        Some(url) => url[Position::BeforePath..].to_string(),
+        None => "/".to_string(),
+    }
+}
+
 pub async fn login(
     boosted: HxBoosted,
     state: State<Arc<AppState>>,
@@ -34,18 +61,16 @@ pub async fn login(
         boosted,
         "login.html",
         context! {
-            next
+            next,
+            sso => state.oidc.is_some(),
         },
     )
 }
 
-pub async fn logout(mut auth_session: AuthSession<Backend>, messages: Messages) -> impl IntoResponse {
-    match auth_session.logout().await {
-        Ok(_) => {
-            messages.success("Successfully logged out.".to_string());
-            Redirect::to("/").into_response()},
-        Err(_) => StatusCode::INTERNAL_SERVER_ERROR.into_response(),
-    }
+pub async fn logout(mut auth_session: AuthSession<Backend>, messages: Messages) -> Result<Redirect, ApiError> {
+    auth_session.logout().await?;
+    messages.success("Successfully logged out.".to_string());
+    Ok(Redirect::to("/"))
 }
 
 pub async fn register(
@@ -67,30 +92,43 @@ pub struct Draft {
 pub async fn draft(
     boosted: HxBoosted,
     state: State<Arc<AppState>>,
+    current: CurrentUser,
     draft: Json<Draft>, 
-) -> impl IntoResponse {
-    let db = state.db.clone();
-    let article = db::article::Article::upsert(1, draft.title.clone(), draft.content.clone(), "content".to_string(), &db).await.unwrap();
+) -> Result<impl IntoResponse, ApiError> {
+    if !current.allows(TokenScope::Write) {
+        return Err(ApiError::Forbidden);
+    }
 
-    info!("Article updated: {:?}", article.get_id());
-    (StatusCode::OK, "Draft")
+    let article = state
+        .repos
+        .articles
+        .upsert(current.user.0.id, draft.title.clone(), draft.content.clone(), "content".to_string())
+        .await?;
+
+    info!(article_id = article.get_id(), "article updated");
+    Ok((StatusCode::OK, "Draft"))
 }
 
 pub async fn get_draft(
     boosted: HxBoosted,
     state: State<Arc<AppState>>,
     path: Path<i64>,
-) -> impl IntoResponse {
+) -> Result<impl IntoResponse, ApiError> {
 
-    let article = db::article::Article::find_by_id(path.0, &state.db).await.unwrap();
-    state.render_with_editor(
+    let article = state
+        .repos
+        .articles
+        .find_by_id(path.0)
+        .await?
+        .ok_or(ApiError::NotFound)?;
+    Ok(state.render_with_editor(
         boosted,
         "draft.html",
         crate::Editor::Quill,
         context! {
             article => article,
         },
-    )
+    )?)
 
 }
 
@@ -99,74 +137,78 @@ pub async fn post_login(
     state: State<Arc<AppState>>,
     messages: Messages,
     mut auth_session: AuthSession<Backend>,
-    Form(creds): Form<Credentials>,
-) -> impl IntoResponse {
-    let user = match auth_session.authenticate(creds.clone()).await {
+    session: Session,
+    Form(creds): Form<PasswordCredentials>,
+) -> Result<Response, ApiError> {
+    let user = match auth_session.authenticate(Credentials::Password(creds.clone())).await {
         Ok(Some(user)) => user,
         Ok(None) => {
-            return state
+            return Ok(state
                 .render_with_context(
                     boosted,
                     "login.html",
                     context! {
                         message => Some("Invalid credentials.".to_string()),
                         next => creds.next,
+                        sso => state.oidc.is_some(),
                     },
-                )
-                .into_response()
+                )?
+                .into_response())
         }
         Err(e) => {
             match e {
                 axum_login::Error::Backend(e) => {
                     match e {
                         db::error::DbError::UserNotFound => {
-                            return state
+                            return Ok(state
                                 .render_with_context(
                                     boosted,
                                     "login.html",
                                     context! {
                                         username_error => Some("Username does not exist".to_string()),
                                         next => creds.next,
+                                        sso => state.oidc.is_some(),
                                     },
-                                )
-                                .into_response()
+                                )?
+                                .into_response())
                         }
                         db::error::DbError::PasswordIncorrect => {
-                            return state
+                            return Ok(state
                                 .render_with_context(
                                     boosted,
                                     "login.html",
                                     context! {
                                         password_error => Some("Invalid password".to_string()),
                                         next => creds.next,
+                                        sso => state.oidc.is_some(),
                                     },
-                                )
-                                .into_response()
-                        }
-                        _ => {
-                            return StatusCode::INTERNAL_SERVER_ERROR.into_response()
+                                )?
+                                .into_response())
                         }
+                        e => return Err(e.into()),
                     }
                 }
-                axum_login::Error::Session(_) => {
-                    return StatusCode::INTERNAL_SERVER_ERROR.into_response()
-                }
+                e => return Err(e.into()),
             }
 
         }
     };
 
-    if auth_session.login(&user).await.is_err() {
-        return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+    auth_session.login(&user).await?;
+
+    if creds.remember_me.is_some() {
+        session.insert(REMEMBER_ME_KEY, true).await?;
+        session.set_expiry(Some(Expiry::OnInactivity(state.session_expiry.remember_me)));
     }
 
     messages.success(format!("Successfully logged in as {}", user.0.username));
 
-    if let Some(ref next) = creds.next {
-        Redirect::to(next).into_response()
-    } else {
-        Redirect::to("/").into_response()
-    }
+    Ok(Redirect::to(&local_redirect(&state.public_url, creds.next.as_deref())).into_response())
+}
+
+/// Fallback for every path no route matches.
+pub async fn not_found() -> ApiError {
+    ApiError::NotFound
 }
 
 pub async fn index(auth_session: AuthSession<Backend>, boosted: HxBoosted, state: State<Arc<AppState>>, messages: Messages) -> impl IntoResponse {
@@ -0,0 +1,74 @@
+use std::sync::Arc;
+
+use axum::{
+    extract::{Query, State},
+    response::Redirect,
+};
+use axum_login::AuthSession;
+use axum_messages::Messages;
+use tower_sessions::Session;
+use tracing::warn;
+
+use crate::{
+    api_error::ApiError,
+    auth::{Backend, Credentials},
+    oidc::{OidcCallback, OidcCredentials, OidcFlow},
+    AppState,
+};
+
+use super::{local_redirect, NextUrl};
+
+const FLOW_STATE_KEY: &str = "oidc.flow";
+
+pub async fn start_login(
+    session: Session,
+    state: State<Arc<AppState>>,
+    Query(NextUrl { next }): Query<NextUrl>,
+) -> Result<Redirect, ApiError> {
+    let oidc = state.oidc.as_ref().ok_or(ApiError::NotFound)?;
+
+    let (url, flow) = oidc.authorize_url(next);
+    session.insert(FLOW_STATE_KEY, flow).await?;
+
+    Ok(Redirect::to(&url))
+}
+
+pub async fn callback(
+    state: State<Arc<AppState>>,
+    mut auth_session: AuthSession<Backend>,
+    session: Session,
+    messages: Messages,
+    Query(callback): Query<OidcCallback>,
+) -> Result<Redirect, ApiError> {
+    let flow = session
+        .remove::<OidcFlow>(FLOW_STATE_KEY)
+        .await?
+        .ok_or_else(|| ApiError::Validation("No single sign-on login in progress.".into()))?;
+
+    if flow.csrf_state.secret() != &callback.state {
+        warn!("OIDC callback state mismatch");
+        return Err(ApiError::Validation("Single sign-on state mismatch.".into()));
+    }
+
+    let creds = Credentials::Oidc(OidcCredentials {
+        code: callback.code,
+        nonce: flow.nonce,
+    });
+
+    let user = match auth_session.authenticate(creds).await {
+        Ok(Some(user)) => user,
+        Ok(None) => return Err(ApiError::Unauthorized),
+        Err(axum_login::Error::Backend(db::error::DbError::CredentialRejected(reason))) => {
+            warn!("OIDC login rejected: {reason}");
+            messages.error("Single sign-on failed.".to_string());
+            return Ok(Redirect::to("/login"));
+        }
+        Err(e) => return Err(e.into()),
+    };
+
+    auth_session.login(&user).await?;
+
+    messages.success(format!("Successfully logged in as {}", user.0.username));
+
+    Ok(Redirect::to(&local_redirect(&state.public_url, flow.next.as_deref())))
+}
@@ -0,0 +1,192 @@
+use std::sync::Arc;
+
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::{IntoResponse, Redirect, Response},
+    Json,
+};
+use axum_htmx::HxBoosted;
+use axum_login::AuthSession;
+use axum_messages::Messages;
+use minijinja::context;
+use serde::Deserialize;
+use tower_sessions::Session;
+use tracing::{info, warn};
+use webauthn_rs::prelude::{
+    DiscoverableAuthentication, PasskeyRegistration, PublicKeyCredential,
+    RegisterPublicKeyCredential,
+};
+
+use crate::{
+    api_error::ApiError,
+    auth::{encode_credential_id, now, webauthn_user_id, Backend, Credentials, PasskeyCredentials},
+    AppState,
+};
+
+const REGISTRATION_STATE_KEY: &str = "passkey.registration";
+const AUTHENTICATION_STATE_KEY: &str = "passkey.authentication";
+
+pub async fn passkeys(
+    auth_session: AuthSession<Backend>,
+    boosted: HxBoosted,
+    state: State<Arc<AppState>>,
+) -> Result<Response, ApiError> {
+    let Some(user) = auth_session.user else {
+        return Ok(Redirect::to("/login?next=/passkeys").into_response());
+    };
+
+    let passkeys = state.repos.passkeys.find_by_user(user.0.id).await?;
+
+    Ok(state
+        .render_with_context(
+            boosted,
+            "passkeys.html",
+            context! {
+                user => user.0,
+                passkeys,
+            },
+        )?
+        .into_response())
+}
+
+pub async fn start_registration(
+    auth_session: AuthSession<Backend>,
+    session: Session,
+    state: State<Arc<AppState>>,
+) -> Result<Response, ApiError> {
+    let user = auth_session.user.ok_or(ApiError::Unauthorized)?;
+
+    // Don't let the authenticator register a credential it already holds for
+    // this account.
+    let existing = state
+        .repos
+        .passkeys
+        .find_by_user(user.0.id)
+        .await?
+        .into_iter()
+        .filter_map(|p| serde_json::from_str::<webauthn_rs::prelude::Passkey>(&p.passkey).ok())
+        .map(|p| p.cred_id().clone())
+        .collect::<Vec<_>>();
+
+    let (challenge, registration) = state
+        .webauthn
+        .start_passkey_registration(
+            webauthn_user_id(user.0.id),
+            &user.0.username,
+            &user.0.username,
+            Some(existing),
+        )
+        .map_err(|e| ApiError::Internal(format!("failed to start passkey registration: {e}")))?;
+
+    session.insert(REGISTRATION_STATE_KEY, registration).await?;
+
+    Ok(Json(challenge).into_response())
+}
+
+#[derive(Debug, Deserialize)]
+pub struct FinishRegistration {
+    name: String,
+    credential: RegisterPublicKeyCredential,
+}
+
+pub async fn finish_registration(
+    auth_session: AuthSession<Backend>,
+    session: Session,
+    state: State<Arc<AppState>>,
+    Json(body): Json<FinishRegistration>,
+) -> Result<StatusCode, ApiError> {
+    let user = auth_session.user.ok_or(ApiError::Unauthorized)?;
+
+    let registration = session
+        .remove::<PasskeyRegistration>(REGISTRATION_STATE_KEY)
+        .await?
+        .ok_or_else(|| ApiError::Validation("No passkey registration in progress.".into()))?;
+
+    let passkey = state
+        .webauthn
+        .finish_passkey_registration(&body.credential, &registration)
+        .map_err(|e| {
+            warn!("passkey registration rejected: {e}");
+            ApiError::Validation("The passkey could not be verified.".into())
+        })?;
+
+    let serialized = serde_json::to_string(&passkey)?;
+
+    let name = match body.name.trim() {
+        "" => "Passkey".to_string(),
+        name => name.to_string(),
+    };
+
+    let passkey = state
+        .repos
+        .passkeys
+        .create(user.0.id, passkey.cred_id().to_string(), name, serialized, now())
+        .await?;
+
+    info!(passkey_id = passkey.id, "passkey registered");
+    Ok(StatusCode::CREATED)
+}
+
+pub async fn delete_passkey(
+    auth_session: AuthSession<Backend>,
+    state: State<Arc<AppState>>,
+    messages: Messages,
+    Path(id): Path<i64>,
+) -> Result<Redirect, ApiError> {
+    let user = auth_session.user.ok_or(ApiError::Unauthorized)?;
+
+    if !state.repos.passkeys.delete(id, user.0.id).await? {
+        return Err(ApiError::NotFound);
+    }
+
+    messages.success("Passkey removed.".to_string());
+    Ok(Redirect::to("/passkeys"))
+}
+
+pub async fn start_login(
+    session: Session,
+    state: State<Arc<AppState>>,
+) -> Result<Response, ApiError> {
+    let (challenge, authentication) = state
+        .webauthn
+        .start_discoverable_authentication()
+        .map_err(|e| ApiError::Internal(format!("failed to start passkey login: {e}")))?;
+
+    session.insert(AUTHENTICATION_STATE_KEY, authentication).await?;
+
+    Ok(Json(challenge).into_response())
+}
+
+pub async fn finish_login(
+    mut auth_session: AuthSession<Backend>,
+    session: Session,
+    messages: Messages,
+    Json(response): Json<PublicKeyCredential>,
+) -> Result<StatusCode, ApiError> {
+    let authentication = session
+        .remove::<DiscoverableAuthentication>(AUTHENTICATION_STATE_KEY)
+        .await?
+        .ok_or_else(|| ApiError::Validation("No passkey login in progress.".into()))?;
+
+    let creds = Credentials::Passkey(PasskeyCredentials {
+        state: authentication,
+        response,
+    });
+
+    let user = match auth_session.authenticate(creds).await {
+        Ok(Some(user)) => user,
+        Ok(None) => return Err(ApiError::Unauthorized),
+        Err(axum_login::Error::Backend(db::error::DbError::CredentialRejected(reason))) => {
+            warn!("passkey login rejected: {reason}");
+            return Err(ApiError::Unauthorized);
+        }
+        Err(e) => return Err(e.into()),
+    };
+
+    auth_session.login(&user).await?;
+
+    messages.success(format!("Successfully logged in as {}", user.0.username));
+
+    Ok(StatusCode::NO_CONTENT)
+}
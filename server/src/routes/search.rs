@@ -0,0 +1,143 @@
+use std::sync::Arc;
+
+use axum::{
+    extract::{Query, State},
+    response::{IntoResponse, Response},
+};
+use axum_htmx::HxBoosted;
+use axum_login::AuthSession;
+use db::search::{SearchFilter, SearchHit, HIGHLIGHT_END, HIGHLIGHT_START};
+use minijinja::{context, Value};
+use serde::{Deserialize, Serialize};
+use url::form_urlencoded;
+
+use crate::{api_error::ApiError, auth::Backend, AppState};
+
+const PER_PAGE: i64 = 20;
+
+/// The search form. Empty fields are sent as empty strings, so the filters
+/// are parsed by hand rather than as numbers.
+#[derive(Debug, Default, Deserialize)]
+pub struct SearchParams {
+    #[serde(default)]
+    q: String,
+    /// Username of the author.
+    #[serde(default)]
+    author: String,
+    #[serde(default)]
+    category: String,
+    page: Option<i64>,
+}
+
+impl SearchParams {
+    /// Capped so the offset of the last page still fits an `i64`.
+    fn page(&self) -> i64 {
+        self.page.unwrap_or(1).clamp(1, i64::MAX / PER_PAGE)
+    }
+
+    fn category_id(&self) -> Option<i64> {
+        self.category.parse().ok()
+    }
+
+    /// Link to another page of the same search.
+    fn page_url(&self, page: i64) -> String {
+        let query = form_urlencoded::Serializer::new(String::new())
+            .append_pair("q", &self.q)
+            .append_pair("author", &self.author)
+            .append_pair("category", &self.category)
+            .append_pair("page", &page.to_string())
+            .finish();
+        format!("/search?{query}")
+    }
+}
+
+/// A search result as shown on the page. There are no pages for single
+/// threads, posts or articles yet, so results don't link anywhere.
+#[derive(Debug, Serialize)]
+struct Hit {
+    kind: String,
+    username: Option<String>,
+    title: Value,
+    snippet: Value,
+}
+
+impl From<SearchHit> for Hit {
+    fn from(hit: SearchHit) -> Self {
+        Self {
+            kind: hit.kind,
+            username: hit.username,
+            title: Value::from_safe_string(highlight(&hit.title)),
+            snippet: Value::from_safe_string(highlight(&hit.snippet)),
+        }
+    }
+}
+
+/// Escapes `text` for HTML and turns the highlight markers set by the
+/// database into `<mark>` elements.
+fn highlight(text: &str) -> String {
+    let mut html = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            HIGHLIGHT_START => html.push_str("<mark>"),
+            HIGHLIGHT_END => html.push_str("</mark>"),
+            '&' => html.push_str("&amp;"),
+            '<' => html.push_str("&lt;"),
+            '>' => html.push_str("&gt;"),
+            '"' => html.push_str("&quot;"),
+            '\'' => html.push_str("&#x27;"),
+            c => html.push(c),
+        }
+    }
+    html
+}
+
+pub async fn search(
+    auth_session: AuthSession<Backend>,
+    boosted: HxBoosted,
+    state: State<Arc<AppState>>,
+    Query(params): Query<SearchParams>,
+) -> Result<Response, ApiError> {
+    let categories = state.repos.categories.list(100, 0).await?;
+
+    let author = match params.author.trim() {
+        "" => None,
+        username => Some(state.repos.users.find_by_username(username).await?),
+    };
+
+    let page = params.page();
+    let (hits, total) = match author {
+        // Nobody by that name, so nothing can match.
+        Some(None) => (Vec::new(), 0),
+        author => {
+            let filter = SearchFilter {
+                user_id: author.flatten().map(|user| user.id),
+                category_id: params.category_id(),
+            };
+            let offset = (page - 1) * PER_PAGE;
+            let hits = state.repos.search.search(&params.q, &filter, PER_PAGE, offset).await?;
+            let total = state.repos.search.count(&params.q, &filter).await?;
+            (hits, total)
+        }
+    };
+    let total_pages = (total + PER_PAGE - 1) / PER_PAGE;
+
+    Ok(state
+        .render_with_context(
+            boosted,
+            "search.html",
+            context! {
+                user => auth_session.user.map(|user| user.0),
+                q => &params.q,
+                author => &params.author,
+                category_id => params.category_id(),
+                categories,
+                hits => hits.into_iter().map(Hit::from).collect::<Vec<_>>(),
+                total,
+                page,
+                total_pages,
+                prev_url => (page > 1).then(|| params.page_url(page - 1)),
+                next_url => (page < total_pages).then(|| params.page_url(page + 1)),
+            },
+        )?
+        .into_response())
+}
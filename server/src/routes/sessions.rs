@@ -0,0 +1,181 @@
+use std::sync::Arc;
+
+use axum::{
+    extract::{Path, State},
+    response::{IntoResponse, Redirect, Response},
+};
+use axum_htmx::HxBoosted;
+use axum_login::{AuthSession, AuthzBackend};
+use axum_messages::Messages;
+use db::user::{DbPermission, DbUser};
+use minijinja::context;
+use tower_sessions::Session;
+
+use crate::{
+    api_error::ApiError,
+    auth::{now, Backend, User},
+    AppState,
+};
+
+const MANAGE_SESSIONS: &str = "sessions.manage";
+
+async fn can_manage_sessions(auth_session: &AuthSession<Backend>, user: &User) -> bool {
+    auth_session
+        .backend
+        .has_perm(user, DbPermission::from(MANAGE_SESSIONS))
+        .await
+        .unwrap_or(false)
+}
+
+async fn render_sessions(
+    state: &AppState,
+    boosted: HxBoosted,
+    user: &User,
+    owner: DbUser,
+    session: &Session,
+    admin: bool,
+) -> Result<Response, ApiError> {
+    let sessions = state.repos.sessions.find_active_by_user(owner.id, now()).await?;
+
+    let current_session_id = session.id().map(|id| id.to_string());
+    let current = sessions
+        .iter()
+        .find(|s| Some(&s.session_id) == current_session_id.as_ref())
+        .map(|s| s.id);
+
+    Ok(state
+        .render_with_context(
+            boosted,
+            "sessions.html",
+            context! {
+                user => user.0.clone(),
+                owner,
+                sessions,
+                current,
+                admin,
+            },
+        )?
+        .into_response())
+}
+
+pub async fn sessions(
+    auth_session: AuthSession<Backend>,
+    session: Session,
+    boosted: HxBoosted,
+    state: State<Arc<AppState>>,
+) -> Result<Response, ApiError> {
+    let Some(user) = auth_session.user.clone() else {
+        return Ok(Redirect::to("/login?next=/sessions").into_response());
+    };
+
+    render_sessions(&state, boosted, &user, user.0.clone(), &session, false).await
+}
+
+pub async fn revoke_session(
+    mut auth_session: AuthSession<Backend>,
+    session: Session,
+    state: State<Arc<AppState>>,
+    messages: Messages,
+    Path(id): Path<i64>,
+) -> Result<Response, ApiError> {
+    let user = auth_session.user.clone().ok_or(ApiError::Unauthorized)?;
+
+    let target = state
+        .repos
+        .sessions
+        .find_by_id(id)
+        .await?
+        .filter(|target| target.user_id == user.0.id)
+        .ok_or(ApiError::NotFound)?;
+
+    // Revoking the session we're using is just a logout.
+    if session.id().map(|id| id.to_string()).as_ref() == Some(&target.session_id) {
+        auth_session.logout().await?;
+        return Ok(Redirect::to("/").into_response());
+    }
+
+    state.repos.sessions.revoke(&target).await?;
+
+    messages.success("Logged out of the selected device.".to_string());
+    Ok(Redirect::to("/sessions").into_response())
+}
+
+pub async fn revoke_all_sessions(
+    mut auth_session: AuthSession<Backend>,
+    state: State<Arc<AppState>>,
+    messages: Messages,
+) -> Result<Response, ApiError> {
+    let user = auth_session.user.clone().ok_or(ApiError::Unauthorized)?;
+
+    state.repos.sessions.revoke_all_for_user(user.0.id).await?;
+    auth_session.logout().await?;
+
+    messages.success("Logged out everywhere.".to_string());
+    Ok(Redirect::to("/").into_response())
+}
+
+pub async fn admin_user_sessions(
+    auth_session: AuthSession<Backend>,
+    session: Session,
+    boosted: HxBoosted,
+    state: State<Arc<AppState>>,
+    Path(user_id): Path<i64>,
+) -> Result<Response, ApiError> {
+    let user = auth_session.user.clone().ok_or(ApiError::Unauthorized)?;
+
+    if !can_manage_sessions(&auth_session, &user).await {
+        return Err(ApiError::Forbidden);
+    }
+
+    let owner = state
+        .repos
+        .users
+        .find_by_id(user_id)
+        .await?
+        .ok_or(ApiError::NotFound)?;
+
+    render_sessions(&state, boosted, &user, owner, &session, true).await
+}
+
+pub async fn admin_revoke_session(
+    auth_session: AuthSession<Backend>,
+    state: State<Arc<AppState>>,
+    messages: Messages,
+    Path(id): Path<i64>,
+) -> Result<Response, ApiError> {
+    let user = auth_session.user.clone().ok_or(ApiError::Unauthorized)?;
+
+    if !can_manage_sessions(&auth_session, &user).await {
+        return Err(ApiError::Forbidden);
+    }
+
+    let target = state
+        .repos
+        .sessions
+        .find_by_id(id)
+        .await?
+        .ok_or(ApiError::NotFound)?;
+
+    state.repos.sessions.revoke(&target).await?;
+
+    messages.success("Session revoked.".to_string());
+    Ok(Redirect::to(&format!("/admin/users/{}/sessions", target.user_id)).into_response())
+}
+
+pub async fn admin_revoke_all_sessions(
+    auth_session: AuthSession<Backend>,
+    state: State<Arc<AppState>>,
+    messages: Messages,
+    Path(user_id): Path<i64>,
+) -> Result<Response, ApiError> {
+    let user = auth_session.user.clone().ok_or(ApiError::Unauthorized)?;
+
+    if !can_manage_sessions(&auth_session, &user).await {
+        return Err(ApiError::Forbidden);
+    }
+
+    let count = state.repos.sessions.revoke_all_for_user(user_id).await?;
+
+    messages.success(format!("Revoked {count} sessions."));
+    Ok(Redirect::to(&format!("/admin/users/{user_id}/sessions")).into_response())
+}
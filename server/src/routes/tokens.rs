@@ -0,0 +1,115 @@
+use std::sync::Arc;
+
+use axum::{
+    extract::{Path, State},
+    response::{IntoResponse, Redirect, Response},
+    Form,
+};
+use axum_htmx::HxBoosted;
+use axum_login::AuthSession;
+use axum_messages::Messages;
+use minijinja::context;
+use serde::Deserialize;
+
+use crate::{
+    api_error::ApiError,
+    api_token::{generate_token, TokenScope},
+    auth::{now, Backend},
+    AppState,
+};
+
+pub async fn tokens(
+    auth_session: AuthSession<Backend>,
+    boosted: HxBoosted,
+    state: State<Arc<AppState>>,
+) -> Result<Response, ApiError> {
+    let Some(user) = auth_session.user else {
+        return Ok(Redirect::to("/login?next=/tokens").into_response());
+    };
+
+    let tokens = state.repos.tokens.find_by_user(user.0.id).await?;
+
+    Ok(state
+        .render_with_context(
+            boosted,
+            "tokens.html",
+            context! {
+                user => user.0,
+                tokens,
+                scopes => TokenScope::ALL.map(|scope| scope.as_str()),
+            },
+        )?
+        .into_response())
+}
+
+// Checkboxes named after the scopes; unchecked ones are absent.
+#[derive(Debug, Deserialize)]
+pub struct NewToken {
+    name: String,
+    read: Option<String>,
+    write: Option<String>,
+}
+
+pub async fn create_token(
+    auth_session: AuthSession<Backend>,
+    boosted: HxBoosted,
+    state: State<Arc<AppState>>,
+    Form(new_token): Form<NewToken>,
+) -> Result<Response, ApiError> {
+    let user = auth_session.user.ok_or(ApiError::Unauthorized)?;
+
+    let mut scopes = vec![];
+    if new_token.read.is_some() {
+        scopes.push(TokenScope::Read.as_str());
+    }
+    if new_token.write.is_some() {
+        scopes.push(TokenScope::Write.as_str());
+    }
+
+    let name = new_token.name.trim();
+    if name.is_empty() {
+        return Err(ApiError::Validation("A token needs a name.".into()));
+    }
+    if scopes.is_empty() {
+        return Err(ApiError::Validation("A token needs at least one scope.".into()));
+    }
+
+    let (token, hash) = generate_token();
+    state
+        .repos
+        .tokens
+        .create(user.0.id, name, &hash, &scopes.join(" "), now())
+        .await?;
+
+    let tokens = state.repos.tokens.find_by_user(user.0.id).await?;
+
+    // The plaintext token is only ever shown here.
+    Ok(state
+        .render_with_context(
+            boosted,
+            "tokens.html",
+            context! {
+                user => user.0,
+                tokens,
+                scopes => TokenScope::ALL.map(|scope| scope.as_str()),
+                new_token => token,
+            },
+        )?
+        .into_response())
+}
+
+pub async fn revoke_token(
+    auth_session: AuthSession<Backend>,
+    state: State<Arc<AppState>>,
+    messages: Messages,
+    Path(id): Path<i64>,
+) -> Result<Redirect, ApiError> {
+    let user = auth_session.user.ok_or(ApiError::Unauthorized)?;
+
+    if !state.repos.tokens.delete(id, user.0.id).await? {
+        return Err(ApiError::NotFound);
+    }
+
+    messages.success("Token revoked.".to_string());
+    Ok(Redirect::to("/tokens"))
+}
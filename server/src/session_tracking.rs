@@ -0,0 +1,85 @@
+use std::{net::SocketAddr, sync::Arc};
+
+use axum::{
+    extract::{ConnectInfo, Request, State},
+    http::header::USER_AGENT,
+    middleware::Next,
+    response::Response,
+};
+use axum_login::AuthSession;
+use db::repo::RepoResult;
+use tower_sessions::{Expiry, Session};
+
+use crate::{
+    auth::{now, Backend},
+    telemetry, AppState,
+};
+
+/// Activity is written at most this often per session, so browsing doesn't
+/// turn every request into a database write.
+const RECORD_INTERVAL_SECS: i64 = 60;
+
+/// Set in the session data of logins with "remember me" checked.
+pub const REMEMBER_ME_KEY: &str = "auth.remember_me";
+
+/// Records the user agent, address and last activity of every authenticated
+/// session so they can be listed and revoked from the sessions page.
+pub async fn track_session(
+    auth_session: AuthSession<Backend>,
+    session: Session,
+    State(state): State<Arc<AppState>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let user_agent = request
+        .headers()
+        .get(USER_AGENT)
+        .and_then(|value| value.to_str().ok())
+        .map(str::to_owned);
+    let ip = request
+        .extensions()
+        .get::<ConnectInfo<SocketAddr>>()
+        .map(|ConnectInfo(addr)| addr.ip().to_string());
+
+    if let Some(user) = &auth_session.user {
+        telemetry::record_user(user.0.id);
+    }
+
+    // Every request starts out with the session layer's expiry, so a
+    // remembered login has to ask for its longer one again before the session
+    // is saved.
+    if let Ok(Some(true)) = session.get::<bool>(REMEMBER_ME_KEY).await {
+        session.set_expiry(Some(Expiry::OnInactivity(state.session_expiry.remember_me)));
+    }
+
+    let response = next.run(request).await;
+
+    // A freshly created session only gets its id once it has been saved, so
+    // it is picked up on the next request.
+    if let (Some(user), Some(id)) = (&auth_session.user, session.id()) {
+        if let Err(e) = record_activity(&state, &id.to_string(), user.0.id, user_agent, ip).await {
+            tracing::warn!("failed to record session activity: {e}");
+        }
+    }
+
+    response
+}
+
+async fn record_activity(
+    state: &AppState,
+    session_id: &str,
+    user_id: i64,
+    user_agent: Option<String>,
+    ip: Option<String>,
+) -> RepoResult<()> {
+    let now = now();
+    let last_seen = state.repos.sessions.last_seen(session_id).await?;
+    if last_seen.is_some_and(|last_seen| now - last_seen < RECORD_INTERVAL_SECS) {
+        return Ok(());
+    }
+    state
+        .repos
+        .sessions
+        .record(session_id, user_id, user_agent.as_deref(), ip.as_deref(), now)
+        .await
+}
@@ -1,4 +1,4 @@
-use std::{ffi::OsStr, sync::Arc};
+use std::sync::Arc;
 
 use axum::{
     extract::{Path, State},
@@ -11,7 +11,7 @@ use axum::{
 };
 use axum_cc::{CacheControlLayer, MimeType};
 use minijinja::Environment;
-use crate::AppState;
+use crate::{embedded::Source, AppState};
 
 pub fn static_file_handler(state: Arc<AppState>) -> Router {
     const PRECOMPRESSED_MIME_TYPES: &[MimeType; 2] = &[MimeType::CSS, MimeType::JS];
@@ -21,7 +21,8 @@ pub fn static_file_handler(state: Arc<AppState>) -> Router {
             "/:file",
             get(
                 |state: State<Arc<AppState>>, path: Path<String>| async move {
-                    let Some(asset) = state.asset_cache.get_from_path(&path) else {
+                    let frontend = state.frontend.get();
+                    let Some(asset) = frontend.assets.get_from_path(&path) else {
                         return StatusCode::NOT_FOUND.into_response();
                     };
 
@@ -48,24 +49,28 @@ pub fn static_file_handler(state: Arc<AppState>) -> Router {
 }
 
 
-pub fn import_templates() -> anyhow::Result<Environment<'static>> {
+pub fn import_templates(source: &Source) -> anyhow::Result<Environment<'static>> {
     let mut env = Environment::new();
+    env.add_filter("datetime", datetime);
 
-    for entry in std::fs::read_dir("server/templates")?.filter_map(Result::ok) {
-        let path = entry.path();
-
-        if path.is_file() && path.extension() == Some(OsStr::new("html")) {
-            let name = path
-                .file_name()
-                .and_then(OsStr::to_str)
-                .ok_or_else(|| anyhow::anyhow!("failed to get filename"))?
-                .to_owned();
-
-            let data = std::fs::read_to_string(&path)?;
-
-            env.add_template_owned(name, data)?;
-        }
+    for (name, data) in source.templates()? {
+        env.add_template_owned(name, data)?;
     }
 
     Ok(env)
-}
\ No newline at end of file
+}
+
+/// Formats a unix timestamp as `YYYY-MM-DD HH:MM UTC`.
+fn datetime(timestamp: i64) -> String {
+    match time::OffsetDateTime::from_unix_timestamp(timestamp) {
+        Ok(t) => format!(
+            "{}-{:02}-{:02} {:02}:{:02} UTC",
+            t.year(),
+            t.month() as u8,
+            t.day(),
+            t.hour(),
+            t.minute()
+        ),
+        Err(_) => timestamp.to_string(),
+    }
+}
@@ -0,0 +1,120 @@
+use std::{future::Future, time::Duration};
+
+use tokio::task::JoinSet;
+use tokio_util::sync::CancellationToken;
+
+/// Longest pause before a failed task is restarted.
+const MAX_BACKOFF: Duration = Duration::from_secs(60);
+
+/// Runs the server's background jobs. Failing jobs are restarted with
+/// exponential backoff, and all of them are told to stop through a shared
+/// [`CancellationToken`] on shutdown.
+pub struct Supervisor {
+    shutdown: CancellationToken,
+    tasks: JoinSet<()>,
+}
+
+impl Default for Supervisor {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Supervisor {
+    pub fn new() -> Self {
+        Self {
+            shutdown: CancellationToken::new(),
+            tasks: JoinSet::new(),
+        }
+    }
+
+    /// The token cancelled when the server shuts down.
+    pub fn token(&self) -> CancellationToken {
+        self.shutdown.clone()
+    }
+
+    /// Runs `job` until it returns `Ok` or the server shuts down. The job gets
+    /// the shutdown token and should return soon after it is cancelled.
+    pub fn spawn<F, Fut>(&mut self, name: &'static str, job: F)
+    where
+        F: Fn(CancellationToken) -> Fut + Send + 'static,
+        Fut: Future<Output = anyhow::Result<()>> + Send + 'static,
+    {
+        let shutdown = self.shutdown.clone();
+
+        self.tasks.spawn(async move {
+            let mut backoff = Duration::from_secs(1);
+            loop {
+                match job(shutdown.clone()).await {
+                    Ok(()) => {
+                        tracing::debug!("{name} finished");
+                        return;
+                    }
+                    Err(e) if shutdown.is_cancelled() => {
+                        tracing::warn!("{name} failed during shutdown: {e:#}");
+                        return;
+                    }
+                    Err(e) => {
+                        tracing::error!("{name} failed, restarting in {backoff:?}: {e:#}");
+                    }
+                }
+
+                tokio::select! {
+                    _ = tokio::time::sleep(backoff) => {}
+                    _ = shutdown.cancelled() => return,
+                }
+                backoff = (backoff * 2).min(MAX_BACKOFF);
+            }
+        });
+    }
+
+    /// Cancels the shutdown token and waits up to `timeout` for the jobs to
+    /// stop, aborting the ones that don't.
+    pub async fn shutdown(mut self, timeout: Duration) {
+        self.shutdown.cancel();
+
+        let drained = tokio::time::timeout(timeout, async {
+            while self.tasks.join_next().await.is_some() {}
+        })
+        .await;
+
+        if drained.is_err() {
+            tracing::warn!(
+                "{} background jobs did not stop within {timeout:?}, aborting them",
+                self.tasks.len()
+            );
+            self.tasks.shutdown().await;
+        }
+    }
+}
+
+/// Resolves on Ctrl+C or, on Unix, SIGTERM.
+pub async fn shutdown_signal() {
+    let ctrl_c = async {
+        if let Err(e) = tokio::signal::ctrl_c().await {
+            tracing::error!("failed to listen for Ctrl+C: {e}");
+            std::future::pending::<()>().await;
+        }
+    };
+
+    #[cfg(unix)]
+    let terminate = async {
+        match tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate()) {
+            Ok(mut signal) => {
+                signal.recv().await;
+            }
+            Err(e) => {
+                tracing::error!("failed to listen for SIGTERM: {e}");
+                std::future::pending::<()>().await;
+            }
+        }
+    };
+
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = ctrl_c => {}
+        _ = terminate => {}
+    }
+}
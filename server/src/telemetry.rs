@@ -0,0 +1,106 @@
+use std::time::Duration;
+
+use axum::{
+    body::Body,
+    extract::MatchedPath,
+    http::{HeaderName, Request, Response},
+};
+use opentelemetry::KeyValue;
+use opentelemetry_otlp::WithExportConfig;
+use opentelemetry_sdk::{runtime, trace, Resource};
+use tracing::{field, Span};
+use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt, EnvFilter};
+
+use crate::config::{LogConfig, LogFormat};
+
+/// Header carrying the request id, taken from the client or proxy when
+/// present and generated otherwise. It is echoed back on every response.
+pub const REQUEST_ID_HEADER: HeaderName = HeaderName::from_static("x-request-id");
+
+/// Flushes exported spans when dropped. Keep it alive until the process
+/// exits.
+pub struct Telemetry {
+    otel: bool,
+}
+
+impl Drop for Telemetry {
+    fn drop(&mut self) {
+        if self.otel {
+            opentelemetry::global::shutdown_tracer_provider();
+        }
+    }
+}
+
+/// Installs the global tracing subscriber: logs in the configured format
+/// and, if `log.otlp_endpoint` is set, span export to an OpenTelemetry
+/// collector. Must be called from within the Tokio runtime.
+pub fn init(config: &LogConfig) -> anyhow::Result<Telemetry> {
+    let otel = match &config.otlp_endpoint {
+        Some(endpoint) => {
+            let tracer = opentelemetry_otlp::new_pipeline()
+                .tracing()
+                .with_exporter(
+                    opentelemetry_otlp::new_exporter()
+                        .tonic()
+                        .with_endpoint(endpoint.as_str()),
+                )
+                .with_trace_config(trace::config().with_resource(Resource::new([KeyValue::new(
+                    "service.name",
+                    config.service_name.clone(),
+                )])))
+                .install_batch(runtime::Tokio)?;
+            Some(tracing_opentelemetry::layer().with_tracer(tracer))
+        }
+        None => None,
+    };
+    let exporting = otel.is_some();
+
+    let (pretty, json) = match config.format {
+        LogFormat::Pretty => (Some(tracing_subscriber::fmt::layer()), None),
+        LogFormat::Json => (None, Some(tracing_subscriber::fmt::layer().json())),
+    };
+
+    tracing_subscriber::registry()
+        .with(EnvFilter::try_new(&config.filter)?)
+        .with(pretty)
+        .with(json)
+        .with(otel)
+        .try_init()?;
+
+    Ok(Telemetry { otel: exporting })
+}
+
+/// The span every request runs in. `user_id` is filled in once the request
+/// has been authenticated and `status` once the response is ready, so every
+/// event logged while handling the request carries them.
+pub fn request_span(request: &Request<Body>) -> Span {
+    let route = request
+        .extensions()
+        .get::<MatchedPath>()
+        .map(MatchedPath::as_str)
+        .unwrap_or("unmatched");
+    let request_id = request
+        .headers()
+        .get(REQUEST_ID_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .unwrap_or_default();
+
+    tracing::info_span!(
+        "request",
+        method = %request.method(),
+        route,
+        request_id,
+        user_id = field::Empty,
+        status = field::Empty,
+    )
+}
+
+pub fn record_response(response: &Response<Body>, latency: Duration, span: &Span) {
+    span.record("status", response.status().as_u16());
+    tracing::debug!(latency_ms = latency.as_millis() as u64, "finished processing request");
+}
+
+/// Attaches the authenticated user to the current request span.
+pub fn record_user(user_id: i64) {
+    Span::current().record("user_id", user_id);
+}
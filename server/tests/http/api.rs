@@ -0,0 +1,17 @@
+use axum::http::StatusCode;
+
+use crate::harness::TestApp;
+
+#[tokio::test]
+async fn huge_page_numbers_are_empty_pages() {
+    let app = TestApp::spawn().await;
+
+    let response = app
+        .client()
+        .get(&format!("/api/v1/threads?page={}&per_page=100", i64::MAX))
+        .await;
+    assert_eq!(response.status, StatusCode::OK, "{}", response.body);
+    let page = response.json();
+    assert_eq!(page["items"].as_array().map(Vec::len), Some(0));
+    assert_eq!(page["page"], i64::MAX / 100);
+}
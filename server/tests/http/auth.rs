@@ -0,0 +1,86 @@
+use axum::http::StatusCode;
+
+use crate::harness::TestApp;
+
+#[tokio::test]
+async fn login_and_logout() {
+    let app = TestApp::spawn().await;
+    app.create_user("alice", "correct horse", &["users"]).await;
+    let mut client = app.client();
+
+    let response = client
+        .post_form("/login", &[("username", "alice"), ("password", "correct horse")])
+        .await;
+    assert_eq!(response.status, StatusCode::SEE_OTHER);
+    assert_eq!(response.location(), Some("/"));
+
+    let page = client.get("/").await;
+    assert!(page.body.contains(r#"<span id="user">alice</span>"#), "{}", page.body);
+    assert!(page.body.contains("Successfully logged in as alice"), "{}", page.body);
+
+    let me = client.get("/api/v1/users/me").await;
+    assert_eq!(me.status, StatusCode::OK);
+    assert_eq!(me.json()["username"], "alice");
+
+    let response = client.get("/logout").await;
+    assert_eq!(response.status, StatusCode::SEE_OTHER);
+
+    let page = client.get("/").await;
+    assert!(!page.body.contains(r#"id="user""#), "{}", page.body);
+    assert_eq!(client.get("/api/v1/users/me").await.status, StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+async fn login_redirects_to_next() {
+    let app = TestApp::spawn().await;
+    app.create_user("alice", "correct horse", &["users"]).await;
+
+    let response = app
+        .client()
+        .post_form(
+            "/login",
+            &[("username", "alice"), ("password", "correct horse"), ("next", "/about")],
+        )
+        .await;
+    assert_eq!(response.status, StatusCode::SEE_OTHER);
+    assert_eq!(response.location(), Some("/about"));
+}
+
+#[tokio::test]
+async fn login_only_redirects_on_this_site() {
+    let app = TestApp::spawn().await;
+    app.create_user("alice", "correct horse", &["users"]).await;
+
+    for next in ["//evil.com", "/\t/evil.com", "/\nfoo", "/\\evil.com", "https://evil.com/"] {
+        let response = app
+            .client()
+            .post_form(
+                "/login",
+                &[("username", "alice"), ("password", "correct horse"), ("next", next)],
+            )
+            .await;
+        assert_eq!(response.status, StatusCode::SEE_OTHER, "{next:?}");
+        assert_eq!(response.location(), Some("/"), "{next:?}");
+    }
+}
+
+#[tokio::test]
+async fn login_rejects_bad_credentials() {
+    let app = TestApp::spawn().await;
+    app.create_user("alice", "correct horse", &["users"]).await;
+    let mut client = app.client();
+
+    let response = client
+        .post_form("/login", &[("username", "alice"), ("password", "battery staple")])
+        .await;
+    assert_eq!(response.status, StatusCode::OK);
+    assert!(response.body.contains("Invalid password"), "{}", response.body);
+
+    let response = client
+        .post_form("/login", &[("username", "mallory"), ("password", "correct horse")])
+        .await;
+    assert_eq!(response.status, StatusCode::OK);
+    assert!(response.body.contains("Username does not exist"), "{}", response.body);
+
+    assert_eq!(client.get("/api/v1/users/me").await.status, StatusCode::UNAUTHORIZED);
+}
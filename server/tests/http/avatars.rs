@@ -0,0 +1,122 @@
+use std::io::Cursor;
+
+use axum::http::StatusCode;
+use image::{ImageFormat, Rgb, RgbImage, Rgba, RgbaImage};
+
+use crate::harness::TestApp;
+
+fn png(image: impl Into<image::DynamicImage>) -> Vec<u8> {
+    let mut png = Vec::new();
+    image
+        .into()
+        .write_to(&mut Cursor::new(&mut png), ImageFormat::Png)
+        .expect("failed to encode the test image");
+    png
+}
+
+#[tokio::test]
+async fn users_without_avatar_get_an_identicon() {
+    let app = TestApp::spawn().await;
+    let alice = app.create_user("alice", "correct horse", &["users"]).await;
+    let mut client = app.client();
+
+    let response = client.get(&format!("/avatars/{}?size=50", alice.id)).await;
+    assert_eq!(response.status, StatusCode::OK);
+    assert_eq!(response.header("content-type"), Some("image/svg+xml"));
+    assert!(response.body.contains(r#"width="64""#), "{}", response.body);
+    assert!(response.header("cache-control").is_some_and(|value| value.starts_with("public")));
+    assert!(response.header("set-cookie").is_none());
+
+    assert_eq!(client.get("/avatars/9999").await.status, StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn avatars_are_revalidated_with_etags() {
+    let app = TestApp::spawn().await;
+    let alice = app.create_user("alice", "correct horse", &["users"]).await;
+    let mut client = app.client();
+    let path = format!("/avatars/{}", alice.id);
+
+    let response = client.get(&path).await;
+    let etag = response.header("etag").expect("avatars have an etag").to_owned();
+
+    let mut client = app.client();
+    let response = client
+        .get_with_headers(&path, &[("if-none-match", &etag)])
+        .await;
+    assert_eq!(response.status, StatusCode::NOT_MODIFIED);
+    assert!(response.body.is_empty());
+
+    let response = client
+        .get_with_headers(&path, &[("if-none-match", "\"stale\"")])
+        .await;
+    assert_eq!(response.status, StatusCode::OK);
+}
+
+#[tokio::test]
+async fn uploads_are_resized_and_replace_the_identicon() {
+    let app = TestApp::spawn().await;
+    let alice = app.create_user("alice", "correct horse", &["users"]).await;
+    let mut client = app.login("alice", "correct horse").await;
+
+    let photo = png(RgbImage::from_pixel(300, 200, Rgb([200, 30, 30])));
+    let response = client.post_file("/profile/avatar", "avatar", "image/png", &photo).await;
+    assert_eq!(response.status, StatusCode::SEE_OTHER, "{}", response.body);
+    assert_eq!(response.location(), Some("/profile"));
+
+    let page = client.get("/profile").await;
+    assert!(page.body.contains("Avatar updated."), "{}", page.body);
+    assert!(page.body.contains("Remove avatar"), "{}", page.body);
+
+    // Without transparency avatars are stored as JPEG.
+    let response = client.get(&format!("/avatars/{}?size=32", alice.id)).await;
+    assert_eq!(response.status, StatusCode::OK);
+    assert_eq!(response.header("content-type"), Some("image/jpeg"));
+
+    let logo = png(RgbaImage::from_pixel(64, 64, Rgba([0, 0, 0, 0])));
+    client.post_file("/profile/avatar", "avatar", "image/png", &logo).await;
+    let response = client.get(&format!("/avatars/{}", alice.id)).await;
+    assert_eq!(response.header("content-type"), Some("image/png"));
+
+    let response = client.post_form("/profile/avatar/delete", &[]).await;
+    assert_eq!(response.status, StatusCode::SEE_OTHER);
+    let response = client.get(&format!("/avatars/{}", alice.id)).await;
+    assert_eq!(response.header("content-type"), Some("image/svg+xml"));
+}
+
+#[tokio::test]
+async fn uploads_must_be_images() {
+    let app = TestApp::spawn().await;
+    app.create_user("alice", "correct horse", &["users"]).await;
+    let mut client = app.login("alice", "correct horse").await;
+
+    let response = client
+        .post_file("/profile/avatar", "avatar", "image/png", b"not an image")
+        .await;
+    assert_eq!(response.status, StatusCode::BAD_REQUEST);
+    assert!(response.body.contains("PNG, JPEG, GIF or WebP"), "{}", response.body);
+
+    let response = app.client().post_file("/profile/avatar", "avatar", "image/png", &[]).await;
+    assert_eq!(response.status, StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+async fn oversized_uploads_are_rejected_with_the_limit() {
+    let app = TestApp::spawn_with(|config| config.avatars.max_upload_kib = 1).await;
+    app.create_user("alice", "correct horse", &["users"]).await;
+    let mut client = app.login("alice", "correct horse").await;
+
+    // Over the limit, but within the room left for the multipart framing.
+    let response = client
+        .post_file("/profile/avatar", "avatar", "image/png", &[0; 2 * 1024])
+        .await;
+    assert_eq!(response.status, StatusCode::PAYLOAD_TOO_LARGE);
+    assert!(response.body.contains("Avatars can be at most 1 KiB"), "{}", response.body);
+
+    // Over the body limit of the route.
+    let response = client
+        .post_file("/profile/avatar", "avatar", "image/png", &[0; 64 * 1024])
+        .await;
+    assert_eq!(response.status, StatusCode::PAYLOAD_TOO_LARGE);
+    assert!(response.body.contains("Avatars can be at most 1 KiB"), "{}", response.body);
+}
@@ -0,0 +1,57 @@
+use axum::http::StatusCode;
+use serde_json::json;
+
+use crate::harness::TestApp;
+
+#[tokio::test]
+async fn drafts_need_a_user() {
+    let app = TestApp::spawn().await;
+
+    let response = app
+        .client()
+        .post_json("/draft", json!({ "title": "Draft", "content": {} }))
+        .await;
+    assert_eq!(response.status, StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+async fn saving_a_draft_again_replaces_it() {
+    let app = TestApp::spawn().await;
+    let alice = app.create_user("alice", "correct horse", &["users"]).await;
+    let mut client = app.login("alice", "correct horse").await;
+
+    let first = json!({ "ops": [{ "insert": "Hello\n" }] });
+    let response = client
+        .post_json("/draft", json!({ "title": "Greeting", "content": first }))
+        .await;
+    assert_eq!(response.status, StatusCode::OK, "{}", response.body);
+
+    let second = json!({ "ops": [{ "insert": "Hello, world\n" }] });
+    let response = client
+        .post_json("/draft", json!({ "title": "Greeting", "content": second }))
+        .await;
+    assert_eq!(response.status, StatusCode::OK, "{}", response.body);
+
+    let articles = client
+        .get(&format!("/api/v1/articles?user_id={}", alice.id))
+        .await
+        .json();
+    assert_eq!(articles["total"], 1);
+    assert_eq!(articles["items"][0]["title"], "Greeting");
+    assert_eq!(articles["items"][0]["editor_content"], second);
+}
+
+#[tokio::test]
+async fn drafts_need_the_write_scope() {
+    let app = TestApp::spawn().await;
+    let alice = app.create_user("alice", "correct horse", &["users"]).await;
+    let draft = json!({ "title": "Draft", "content": {} });
+
+    let mut reader = app.token_client(&alice, "read").await;
+    let response = reader.post_json("/draft", draft.clone()).await;
+    assert_eq!(response.status, StatusCode::FORBIDDEN);
+
+    let mut writer = app.token_client(&alice, "read write").await;
+    let response = writer.post_json("/draft", draft).await;
+    assert_eq!(response.status, StatusCode::OK, "{}", response.body);
+}
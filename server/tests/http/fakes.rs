@@ -0,0 +1,70 @@
+use axum::http::StatusCode;
+use serde_json::json;
+
+use crate::harness::MemoryApp;
+
+/// What the `users` and `superusers` groups grant in a migrated database.
+const OWN: &[&str] = &["edit_own_post", "delete_own_post"];
+const ANY: &[&str] = &["edit_any_post", "delete_any_post"];
+
+#[tokio::test]
+async fn permissions_are_checked_against_the_repositories() {
+    let app = MemoryApp::spawn().await;
+    app.create_user("alice", "correct horse", OWN);
+    app.create_user("bob", "battery staple", OWN);
+    app.create_user("root", "hunter2", ANY);
+    let category = app.repository.add_category("General", None);
+
+    let mut alice = app.login("alice", "correct horse").await;
+    let mut bob = app.login("bob", "battery staple").await;
+    let mut root = app.login("root", "hunter2").await;
+
+    let response = alice
+        .post_json("/api/v1/threads", json!({ "category_id": category.id, "title": "Hello" }))
+        .await;
+    assert_eq!(response.status, StatusCode::CREATED, "{}", response.body);
+    let thread = format!("/api/v1/threads/{}", response.json()["id"]);
+
+    let response = bob.patch_json(&thread, json!({ "title": "Hijacked" })).await;
+    assert_eq!(response.status, StatusCode::FORBIDDEN);
+
+    let response = alice.patch_json(&thread, json!({ "title": "Hello again" })).await;
+    assert_eq!(response.status, StatusCode::OK, "{}", response.body);
+
+    assert_eq!(root.delete(&thread).await.status, StatusCode::NO_CONTENT);
+    assert_eq!(alice.get(&thread).await.status, StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn tokens_search_and_avatars_go_through_the_repositories() {
+    let app = MemoryApp::spawn().await;
+    let alice = app.create_user("alice", "correct horse", OWN);
+    let category = app.repository.add_category("General", None);
+
+    let mut client = app.token_client(&alice, "read write").await;
+    let response = client.get("/api/v1/users/me").await;
+    assert_eq!(response.status, StatusCode::OK, "{}", response.body);
+    assert_eq!(response.json()["username"], "alice");
+
+    let response = client
+        .post_json("/api/v1/threads", json!({ "category_id": category.id, "title": "Gardening" }))
+        .await;
+    assert_eq!(response.status, StatusCode::CREATED, "{}", response.body);
+    let post = json!({
+        "thread_id": response.json()["id"],
+        "title": "Tomatoes",
+        "content": "<p>Grow <em>tomatoes</em> in pots</p>",
+    });
+    let response = client.post_json("/api/v1/posts", post).await;
+    assert_eq!(response.status, StatusCode::CREATED, "{}", response.body);
+
+    let response = app.client().get("/search?q=TOMATOES&author=alice").await;
+    assert_eq!(response.status, StatusCode::OK, "{}", response.body);
+    assert!(response.body.contains("<li>Tomatoes: Grow tomatoes in pots</li>"), "{}", response.body);
+
+    let response = app.client().get(&format!("/avatars/{}", alice.id)).await;
+    assert_eq!(response.status, StatusCode::OK);
+    assert_eq!(response.header("content-type"), Some("image/svg+xml"));
+    let response = app.client().get("/avatars/999").await;
+    assert_eq!(response.status, StatusCode::NOT_FOUND);
+}
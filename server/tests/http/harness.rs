@@ -0,0 +1,432 @@
+use std::{collections::HashMap, net::SocketAddr, sync::Arc};
+
+use axum::{
+    body::Body,
+    http::{header, HeaderMap, HeaderValue, Method, Request, StatusCode},
+    Router,
+};
+use db::{
+    api_token::DbApiToken,
+    repo::{MemoryRepository, Repositories, TokenRepo},
+    sqlx,
+    user::DbUser,
+    DbPool,
+};
+use serde_json::Value;
+use server::{config::Config, embedded::Source, router_with_repositories, Server};
+use sha2::{Digest, Sha256};
+use tempfile::TempDir;
+use tower::ServiceExt;
+use url::form_urlencoded;
+
+/// A page template that extends the layout unless it is rendered for a
+/// boosted request, like the real ones.
+macro_rules! page {
+    ($main:expr) => {
+        concat!(
+            r#"{% extends "_base.html" if base is defined else "_partial.html" %}"#,
+            "{% block main %}",
+            $main,
+            "{% endblock %}"
+        )
+    };
+}
+
+/// Stand-ins for the real templates. They use the same layout switch, so a
+/// page rendered with the layout starts with a doctype and a boosted one
+/// doesn't, and keep the markup to what the tests look for.
+const TEMPLATES: &[(&str, &str)] = &[
+    (
+        "_base.html",
+        r#"<!DOCTYPE html>
+<html>
+<head>
+<link rel="stylesheet" href="/{{ base.styles }}">
+{% if editor %}<link rel="stylesheet" href="/{{ editor.styles }}">{% endif %}
+</head>
+<body>
+{% if user %}<span id="user">{{ user.username }}</span>{% endif %}
+{% for message in success_messages %}<p class="message">{{ message.m }}</p>{% endfor %}
+<main id="content">{% block main %}{% endblock %}</main>
+</body>
+</html>"#,
+    ),
+    ("_partial.html", "{% block main %}{% endblock %}"),
+    ("index.html", page!("<h1>Index</h1>")),
+    ("about.html", page!("<h1>About</h1>")),
+    ("register.html", page!("<h1>Register</h1>")),
+    (
+        "login.html",
+        page!(
+            "<h1>Login</h1>{{ message }}{{ username_error }}{{ password_error }}\
+             <input name=\"next\" value=\"{{ next or '' }}\">"
+        ),
+    ),
+    ("404.html", page!("<h1>Not found</h1>")),
+    ("profile.html", page!("<h1>Profile</h1>{% if has_avatar %}<p>Remove avatar</p>{% endif %}")),
+    ("error.html", page!("<h1>{{ problem.title }}</h1><p>{{ problem.detail }}</p>")),
+    (
+        "search.html",
+        page!("{% for hit in hits %}<li>{{ hit.title }}: {{ hit.snippet }}</li>{% endfor %}"),
+    ),
+];
+
+/// The assets the layouts link to. Their contents don't matter.
+const ASSETS: &[&str] = &[
+    "index.css",
+    "index.js",
+    "snow.css",
+    "quill.js",
+    "lexical.css",
+    "lexical_editor.js",
+];
+
+fn source() -> Source {
+    Source::Memory {
+        templates: TEMPLATES
+            .iter()
+            .map(|(name, source)| (name.to_string(), source.to_string()))
+            .collect(),
+        assets: ASSETS
+            .iter()
+            .map(|name| (name.to_string(), format!("/* {name} */").into_bytes()))
+            .collect(),
+    }
+}
+
+/// A [`Server`] with a database of its own, removed when the app is dropped.
+pub struct TestApp {
+    pub db: DbPool,
+    router: Router,
+    // Keeps the background jobs running.
+    _server: Server,
+    _dir: TempDir,
+}
+
+impl TestApp {
+    pub async fn spawn() -> Self {
+        Self::spawn_with(|_| {}).await
+    }
+
+    /// Like [`TestApp::spawn`], with `configure` applied to the default
+    /// config first.
+    pub async fn spawn_with(configure: impl FnOnce(&mut Config)) -> Self {
+        let dir = tempfile::tempdir().expect("failed to create a temporary directory");
+
+        let mut config = Config::default();
+        config.database.url = format!("sqlite:{}", dir.path().join("forum.db").display());
+        config.server.listen = SocketAddr::from(([127, 0, 0, 1], 0));
+        configure(&mut config);
+
+        let server = Server::with_source(config, source())
+            .await
+            .expect("failed to start the server");
+
+        Self {
+            db: server.state.get_db(),
+            router: server.router(),
+            _server: server,
+            _dir: dir,
+        }
+    }
+
+    /// A client without a session.
+    pub fn client(&self) -> Client {
+        Client {
+            router: self.router.clone(),
+            cookies: HashMap::new(),
+            token: None,
+        }
+    }
+
+    /// A client logged in as `username`.
+    pub async fn login(&self, username: &str, password: &str) -> Client {
+        self.client().login(username, password).await
+    }
+
+    /// A client authenticating with a new API token of `user` instead of a
+    /// session.
+    pub async fn token_client(&self, user: &DbUser, scopes: &str) -> Client {
+        let (token, hash) = test_token(user, scopes);
+        DbApiToken::create(user.id, "test", &hash, scopes, 0, &self.db)
+            .await
+            .expect("failed to create an API token");
+
+        let mut client = self.client();
+        client.token = Some(token);
+        client
+    }
+
+    /// Creates a user and adds it to `groups`, e.g. `users` or `superusers`.
+    pub async fn create_user(&self, username: &str, password: &str, groups: &[&str]) -> DbUser {
+        let hash = password_auth::generate_hash(password);
+        let user = DbUser::create(username, &hash, &self.db)
+            .await
+            .expect("failed to create a user");
+
+        for group in groups {
+            sqlx::query("INSERT INTO users_groups (user_id, group_id) SELECT $1, id FROM groups WHERE name = $2")
+                .bind(user.id)
+                .bind(group)
+                .execute(&self.db)
+                .await
+                .expect("failed to add a user to a group");
+        }
+        user
+    }
+
+    pub async fn create_category(&self, title: &str) -> i64 {
+        sqlx::query_scalar("INSERT INTO categories (title) VALUES ($1) RETURNING id")
+            .bind(title)
+            .fetch_one(&self.db)
+            .await
+            .expect("failed to create a category")
+    }
+}
+
+/// Every route over [`Repositories::memory`] instead of a database, for
+/// tests of handlers that don't need SQL. Seed it through `repository`.
+pub struct MemoryApp {
+    pub repository: Arc<MemoryRepository>,
+    router: Router,
+}
+
+impl MemoryApp {
+    pub async fn spawn() -> Self {
+        Self::spawn_with(|_| {}).await
+    }
+
+    /// Like [`MemoryApp::spawn`], with `configure` applied to the default
+    /// config first.
+    pub async fn spawn_with(configure: impl FnOnce(&mut Config)) -> Self {
+        let mut config = Config::default();
+        configure(&mut config);
+
+        let repository = Arc::new(MemoryRepository::default());
+        let router = router_with_repositories(
+            config,
+            source(),
+            Repositories::memory(repository.clone()),
+        )
+        .await
+        .expect("failed to build the router");
+
+        Self { repository, router }
+    }
+
+    /// A client without a session.
+    pub fn client(&self) -> Client {
+        Client {
+            router: self.router.clone(),
+            cookies: HashMap::new(),
+            token: None,
+        }
+    }
+
+    /// A client logged in as `username`.
+    pub async fn login(&self, username: &str, password: &str) -> Client {
+        self.client().login(username, password).await
+    }
+
+    /// A client authenticating with a new API token of `user` instead of a
+    /// session.
+    pub async fn token_client(&self, user: &DbUser, scopes: &str) -> Client {
+        let (token, hash) = test_token(user, scopes);
+        TokenRepo::create(&*self.repository, user.id, "test", &hash, scopes, 0)
+            .await
+            .expect("failed to create an API token");
+
+        let mut client = self.client();
+        client.token = Some(token);
+        client
+    }
+
+    /// Creates a user with `permissions`, e.g. `edit_own_post`. Groups don't
+    /// carry permissions in memory, so they are granted directly.
+    pub fn create_user(&self, username: &str, password: &str, permissions: &[&str]) -> DbUser {
+        let hash = password_auth::generate_hash(password);
+        let user = self.repository.add_user(username, &hash);
+        for permission in permissions {
+            self.repository.grant(user.id, permission);
+        }
+        user
+    }
+}
+
+/// A token for `user` and the hash it is stored under.
+fn test_token(user: &DbUser, scopes: &str) -> (String, String) {
+    let token = format!("ffp_test_{}_{}", user.id, scopes.replace(' ', "_"));
+    let hash = format!("{:x}", Sha256::digest(token.as_bytes()));
+    (token, hash)
+}
+
+/// Sends requests through the router like a browser would: cookies set by a
+/// response are sent with the following requests, redirects are not followed.
+pub struct Client {
+    router: Router,
+    cookies: HashMap<String, String>,
+    token: Option<String>,
+}
+
+impl Client {
+    async fn login(mut self, username: &str, password: &str) -> Client {
+        let response = self
+            .post_form("/login", &[("username", username), ("password", password)])
+            .await;
+        assert_eq!(response.status, StatusCode::SEE_OTHER, "login failed: {}", response.body);
+        self
+    }
+
+    pub async fn get(&mut self, path: &str) -> TestResponse {
+        self.send(Method::GET, path, &[], Body::empty()).await
+    }
+
+    pub async fn get_with_headers(&mut self, path: &str, headers: &[(&str, &str)]) -> TestResponse {
+        self.send(Method::GET, path, headers, Body::empty()).await
+    }
+
+    /// A GET the way htmx sends it for boosted links.
+    pub async fn get_boosted(&mut self, path: &str) -> TestResponse {
+        self.send(Method::GET, path, &[("hx-boosted", "true")], Body::empty())
+            .await
+    }
+
+    pub async fn post_form(&mut self, path: &str, fields: &[(&str, &str)]) -> TestResponse {
+        let body = form_urlencoded::Serializer::new(String::new())
+            .extend_pairs(fields)
+            .finish();
+        let headers = [(header::CONTENT_TYPE.as_str(), "application/x-www-form-urlencoded")];
+        self.send(Method::POST, path, &headers, Body::from(body)).await
+    }
+
+    /// A multipart form with a single file field, like a browser upload.
+    pub async fn post_file(
+        &mut self,
+        path: &str,
+        field: &str,
+        content_type: &str,
+        file: &[u8],
+    ) -> TestResponse {
+        const BOUNDARY: &str = "test-boundary";
+        let mut body = format!(
+            "--{BOUNDARY}\r\nContent-Disposition: form-data; name=\"{field}\"; filename=\"upload\"\r\nContent-Type: {content_type}\r\n\r\n"
+        )
+        .into_bytes();
+        body.extend_from_slice(file);
+        body.extend_from_slice(format!("\r\n--{BOUNDARY}--\r\n").as_bytes());
+
+        let content_type = format!("multipart/form-data; boundary={BOUNDARY}");
+        let headers = [(header::CONTENT_TYPE.as_str(), content_type.as_str())];
+        self.send(Method::POST, path, &headers, Body::from(body)).await
+    }
+
+    pub async fn post_json(&mut self, path: &str, body: Value) -> TestResponse {
+        self.send_json(Method::POST, path, body).await
+    }
+
+    pub async fn patch_json(&mut self, path: &str, body: Value) -> TestResponse {
+        self.send_json(Method::PATCH, path, body).await
+    }
+
+    pub async fn delete(&mut self, path: &str) -> TestResponse {
+        self.send(Method::DELETE, path, &[], Body::empty()).await
+    }
+
+    async fn send_json(&mut self, method: Method, path: &str, body: Value) -> TestResponse {
+        let headers = [(header::CONTENT_TYPE.as_str(), "application/json")];
+        self.send(method, path, &headers, Body::from(body.to_string()))
+            .await
+    }
+
+    async fn send(
+        &mut self,
+        method: Method,
+        path: &str,
+        headers: &[(&str, &str)],
+        body: Body,
+    ) -> TestResponse {
+        let mut request = Request::builder().method(method).uri(path);
+        for (name, value) in headers {
+            request = request.header(*name, *value);
+        }
+        if !self.cookies.is_empty() {
+            let cookies: Vec<_> = self
+                .cookies
+                .iter()
+                .map(|(name, value)| format!("{name}={value}"))
+                .collect();
+            request = request.header(header::COOKIE, cookies.join("; "));
+        }
+        if let Some(token) = &self.token {
+            request = request.header(header::AUTHORIZATION, format!("Bearer {token}"));
+        }
+
+        let response = self
+            .router
+            .clone()
+            .oneshot(request.body(body).expect("invalid request"))
+            .await
+            .expect("the router is infallible");
+
+        for cookie in response.headers().get_all(header::SET_COOKIE) {
+            self.store_cookie(cookie);
+        }
+
+        let status = response.status();
+        let headers = response.headers().clone();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .expect("failed to read the response body");
+
+        TestResponse {
+            status,
+            headers,
+            // Images aren't text, tests only look at their headers.
+            body: String::from_utf8_lossy(&body).into_owned(),
+        }
+    }
+
+    fn store_cookie(&mut self, cookie: &HeaderValue) {
+        let cookie = cookie.to_str().expect("cookie is not ascii");
+        let mut attributes = cookie.split(';').map(str::trim);
+        let Some((name, value)) = attributes.next().and_then(|pair| pair.split_once('=')) else {
+            return;
+        };
+
+        let removed = value.is_empty()
+            || attributes.any(|attribute| attribute.eq_ignore_ascii_case("max-age=0"));
+        if removed {
+            self.cookies.remove(name);
+        } else {
+            self.cookies.insert(name.to_owned(), value.to_owned());
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct TestResponse {
+    pub status: StatusCode,
+    pub headers: HeaderMap,
+    pub body: String,
+}
+
+impl TestResponse {
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers.get(name).and_then(|value| value.to_str().ok())
+    }
+
+    pub fn location(&self) -> Option<&str> {
+        self.header(header::LOCATION.as_str())
+    }
+
+    pub fn json(&self) -> Value {
+        serde_json::from_str(&self.body)
+            .unwrap_or_else(|e| panic!("response is not JSON ({e}): {}", self.body))
+    }
+
+    /// Whether the page was rendered with `_base.html` rather than as a
+    /// fragment.
+    pub fn is_full_page(&self) -> bool {
+        self.body.starts_with("<!DOCTYPE html>")
+    }
+}
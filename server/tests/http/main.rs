@@ -0,0 +1,21 @@
+//! Drives the whole router the way a browser or API client would: sessions,
+//! middleware, rendering and the database, each test against a SQLite
+//! database of its own and the in-memory templates from [`harness`]. The
+//! tests in [`fakes`] use the in-memory repositories instead.
+
+// The harness builds SQLite URLs.
+#![cfg(not(feature = "postgres"))]
+
+mod api;
+mod auth;
+mod avatars;
+mod drafts;
+mod fakes;
+mod harness;
+mod mock_idp;
+mod oidc;
+mod passkeys;
+mod permissions;
+mod rendering;
+mod search;
+mod sessions;
@@ -0,0 +1,159 @@
+use std::{
+    collections::HashMap,
+    net::SocketAddr,
+    sync::{Arc, Mutex},
+};
+
+use axum::{extract::State, routing::{get, post}, Form, Json, Router};
+use chrono::{Duration, Utc};
+use openidconnect::{
+    core::{
+        CoreGenderClaim, CoreJsonWebKeySet, CoreJsonWebKeyType, CoreJweContentEncryptionAlgorithm,
+        CoreJwsSigningAlgorithm, CoreProviderMetadata, CoreResponseType, CoreRsaPrivateSigningKey,
+        CoreSubjectIdentifierType,
+    },
+    AdditionalClaims, Audience, AuthUrl, EmptyAdditionalProviderMetadata, EndUserUsername,
+    IdToken, IdTokenClaims, IssuerUrl, JsonWebKeyId, JsonWebKeySetUrl, Nonce, PrivateSigningKey,
+    ResponseTypes, StandardClaims, SubjectIdentifier, TokenUrl,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use tokio::net::TcpListener;
+
+const SIGNING_KEY: &str = include_str!("fixtures/oidc_signing_key.pem");
+pub const CLIENT_ID: &str = "forum";
+
+/// The claims the identity provider asserts for an authorization code.
+#[derive(Debug, Clone)]
+pub struct Identity {
+    pub subject: &'static str,
+    pub username: &'static str,
+    pub groups: Vec<&'static str>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct GroupClaims {
+    groups: Vec<String>,
+}
+
+impl AdditionalClaims for GroupClaims {}
+
+type GroupIdToken = IdToken<
+    GroupClaims,
+    CoreGenderClaim,
+    CoreJweContentEncryptionAlgorithm,
+    CoreJwsSigningAlgorithm,
+    CoreJsonWebKeyType,
+>;
+
+struct IdpState {
+    issuer: IssuerUrl,
+    key: CoreRsaPrivateSigningKey,
+    /// Authorization codes, with the identity and nonce they are issued for.
+    codes: Mutex<HashMap<String, (Identity, Nonce)>>,
+}
+
+/// A minimal OpenID Connect provider on a local port: discovery, keys and
+/// the token endpoint. Users never visit it, tests call
+/// [`MockIdp::authorize`] instead of logging in at its authorization
+/// endpoint.
+pub struct MockIdp {
+    pub issuer: String,
+    state: Arc<IdpState>,
+}
+
+impl MockIdp {
+    pub async fn spawn() -> Self {
+        let listener = TcpListener::bind(SocketAddr::from(([127, 0, 0, 1], 0)))
+            .await
+            .expect("failed to bind the identity provider");
+        let issuer = format!("http://{}", listener.local_addr().unwrap());
+
+        let state = Arc::new(IdpState {
+            issuer: IssuerUrl::new(issuer.clone()).unwrap(),
+            key: CoreRsaPrivateSigningKey::from_pem(
+                SIGNING_KEY,
+                Some(JsonWebKeyId::new("test".into())),
+            )
+            .expect("invalid signing key"),
+            codes: Mutex::new(HashMap::new()),
+        });
+        let router = Router::new()
+            .route("/.well-known/openid-configuration", get(discovery))
+            .route("/jwks", get(jwks))
+            .route("/token", post(token))
+            .with_state(state.clone());
+        tokio::spawn(async move { axum::serve(listener, router).await });
+
+        Self { issuer, state }
+    }
+
+    /// Issues `code` for `identity`, as if the user had logged in after being
+    /// sent to the authorization URL with `nonce`.
+    pub fn authorize(&self, code: &str, identity: Identity, nonce: &str) {
+        self.state
+            .codes
+            .lock()
+            .unwrap()
+            .insert(code.to_owned(), (identity, Nonce::new(nonce.to_owned())));
+    }
+}
+
+async fn discovery(State(state): State<Arc<IdpState>>) -> Json<CoreProviderMetadata> {
+    let url = |path: &str| format!("{}{path}", state.issuer.as_str());
+    let metadata = CoreProviderMetadata::new(
+        state.issuer.clone(),
+        AuthUrl::new(url("/authorize")).unwrap(),
+        JsonWebKeySetUrl::new(url("/jwks")).unwrap(),
+        vec![ResponseTypes::new(vec![CoreResponseType::Code])],
+        vec![CoreSubjectIdentifierType::Public],
+        vec![CoreJwsSigningAlgorithm::RsaSsaPkcs1V15Sha256],
+        EmptyAdditionalProviderMetadata {},
+    )
+    .set_token_endpoint(Some(TokenUrl::new(url("/token")).unwrap()));
+    Json(metadata)
+}
+
+async fn jwks(State(state): State<Arc<IdpState>>) -> Json<CoreJsonWebKeySet> {
+    Json(CoreJsonWebKeySet::new(vec![state.key.as_verification_key()]))
+}
+
+async fn token(
+    State(state): State<Arc<IdpState>>,
+    Form(form): Form<HashMap<String, String>>,
+) -> Json<Value> {
+    let code = form.get("code").map(String::as_str).unwrap_or_default();
+    let (identity, nonce) = state
+        .codes
+        .lock()
+        .unwrap()
+        .remove(code)
+        .expect("unknown authorization code");
+
+    let claims = IdTokenClaims::new(
+        state.issuer.clone(),
+        vec![Audience::new(CLIENT_ID.into())],
+        Utc::now() + Duration::minutes(5),
+        Utc::now(),
+        StandardClaims::new(SubjectIdentifier::new(identity.subject.into()))
+            .set_preferred_username(Some(EndUserUsername::new(identity.username.into()))),
+        GroupClaims {
+            groups: identity.groups.iter().map(|group| group.to_string()).collect(),
+        },
+    )
+    .set_nonce(Some(nonce));
+    let id_token = GroupIdToken::new(
+        claims,
+        &state.key,
+        CoreJwsSigningAlgorithm::RsaSsaPkcs1V15Sha256,
+        None,
+        None,
+    )
+    .expect("failed to sign the id token");
+
+    Json(json!({
+        "access_token": "access",
+        "token_type": "Bearer",
+        "id_token": id_token.to_string(),
+    }))
+}
@@ -0,0 +1,185 @@
+use std::collections::HashMap;
+
+use axum::http::StatusCode;
+use db::{group::DbGroup, user::DbUser};
+use server::config::{Config, OidcConfig};
+use url::Url;
+
+use crate::{
+    harness::{Client, MemoryApp, TestApp},
+    mock_idp::{Identity, MockIdp, CLIENT_ID},
+};
+
+async fn spawn(idp: &MockIdp) -> TestApp {
+    TestApp::spawn_with(configure(idp, "http://localhost:3000")).await
+}
+
+/// Like [`spawn`], but handlers go through in-memory repositories.
+async fn spawn_memory(idp: &MockIdp, public_url: &str) -> MemoryApp {
+    MemoryApp::spawn_with(configure(idp, public_url)).await
+}
+
+fn configure(idp: &MockIdp, public_url: &str) -> impl FnOnce(&mut Config) {
+    let issuer_url = idp.issuer.clone();
+    let public_url = Url::parse(public_url).unwrap();
+    move |config| {
+        config.server.public_url = public_url;
+        config.oidc = Some(OidcConfig {
+            provider: "mock".into(),
+            issuer_url,
+            client_id: CLIENT_ID.into(),
+            client_secret: Some("secret".into()),
+            redirect_url: None,
+            scopes: vec!["profile".into()],
+            groups_claim: "groups".into(),
+            group_map: HashMap::from([("idp-admins".into(), "superusers".into())]),
+        });
+    }
+}
+
+/// Starts a login at `/login/oidc`, lets `identity` log in at the identity
+/// provider and follows its redirect back to the callback.
+async fn log_in(client: &mut Client, idp: &MockIdp, code: &str, identity: Identity, next: &str) -> String {
+    let response = client.get(&format!("/login/oidc?next={next}")).await;
+    assert_eq!(response.status, StatusCode::SEE_OTHER, "{}", response.body);
+    let authorize = Url::parse(response.location().unwrap()).unwrap();
+    assert!(authorize.as_str().starts_with(&idp.issuer));
+    let param = |name: &str| {
+        authorize
+            .query_pairs()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value.into_owned())
+            .unwrap_or_else(|| panic!("no {name} in {authorize}"))
+    };
+
+    idp.authorize(code, identity, &param("nonce"));
+    let response = client
+        .get(&format!("/login/oidc/callback?code={code}&state={}", param("state")))
+        .await;
+    assert_eq!(response.status, StatusCode::SEE_OTHER, "{}", response.body);
+    response.location().unwrap().to_owned()
+}
+
+async fn group_names(user_id: i64, app: &TestApp) -> Vec<String> {
+    let mut names: Vec<_> = DbGroup::find_by_user(user_id, &app.db)
+        .await
+        .unwrap()
+        .into_iter()
+        .map(|group| group.name)
+        .collect();
+    names.sort();
+    names
+}
+
+#[tokio::test]
+async fn first_login_provisions_a_user_with_mapped_groups() {
+    let idp = MockIdp::spawn().await;
+    let app = spawn(&idp).await;
+    // Taken by a local account, so the new user gets another name.
+    app.create_user("carol", "correct horse", &["users"]).await;
+
+    let carol = Identity {
+        subject: "carol-at-idp",
+        username: "carol",
+        groups: vec!["idp-admins", "idp-staff"],
+    };
+    let mut client = app.client();
+    let location = log_in(&mut client, &idp, "code-1", carol.clone(), "/about").await;
+    assert_eq!(location, "/about");
+
+    let me = client.get("/api/v1/users/me").await;
+    assert_eq!(me.status, StatusCode::OK);
+    assert_eq!(me.json()["username"], "carol2");
+    let user = DbUser::find_by_username("carol2", &app.db).await.unwrap().unwrap();
+    assert_eq!(group_names(user.id, &app).await, ["superusers", "users"]);
+
+    // The next login finds the linked user and drops groups the identity
+    // provider no longer asserts.
+    let carol = Identity { groups: vec![], ..carol };
+    let mut client = app.client();
+    log_in(&mut client, &idp, "code-2", carol, "/").await;
+    assert_eq!(client.get("/api/v1/users/me").await.json()["username"], "carol2");
+    assert_eq!(group_names(user.id, &app).await, ["users"]);
+}
+
+#[tokio::test]
+async fn first_login_against_the_repositories() {
+    let idp = MockIdp::spawn().await;
+    let app = spawn_memory(&idp, "http://localhost:3000").await;
+    app.repository.add_group("users");
+    app.repository.add_group("superusers");
+
+    let erin = Identity {
+        subject: "erin-at-idp",
+        username: "erin",
+        groups: vec!["idp-admins"],
+    };
+    let mut client = app.client();
+    log_in(&mut client, &idp, "code-1", erin.clone(), "/").await;
+    let me = client.get("/api/v1/users/me").await;
+    assert_eq!(me.status, StatusCode::OK);
+    assert_eq!(me.json()["username"], "erin");
+    let id = me.json()["id"].as_i64().unwrap();
+    assert_eq!(app.repository.group_names(id), ["superusers", "users"]);
+
+    let erin = Identity { groups: vec![], ..erin };
+    let mut client = app.client();
+    log_in(&mut client, &idp, "code-2", erin, "/").await;
+    assert_eq!(client.get("/api/v1/users/me").await.json()["id"], id);
+    assert_eq!(app.repository.group_names(id), ["users"]);
+}
+
+#[tokio::test]
+async fn next_must_stay_on_the_site() {
+    let idp = MockIdp::spawn().await;
+    let app = spawn_memory(&idp, "http://localhost:3000").await;
+
+    for (i, next) in [
+        "//evil.example",
+        "https://evil.example/",
+        "/\\evil.example",
+        "/\t/evil.example",
+        "/\nfoo",
+    ]
+    .into_iter()
+    .enumerate()
+    {
+        let identity = Identity {
+            subject: "dave-at-idp",
+            username: "dave",
+            groups: vec![],
+        };
+        let next = url::form_urlencoded::byte_serialize(next.as_bytes()).collect::<String>();
+        let location = log_in(&mut app.client(), &idp, &format!("code-{i}"), identity, &next).await;
+        assert_eq!(location, "/");
+    }
+}
+
+#[tokio::test]
+async fn callback_state_must_match() {
+    let idp = MockIdp::spawn().await;
+    let app = spawn_memory(&idp, "http://localhost:3000").await;
+    let mut client = app.client();
+
+    let response = client.get("/login/oidc/callback?code=code&state=forged").await;
+    assert_eq!(response.status, StatusCode::BAD_REQUEST);
+
+    client.get("/login/oidc").await;
+    let response = client.get("/login/oidc/callback?code=code&state=forged").await;
+    assert_eq!(response.status, StatusCode::BAD_REQUEST);
+    assert_eq!(client.get("/api/v1/users/me").await.status, StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+async fn callback_defaults_to_the_public_url() {
+    let idp = MockIdp::spawn().await;
+    let app = spawn_memory(&idp, "https://forum.example").await;
+
+    let response = app.client().get("/login/oidc").await;
+    let authorize = Url::parse(response.location().unwrap()).unwrap();
+    let redirect_uri = authorize
+        .query_pairs()
+        .find(|(key, _)| key == "redirect_uri")
+        .map(|(_, value)| value.into_owned());
+    assert_eq!(redirect_uri.as_deref(), Some("https://forum.example/login/oidc/callback"));
+}
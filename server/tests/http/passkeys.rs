@@ -0,0 +1,75 @@
+use axum::http::StatusCode;
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use db::passkey::DbPasskey;
+use serde_json::{json, Value};
+use url::Url;
+use webauthn_authenticator_rs::{softpasskey::SoftPasskey, WebauthnAuthenticator};
+use webauthn_rs::prelude::{CreationChallengeResponse, RequestChallengeResponse};
+
+use crate::harness::TestApp;
+
+/// The origin the default config serves WebAuthn for.
+const ORIGIN: &str = "http://localhost:3000";
+
+#[tokio::test]
+async fn register_and_log_in_with_a_passkey() {
+    let app = TestApp::spawn().await;
+    let alice = app.create_user("alice", "correct horse", &["users"]).await;
+    let origin = Url::parse(ORIGIN).unwrap();
+    let mut authenticator = WebauthnAuthenticator::new(SoftPasskey::new());
+
+    let mut client = app.login("alice", "correct horse").await;
+    let response = client.post_json("/passkeys/register/start", json!({})).await;
+    assert_eq!(response.status, StatusCode::OK, "{}", response.body);
+    let challenge: CreationChallengeResponse = serde_json::from_str(&response.body).unwrap();
+    let credential = authenticator
+        .do_registration(origin.clone(), challenge)
+        .expect("the soft authenticator failed to register");
+    let credential_id = serde_json::to_value(&credential).unwrap()["id"].clone();
+
+    let response = client
+        .post_json(
+            "/passkeys/register/finish",
+            json!({ "name": "Laptop", "credential": credential }),
+        )
+        .await;
+    assert_eq!(response.status, StatusCode::CREATED, "{}", response.body);
+    let passkeys = DbPasskey::find_by_user(alice.id, &app.db).await.unwrap();
+    assert_eq!(passkeys.len(), 1);
+    assert_eq!(passkeys[0].name, "Laptop");
+
+    // A new browser, without a session.
+    let mut client = app.client();
+    let response = client.post_json("/login/passkey/start", json!({})).await;
+    assert_eq!(response.status, StatusCode::OK, "{}", response.body);
+    let mut challenge: Value = serde_json::from_str(&response.body).unwrap();
+    // Browsers find discoverable credentials by the relying party alone, the
+    // soft authenticator has to be told which one to use.
+    challenge["publicKey"]["allowCredentials"] =
+        json!([{ "type": "public-key", "id": credential_id }]);
+    let challenge: RequestChallengeResponse = serde_json::from_value(challenge).unwrap();
+    let assertion = authenticator
+        .do_authentication(origin, challenge)
+        .expect("the soft authenticator failed to sign in");
+
+    // Discoverable credentials return the user handle they were registered
+    // with, which the soft authenticator doesn't keep. It isn't signed.
+    let mut assertion = serde_json::to_value(assertion).unwrap();
+    if assertion["response"]["userHandle"].is_null() {
+        let user_handle = [[0; 8], (alice.id as u64).to_be_bytes()].concat();
+        assertion["response"]["userHandle"] = json!(URL_SAFE_NO_PAD.encode(user_handle));
+    }
+
+    let response = client.post_json("/login/passkey/finish", assertion.clone()).await;
+    assert_eq!(response.status, StatusCode::NO_CONTENT, "{}", response.body);
+    let me = client.get("/api/v1/users/me").await;
+    assert_eq!(me.status, StatusCode::OK);
+    assert_eq!(me.json()["username"], "alice");
+
+    let passkeys = DbPasskey::find_by_user(alice.id, &app.db).await.unwrap();
+    assert!(passkeys[0].last_used_at.is_some());
+
+    // The challenge was used up by the first login.
+    let response = app.client().post_json("/login/passkey/finish", assertion).await;
+    assert_eq!(response.status, StatusCode::BAD_REQUEST);
+}
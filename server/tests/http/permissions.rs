@@ -0,0 +1,65 @@
+use axum::http::StatusCode;
+use serde_json::json;
+
+use crate::harness::TestApp;
+
+#[tokio::test]
+async fn anonymous_users_cannot_write() {
+    let app = TestApp::spawn().await;
+    let category_id = app.create_category("General").await;
+
+    let response = app
+        .client()
+        .post_json("/api/v1/threads", json!({ "category_id": category_id, "title": "Hi" }))
+        .await;
+    assert_eq!(response.status, StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+async fn only_owners_and_superusers_modify_threads() {
+    let app = TestApp::spawn().await;
+    app.create_user("alice", "correct horse", &["users"]).await;
+    app.create_user("bob", "battery staple", &["users"]).await;
+    app.create_user("root", "hunter2", &["users", "superusers"]).await;
+    let category_id = app.create_category("General").await;
+
+    let mut alice = app.login("alice", "correct horse").await;
+    let mut bob = app.login("bob", "battery staple").await;
+    let mut root = app.login("root", "hunter2").await;
+
+    let response = alice
+        .post_json("/api/v1/threads", json!({ "category_id": category_id, "title": "Hello" }))
+        .await;
+    assert_eq!(response.status, StatusCode::CREATED, "{}", response.body);
+    let thread = format!("/api/v1/threads/{}", response.json()["id"]);
+
+    let response = bob.patch_json(&thread, json!({ "title": "Hijacked" })).await;
+    assert_eq!(response.status, StatusCode::FORBIDDEN);
+    assert_eq!(bob.delete(&thread).await.status, StatusCode::FORBIDDEN);
+
+    let response = alice.patch_json(&thread, json!({ "title": "Hello again" })).await;
+    assert_eq!(response.status, StatusCode::OK, "{}", response.body);
+    assert_eq!(response.json()["title"], "Hello again");
+
+    let response = root.patch_json(&thread, json!({ "title": "Moderated" })).await;
+    assert_eq!(response.status, StatusCode::OK, "{}", response.body);
+
+    assert_eq!(root.delete(&thread).await.status, StatusCode::NO_CONTENT);
+    assert_eq!(alice.get(&thread).await.status, StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn read_only_tokens_cannot_write() {
+    let app = TestApp::spawn().await;
+    let alice = app.create_user("alice", "correct horse", &["users"]).await;
+    let category_id = app.create_category("General").await;
+
+    let mut client = app.token_client(&alice, "read").await;
+    let response = client
+        .post_json("/api/v1/threads", json!({ "category_id": category_id, "title": "Hi" }))
+        .await;
+    assert_eq!(response.status, StatusCode::FORBIDDEN);
+
+    // Reads are fine.
+    assert_eq!(client.get("/api/v1/threads").await.status, StatusCode::OK);
+}
@@ -0,0 +1,77 @@
+use axum::http::StatusCode;
+use serde_json::json;
+
+use crate::harness::TestApp;
+
+#[tokio::test]
+async fn pages_render_with_layout_unless_boosted() {
+    let app = TestApp::spawn().await;
+    let mut client = app.client();
+
+    // `render`, without a context.
+    let full = client.get("/register").await;
+    assert_eq!(full.status, StatusCode::OK);
+    assert!(full.is_full_page(), "{}", full.body);
+    assert!(full.body.contains(r#"href="/assets/index.css""#), "{}", full.body);
+    assert!(full.body.contains("<h1>Register</h1>"));
+
+    let boosted = client.get_boosted("/register").await;
+    assert_eq!(boosted.status, StatusCode::OK);
+    assert!(!boosted.is_full_page(), "{}", boosted.body);
+    assert!(boosted.body.contains("<h1>Register</h1>"));
+
+    // `render_with_context`.
+    let full = client.get("/login?next=/about").await;
+    assert!(full.is_full_page(), "{}", full.body);
+    assert!(full.body.contains(r#"value="/about""#), "{}", full.body);
+
+    let boosted = client.get_boosted("/login?next=/about").await;
+    assert!(!boosted.is_full_page(), "{}", boosted.body);
+    assert!(boosted.body.contains(r#"value="/about""#), "{}", boosted.body);
+}
+
+#[tokio::test]
+async fn editor_pages_link_editor_assets() {
+    let app = TestApp::spawn().await;
+    let mut client = app.client();
+
+    let full = client.get("/about").await;
+    assert!(full.is_full_page(), "{}", full.body);
+    assert!(full.body.contains(r#"href="/assets/snow.css""#), "{}", full.body);
+
+    let boosted = client.get_boosted("/about").await;
+    assert!(!boosted.is_full_page(), "{}", boosted.body);
+    assert!(!boosted.body.contains("snow.css"), "{}", boosted.body);
+    assert!(boosted.body.contains("<h1>About</h1>"));
+
+    let asset = client.get("/assets/snow.css").await;
+    assert_eq!(asset.status, StatusCode::OK);
+}
+
+#[tokio::test]
+async fn error_pages_follow_the_request() {
+    let app = TestApp::spawn().await;
+    let mut client = app.client();
+
+    let full = client.get("/no-such-page").await;
+    assert_eq!(full.status, StatusCode::NOT_FOUND);
+    assert!(full.is_full_page(), "{}", full.body);
+    assert!(full.body.contains("<h1>Not found</h1>"));
+
+    let boosted = client.get_boosted("/no-such-page").await;
+    assert_eq!(boosted.status, StatusCode::NOT_FOUND);
+    assert!(!boosted.is_full_page(), "{}", boosted.body);
+    assert_eq!(boosted.header("hx-retarget"), Some("#content"));
+
+    // There is no `401.html`, so the generic page is used.
+    let unauthorized = client
+        .post_json("/draft", json!({ "title": "Draft", "content": {} }))
+        .await;
+    assert_eq!(unauthorized.status, StatusCode::UNAUTHORIZED);
+    assert!(unauthorized.body.contains("<h1>Unauthorized</h1>"), "{}", unauthorized.body);
+
+    let api = client.get("/api/v1/threads/1").await;
+    assert_eq!(api.status, StatusCode::NOT_FOUND);
+    assert_eq!(api.header("content-type"), Some("application/problem+json"));
+    assert_eq!(api.json()["status"], 404);
+}
@@ -0,0 +1,74 @@
+use axum::http::StatusCode;
+use db::{post::Post, sqlx, thread::Thread};
+
+use crate::harness::TestApp;
+
+/// The `<li>` of each hit on the search page for `query`.
+async fn search(app: &TestApp, query: &str) -> Vec<String> {
+    let response = app.client().get(&format!("/search?q={query}")).await;
+    assert_eq!(response.status, StatusCode::OK, "{}", response.body);
+    response
+        .body
+        .split("<li>")
+        .skip(1)
+        .map(|hit| hit.split("</li>").next().unwrap_or_default().to_owned())
+        .collect()
+}
+
+#[tokio::test]
+async fn markup_is_not_searched() {
+    let app = TestApp::spawn().await;
+    let alice = app.create_user("alice", "correct horse", &["users"]).await;
+    let category = app.create_category("General").await;
+    let thread = Thread::create(category, alice.id, "Formatting".into(), &app.db)
+        .await
+        .unwrap();
+    Post::create(
+        thread.id,
+        alice.id,
+        "Formatting".into(),
+        r#"<p>Some <strong>bold</strong> text<br>on &quot;two&quot; lines</p>"#.into(),
+        &app.db,
+    )
+    .await
+    .unwrap();
+
+    assert!(search(&app, "strong").await.is_empty());
+    assert!(search(&app, "br").await.is_empty());
+    assert_eq!(
+        search(&app, "bold").await,
+        ["Formatting: Some <mark>bold</mark> text on &quot;two&quot; lines"]
+    );
+}
+
+#[tokio::test]
+async fn posts_from_before_the_text_column_are_indexed_on_migration() {
+    let app = TestApp::spawn().await;
+    let alice = app.create_user("alice", "correct horse", &["users"]).await;
+    let category = app.create_category("General").await;
+    let thread = Thread::create(category, alice.id, "Old".into(), &app.db)
+        .await
+        .unwrap();
+    // Written the way posts were before `search_text` existed.
+    sqlx::query("INSERT INTO posts (thread_id, user_id, title, content) VALUES ($1, $2, 'Old', '<em>ancient</em> history')")
+        .bind(thread.id)
+        .bind(alice.id)
+        .execute(&app.db)
+        .await
+        .unwrap();
+    assert!(search(&app, "ancient").await.is_empty());
+
+    db::migrate(app.db.acquire().await.unwrap()).await.unwrap();
+    assert_eq!(search(&app, "ancient").await, ["Old: <mark>ancient</mark> history"]);
+}
+
+#[tokio::test]
+async fn huge_page_numbers_are_empty_pages() {
+    let app = TestApp::spawn().await;
+
+    let response = app
+        .client()
+        .get(&format!("/search?q=anything&page={}", i64::MAX))
+        .await;
+    assert_eq!(response.status, StatusCode::OK, "{}", response.body);
+}
@@ -0,0 +1,104 @@
+use axum::http::{header::SET_COOKIE, StatusCode};
+use db::{session::DbSession, sqlx};
+
+use crate::harness::{TestApp, TestResponse};
+
+const DAY: i64 = 24 * 60 * 60;
+
+/// The Max-Age of the session cookie set by `response`, if it set one.
+fn cookie_max_age(response: &TestResponse) -> Option<i64> {
+    response
+        .headers
+        .get_all(SET_COOKIE)
+        .iter()
+        .filter_map(|cookie| cookie.to_str().ok())
+        .flat_map(|cookie| cookie.split(';'))
+        .find_map(|attribute| attribute.trim().strip_prefix("Max-Age="))
+        .and_then(|max_age| max_age.parse().ok())
+}
+
+async fn last_seen(user_id: i64, app: &TestApp) -> Vec<i64> {
+    sqlx::query_scalar("SELECT last_seen_at FROM user_sessions WHERE user_id = $1")
+        .bind(user_id)
+        .fetch_all(&app.db)
+        .await
+        .unwrap()
+}
+
+#[tokio::test]
+async fn activity_is_recorded_at_most_once_a_minute() {
+    let app = TestApp::spawn().await;
+    let alice = app.create_user("alice", "correct horse", &["users"]).await;
+    let mut client = app.login("alice", "correct horse").await;
+
+    // The session gets its id when the login response saves it.
+    client.get("/").await;
+    let recorded = last_seen(alice.id, &app).await;
+    assert_eq!(recorded.len(), 1);
+
+    sqlx::query("UPDATE user_sessions SET last_seen_at = last_seen_at - 30")
+        .execute(&app.db)
+        .await
+        .unwrap();
+    client.get("/").await;
+    assert_eq!(last_seen(alice.id, &app).await, [recorded[0] - 30]);
+
+    sqlx::query("UPDATE user_sessions SET last_seen_at = last_seen_at - 60")
+        .execute(&app.db)
+        .await
+        .unwrap();
+    client.get("/").await;
+    assert!(last_seen(alice.id, &app).await[0] >= recorded[0]);
+}
+
+#[tokio::test]
+async fn revoked_sessions_are_not_recorded_again() {
+    let app = TestApp::spawn().await;
+    let alice = app.create_user("alice", "correct horse", &["users"]).await;
+    let mut laptop = app.login("alice", "correct horse").await;
+    let mut phone = app.login("alice", "correct horse").await;
+    laptop.get("/").await;
+    phone.get("/").await;
+    assert_eq!(last_seen(alice.id, &app).await.len(), 2);
+
+    let response = laptop.post_form("/sessions/revoke-all", &[]).await;
+    assert_eq!(response.status, StatusCode::SEE_OTHER);
+    assert!(last_seen(alice.id, &app).await.is_empty());
+    assert_eq!(DbSession::count_active(0, &app.db).await.unwrap().1, 0);
+
+    // The phone's session is gone from the store, so it is logged out.
+    phone.get("/").await;
+    assert!(last_seen(alice.id, &app).await.is_empty());
+    assert_eq!(phone.get("/api/v1/users/me").await.status, StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+async fn remember_me_outlives_later_requests() {
+    let app = TestApp::spawn().await;
+    app.create_user("alice", "correct horse", &["users"]).await;
+    app.create_user("bob", "battery staple", &["users"]).await;
+
+    let mut alice = app.client();
+    alice
+        .post_form(
+            "/login",
+            &[("username", "alice"), ("password", "correct horse"), ("remember_me", "on")],
+        )
+        .await;
+    let mut bob = app.login("bob", "battery staple").await;
+
+    // Showing the login message saves the session again.
+    let response = alice.get("/").await;
+    let max_age = cookie_max_age(&response).expect("the session was saved");
+    assert!(max_age > 29 * DAY, "max-age {max_age}");
+    let response = bob.get("/").await;
+    let max_age = cookie_max_age(&response).expect("the session was saved");
+    assert!(max_age <= 60 * 60, "max-age {max_age}");
+
+    alice.get("/about").await;
+    let expiry: i64 = sqlx::query_scalar("SELECT MAX(expiry_date) FROM tower_sessions")
+        .fetch_one(&app.db)
+        .await
+        .unwrap();
+    assert!(expiry > time::OffsetDateTime::now_utc().unix_timestamp() + 29 * DAY);
+}
@@ -0,0 +1,266 @@
+//! Fails when the generated OpenAPI document drifts from the committed
+//! contract in `openapi.snapshot`, or from the routes the API actually
+//! serves. The snapshot lists every operation with its parameters, bodies
+//! and status codes and every schema with its typed properties, so a
+//! renamed route, a new error case or a changed model shows up in review.
+//!
+//! After an intended API change, regenerate it with
+//! `UPDATE_OPENAPI=1 cargo test -p server --test openapi`.
+
+use std::collections::BTreeSet;
+
+use serde_json::Value;
+
+const SNAPSHOT: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/openapi.snapshot");
+const API_SOURCE: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/src/api/mod.rs");
+
+fn spec() -> Value {
+    serde_json::to_value(server::openapi()).expect("failed to serialize the OpenAPI document")
+}
+
+fn keys(value: &Value) -> Vec<String> {
+    let mut keys: Vec<String> = value
+        .as_object()
+        .map(|object| object.keys().cloned().collect())
+        .unwrap_or_default();
+    keys.sort();
+    keys
+}
+
+/// A short name for a schema: `integer(int64)`, `[Thread]`, `string | null`.
+fn type_name(schema: &Value) -> String {
+    let name = if let Some(reference) = schema["$ref"].as_str() {
+        reference.rsplit('/').next().unwrap_or(reference).to_owned()
+    } else if let Some(variants) = schema["allOf"].as_array() {
+        variants.iter().map(type_name).collect::<Vec<_>>().join(" & ")
+    } else if let Some(variants) = schema["oneOf"].as_array().or(schema["anyOf"].as_array()) {
+        variants.iter().map(type_name).collect::<Vec<_>>().join(" | ")
+    } else {
+        match (schema["type"].as_str(), schema["format"].as_str()) {
+            (Some("array"), _) => format!("[{}]", type_name(&schema["items"])),
+            (Some(kind), Some(format)) => format!("{kind}({format})"),
+            (Some(kind), None) => kind.to_owned(),
+            (None, _) => "any".to_owned(),
+        }
+    };
+    if schema["nullable"] == true {
+        format!("{name} | null")
+    } else {
+        name
+    }
+}
+
+/// Summarizes the parts of the spec that make up the contract with clients:
+/// operations with their parameters, bodies and status codes, and schemas
+/// with the types of their properties. Optional parameters and properties
+/// are marked with `?`.
+fn contract(spec: &Value) -> String {
+    let mut lines = vec![];
+
+    if let Some(paths) = spec["paths"].as_object() {
+        for (path, operations) in paths {
+            for method in keys(operations) {
+                let operation = &operations[&method];
+                let name = format!("{} {}", method.to_uppercase(), path);
+                let statuses = keys(&operation["responses"]);
+                lines.push(format!("{name} {}", statuses.join(" ")));
+
+                for parameter in operation["parameters"].as_array().into_iter().flatten() {
+                    lines.push(format!(
+                        "{name} {} {}{}: {}",
+                        parameter["in"].as_str().unwrap_or_default(),
+                        parameter["name"].as_str().unwrap_or_default(),
+                        if parameter["required"] == true { "" } else { "?" },
+                        type_name(&parameter["schema"]),
+                    ));
+                }
+                let body = &operation["requestBody"]["content"]["application/json"]["schema"];
+                if !body.is_null() {
+                    lines.push(format!("{name} body: {}", type_name(body)));
+                }
+                for status in statuses {
+                    let body = &operation["responses"][&status]["content"]["application/json"]["schema"];
+                    if !body.is_null() {
+                        lines.push(format!("{name} {status}: {}", type_name(body)));
+                    }
+                }
+            }
+        }
+    }
+
+    if let Some(schemas) = spec["components"]["schemas"].as_object() {
+        for (name, schema) in schemas {
+            let required: Vec<&str> = schema["required"]
+                .as_array()
+                .into_iter()
+                .flatten()
+                .filter_map(Value::as_str)
+                .collect();
+            lines.push(format!("schema {name} {}", type_name(schema)));
+            for property in keys(&schema["properties"]) {
+                lines.push(format!(
+                    "schema {name}.{property}{}: {}",
+                    if required.contains(&property.as_str()) { "" } else { "?" },
+                    type_name(&schema["properties"][&property]),
+                ));
+            }
+        }
+    }
+
+    lines.sort();
+    lines.join("\n") + "\n"
+}
+
+/// The operations `api::router()` registers, as `METHOD /api/v1/path` with
+/// the path parameters spelled like in the spec. Axum can't list the routes
+/// of a router, so they are read from its source.
+fn router_operations() -> BTreeSet<String> {
+    let source = std::fs::read_to_string(API_SOURCE).expect("failed to read the API module");
+    let start = source.find("pub fn router()").expect("no router() in the API module");
+    let body = &source[start..];
+    let body = &body[..body.find("\n}\n").expect("router() doesn't end")];
+    assert!(
+        !body.contains(".nest(") && !body.contains(".merge("),
+        "router() nests or merges routers, which this test doesn't follow"
+    );
+
+    let mut operations = BTreeSet::new();
+    let mut rest = body;
+    while let Some(start) = rest.find(".route(") {
+        let args = balanced(&rest[start + ".route".len()..]);
+        rest = &rest[start + ".route".len() + args.len()..];
+
+        let path = args.split('"').nth(1).expect("route without a path literal");
+        // The document itself isn't part of the API it describes.
+        if path == "/openapi.json" {
+            continue;
+        }
+        let path = path
+            .split('/')
+            .map(|segment| match segment.strip_prefix(':') {
+                Some(param) => format!("{{{param}}}"),
+                None => segment.to_owned(),
+            })
+            .collect::<Vec<_>>()
+            .join("/");
+        for method in top_level_calls(&args[1..args.len() - 1]) {
+            if ["get", "post", "put", "patch", "delete"].contains(&method) {
+                operations.insert(format!("{} /api/v1{path}", method.to_uppercase()));
+            }
+        }
+    }
+    operations
+}
+
+/// The parenthesized group `source` starts with, parentheses included.
+fn balanced(source: &str) -> &str {
+    let mut depth = 0;
+    let mut in_string = false;
+    for (index, c) in source.char_indices() {
+        match c {
+            '"' => in_string = !in_string,
+            '(' if !in_string => depth += 1,
+            ')' if !in_string => {
+                depth -= 1;
+                if depth == 0 {
+                    return &source[..=index];
+                }
+            }
+            _ => {}
+        }
+    }
+    panic!("unbalanced parentheses in {source}");
+}
+
+/// Names of the functions called outside of any parentheses in `args`, like
+/// `get` and `post` in `"/x", get(a::list).post(a::create)`.
+fn top_level_calls(args: &str) -> Vec<&str> {
+    let mut calls = vec![];
+    let mut depth = 0;
+    let mut in_string = false;
+    let mut ident_start = None;
+    for (index, c) in args.char_indices() {
+        if c == '"' {
+            in_string = !in_string;
+        }
+        if in_string {
+            continue;
+        }
+        match c {
+            c if c.is_alphanumeric() || c == '_' => {
+                ident_start.get_or_insert(index);
+            }
+            '(' => {
+                if let (0, Some(start)) = (depth, ident_start) {
+                    if !args[..start].ends_with("::") {
+                        calls.push(&args[start..index]);
+                    }
+                }
+                depth += 1;
+                ident_start = None;
+            }
+            ')' => {
+                depth -= 1;
+                ident_start = None;
+            }
+            _ => ident_start = None,
+        }
+    }
+    calls
+}
+
+fn spec_operations(spec: &Value) -> BTreeSet<String> {
+    let mut operations = BTreeSet::new();
+    for (path, methods) in spec["paths"].as_object().expect("spec has no paths") {
+        for method in keys(methods) {
+            operations.insert(format!("{} {path}", method.to_uppercase()));
+        }
+    }
+    operations
+}
+
+#[test]
+fn every_route_is_documented() {
+    let routes = router_operations();
+    let documented = spec_operations(&spec());
+
+    let undocumented: Vec<_> = routes.difference(&documented).collect();
+    let unrouted: Vec<_> = documented.difference(&routes).collect();
+    assert!(
+        undocumented.is_empty() && unrouted.is_empty(),
+        "routes missing from the OpenAPI document: {undocumented:?}\n\
+         documented operations without a route: {unrouted:?}"
+    );
+}
+
+#[test]
+fn openapi_matches_snapshot() {
+    let contract = contract(&spec());
+
+    if std::env::var_os("UPDATE_OPENAPI").is_some() {
+        std::fs::write(SNAPSHOT, &contract).expect("failed to write openapi.snapshot");
+        return;
+    }
+
+    let committed = std::fs::read_to_string(SNAPSHOT).expect("failed to read openapi.snapshot");
+    assert!(
+        committed == contract,
+        "the OpenAPI document no longer matches openapi.snapshot, rerun with UPDATE_OPENAPI=1 \
+         if the change is intended\n\nexpected:\n{committed}\nactual:\n{contract}"
+    );
+}
+
+#[test]
+fn user_schema_does_not_expose_password() {
+    let spec = spec();
+    let schemas = spec["components"]["schemas"]
+        .as_object()
+        .expect("spec has no schemas");
+
+    for (name, schema) in schemas {
+        assert!(
+            schema["properties"].get("password").is_none(),
+            "schema {name} exposes a password"
+        );
+    }
+}